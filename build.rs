@@ -0,0 +1,5 @@
+// Embedded with include_dir, which cargo doesn't know to watch
+fn main() {
+    println!("cargo:rerun-if-changed=public");
+    println!("cargo:rerun-if-changed=migrations");
+}
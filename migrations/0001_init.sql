@@ -0,0 +1,103 @@
+CREATE TABLE IF NOT EXISTS "user" (
+    username TEXT NOT NULL,
+    email TEXT NOT NULL UNIQUE,
+    password TEXT NOT NULL,
+    date TEXT NOT NULL,
+    deleted INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS request (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_email TEXT NOT NULL,
+    command TEXT NOT NULL,
+    status TEXT NOT NULL,
+    method TEXT NOT NULL,
+    date TEXT NOT NULL,
+    hidden INTEGER NOT NULL,
+    status_message TEXT,
+    status_details TEXT,
+    headers TEXT,
+    trailers TEXT,
+    total_ms REAL,
+    connect_ms REAL,
+    first_byte_ms REAL,
+    response BLOB,
+    response_size INTEGER,
+    response_truncated INTEGER,
+    resolved_command TEXT,
+    FOREIGN KEY (user_email) REFERENCES "user"(email)
+);
+
+CREATE TABLE IF NOT EXISTS proto (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_email TEXT NOT NULL,
+    target TEXT NOT NULL,
+    name TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    content BLOB NOT NULL,
+    date TEXT NOT NULL,
+    UNIQUE (user_email, target, name),
+    FOREIGN KEY (user_email) REFERENCES "user"(email)
+);
+
+CREATE TABLE IF NOT EXISTS environment (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_email TEXT NOT NULL,
+    name TEXT NOT NULL,
+    active INTEGER NOT NULL,
+    date TEXT NOT NULL,
+    UNIQUE (user_email, name),
+    FOREIGN KEY (user_email) REFERENCES "user"(email)
+);
+
+CREATE TABLE IF NOT EXISTS environment_variable (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    environment_id INTEGER NOT NULL,
+    key TEXT NOT NULL,
+    value TEXT NOT NULL,
+    secret INTEGER,
+    UNIQUE (environment_id, key),
+    FOREIGN KEY (environment_id) REFERENCES environment(id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS collection (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_email TEXT NOT NULL,
+    parent_id INTEGER,
+    name TEXT NOT NULL,
+    position INTEGER NOT NULL,
+    date TEXT NOT NULL,
+    FOREIGN KEY (user_email) REFERENCES "user"(email),
+    FOREIGN KEY (parent_id) REFERENCES collection(id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS saved_request (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    collection_id INTEGER NOT NULL,
+    title TEXT NOT NULL,
+    description TEXT,
+    command TEXT NOT NULL,
+    position INTEGER NOT NULL,
+    date TEXT NOT NULL,
+    FOREIGN KEY (collection_id) REFERENCES collection(id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS favorite (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_email TEXT NOT NULL,
+    request_id INTEGER NOT NULL,
+    position INTEGER NOT NULL,
+    date TEXT NOT NULL,
+    UNIQUE (user_email, request_id),
+    FOREIGN KEY (user_email) REFERENCES "user"(email),
+    FOREIGN KEY (request_id) REFERENCES request(id) ON DELETE CASCADE
+);
+
+INSERT OR IGNORE INTO "user" (username, email, password, date, deleted)
+VALUES (
+    'anon',
+    'anon',
+    'anon',
+    strftime('%s', 'now'),
+    0
+);
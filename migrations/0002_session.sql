@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS session (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_email TEXT NOT NULL,
+    token_hash TEXT NOT NULL UNIQUE,
+    expires INTEGER NOT NULL,
+    revoked INTEGER NOT NULL DEFAULT false,
+    date TEXT NOT NULL,
+    FOREIGN KEY (user_email) REFERENCES "user"(email)
+);
+
+CREATE INDEX IF NOT EXISTS session_user_email ON session (user_email);
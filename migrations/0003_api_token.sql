@@ -0,0 +1,14 @@
+CREATE TABLE IF NOT EXISTS api_token (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_email TEXT NOT NULL,
+    name TEXT NOT NULL,
+    token_hash TEXT NOT NULL UNIQUE,
+    scopes TEXT NOT NULL,
+    expires INTEGER,
+    last_used INTEGER,
+    revoked INTEGER NOT NULL DEFAULT false,
+    date TEXT NOT NULL,
+    FOREIGN KEY (user_email) REFERENCES "user"(email)
+);
+
+CREATE INDEX IF NOT EXISTS api_token_user_email ON api_token (user_email);
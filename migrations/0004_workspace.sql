@@ -0,0 +1,19 @@
+CREATE TABLE IF NOT EXISTS workspace (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    date TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS workspace_member (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    workspace_id INTEGER NOT NULL,
+    user_email TEXT NOT NULL,
+    role TEXT NOT NULL CHECK (role IN ('owner', 'editor', 'viewer')),
+    active INTEGER NOT NULL DEFAULT false,
+    date TEXT NOT NULL,
+    UNIQUE (workspace_id, user_email),
+    FOREIGN KEY (workspace_id) REFERENCES workspace(id) ON DELETE CASCADE,
+    FOREIGN KEY (user_email) REFERENCES "user"(email)
+);
+
+CREATE INDEX IF NOT EXISTS workspace_member_user_email ON workspace_member (user_email);
@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS proto_build (
+    user_email TEXT NOT NULL,
+    target TEXT NOT NULL,
+    protoset BLOB NOT NULL,
+    PRIMARY KEY (user_email, target),
+    FOREIGN KEY (user_email) REFERENCES "user"(email)
+);
@@ -1,19 +1,82 @@
 const currentPage = window.location.pathname;
 
-const parseJwt = (token) => {
+// Global
+// The session itself lives in HttpOnly cookies, the page only keeps who's logged in
+function currentProfile() {
+    const profile = localStorage.getItem("profile");
 
-    const base64Url = token.split(".")[1];
-    const base64 = base64Url.replace(/-/g, "+").replace(/_/g, "/");
-    const jsonPayload = decodeURIComponent(window.atob(base64).split("").map(function(c) {
-        return "%" + ("00" + c.charCodeAt(0).toString(16)).slice(-2);
-    }).join(""));
+    return profile ? JSON.parse(profile) : null;
+};
 
-    return JSON.parse(JSON.parse(jsonPayload).sub);
+function storeSession(profile) {
+    localStorage.setItem("profile", JSON.stringify(profile));
+    localStorage.setItem("refreshed", Date.now());
 };
 
-// Global
-function logout() {
-    localStorage.removeItem("auth");
+function clearSession() {
+    localStorage.removeItem("profile");
+    localStorage.removeItem("refreshed");
+};
+
+function readCookie(name) {
+    const cookie = document.cookie.split("; ").find((pair) => pair.startsWith(`${name}=`));
+
+    return cookie ? decodeURIComponent(cookie.split("=")[1]) : null;
+};
+
+// Session cookies expire after a few minutes, swap them for a new pair before they do
+async function refreshSession() {
+    if (!currentProfile()) {
+        return;
+    };
+
+    if (Date.now() - Number(localStorage.getItem("refreshed")) < 600000) {
+        return;
+    };
+
+    const refreshReq = await fetch("/api/user/refresh", { method: "POST" });
+
+    if (refreshReq.ok) {
+        storeSession(await refreshReq.json());
+    } else {
+        clearSession();
+    };
+};
+
+setInterval(refreshSession, 60000);
+
+// The browser sends the session cookie, anything that changes data also echoes the csrf cookie
+function authHeaders(headers = {}) {
+    const csrf = readCookie("gurl_csrf");
+
+    return csrf ? { ...headers, "X-CSRF-Token": csrf } : headers;
+};
+
+function apiFetch(url, options = {}) {
+    return fetch(url, { ...options, headers: authHeaders(options.headers) });
+};
+
+document.addEventListener("htmx:configRequest", (e) => {
+    e.detail.headers = authHeaders(e.detail.headers);
+});
+
+// Data routes follow the workspace picked in the navbar, no id is the user's own data
+async function switchWorkspace(id) {
+    const switchReq = await apiFetch("/api/workspace/active", {
+        method: "PUT",
+        headers: { "Content-Type": "application/json" },
+        body: JSON.stringify({ "id": id ? Number(id) : null })
+    });
+
+    if (switchReq.ok) {
+        window.location.reload();
+    };
+};
+
+async function logout() {
+    await fetch("/api/user/logout", { method: "POST" });
+
+    clearSession();
     window.location.href = "/";
 };
 
@@ -27,6 +90,25 @@ function hideShortcuts() {
     document.getElementById("shortcuts-toggle").src = "/public/show.webp";
 };
 
+// Error bodies from /api are {"error": "..."}
+async function errorMessage(response) {
+    const text = await response.text();
+
+    try {
+        return JSON.parse(text).error ?? text;
+    } catch {
+        return text;
+    };
+};
+
+// /handle and /grpcurl errors are html like any other output, htmx only swaps 2xx by default
+document.addEventListener("htmx:beforeSwap", (e) => {
+    if (e.detail.xhr.status >= 400) {
+        e.detail.shouldSwap = true;
+        e.detail.isError = false;
+    };
+});
+
 function toggleShortcuts() {
     const showShortcuts = localStorage.getItem("shortcuts");
 
@@ -48,6 +130,16 @@ const shortuctKeys = {
     "viewFavorites": "KeyV",
     "hideRequest": "KeyD",
     "closeModal": "KeyQ",
+    "sendMessage": "KeyM",
+    "endStream": "KeyE",
+    "cancelRequest": "KeyC",
+    "viewResponse": "KeyR",
+    "skeleton": "KeyB",
+    "complete": "KeyA",
+    "environment": "KeyN",
+    "viewCollections": "KeyO",
+    "moveUp": "ArrowUp",
+    "moveDown": "ArrowDown",
     "home": "KeyH",
     "login": "KeyL",
     "signup": "KeyS",
@@ -64,6 +156,16 @@ const homeKeys = [
     shortuctKeys["viewFavorites"],
     shortuctKeys["hideRequest"],
     shortuctKeys["closeModal"],
+    shortuctKeys["sendMessage"],
+    shortuctKeys["endStream"],
+    shortuctKeys["cancelRequest"],
+    shortuctKeys["viewResponse"],
+    shortuctKeys["skeleton"],
+    shortuctKeys["complete"],
+    shortuctKeys["environment"],
+    shortuctKeys["viewCollections"],
+    shortuctKeys["moveUp"],
+    shortuctKeys["moveDown"],
     shortuctKeys["login"],
     shortuctKeys["signup"],
     shortuctKeys["profile"],
@@ -88,7 +190,7 @@ const profileKeys = [
 
 const executeHomeShortcuts = (shortcut) => {
 
-    const loggedIn = localStorage.getItem("auth");
+    const loggedIn = currentProfile();
 
     if (shortcut === shortuctKeys["selectRequest"]) {
         fillForm();
@@ -105,6 +207,27 @@ const executeHomeShortcuts = (shortcut) => {
     } else if (shortcut === shortuctKeys["closeModal"]) {
         document.getElementById("history-modal").style.display = "none";
         document.getElementById("favorites-modal").style.display = "none";
+        document.getElementById("collections-modal").style.display = "none";
+    } else if (shortcut === shortuctKeys["sendMessage"]) {
+        sendStreamMessage();
+    } else if (shortcut === shortuctKeys["endStream"]) {
+        closeStream();
+    } else if (shortcut === shortuctKeys["cancelRequest"]) {
+        cancelRequest();
+    } else if (shortcut === shortuctKeys["viewResponse"]) {
+        viewResponse();
+    } else if (shortcut === shortuctKeys["skeleton"]) {
+        generateSkeleton();
+    } else if (shortcut === shortuctKeys["complete"]) {
+        focusCompletions();
+    } else if (shortcut === shortuctKeys["environment"]) {
+        cycleEnvironment();
+    } else if (shortcut === shortuctKeys["viewCollections"]) {
+        toggleCollectionsList();
+    } else if (shortcut === shortuctKeys["moveUp"]) {
+        moveCollectionItem(-1);
+    } else if (shortcut === shortuctKeys["moveDown"]) {
+        moveCollectionItem(1);
     } else if (shortcut === shortuctKeys["login"] && loggedIn === null) {
         window.location.href = "/login";
     } else if (shortcut === shortuctKeys["signup"] && loggedIn === null) {
@@ -1,9 +1,9 @@
-window.onload = () => {
+window.onload = async () => {
+
+    await refreshSession();
 
     const shortcuts = localStorage.getItem("shortcuts");
     const shortcutsModal = document.getElementById("shortcuts-toggle-modal");
-    const tokenString = localStorage.getItem("auth");
-    const email = tokenString ? parseJwt(tokenString).email : "anon";
 
     if (shortcuts && shortcuts == "false") {
         hideShortcuts();
@@ -19,18 +19,41 @@ window.onload = () => {
         document.getElementById("shortcuts-toggle").style.display = "none";
     });
 
-    htmx.ajax("GET", `/handle/navbar/home/${tokenString}`, { target: "#navbar-profile", swap: "innerHTML" });
-    htmx.ajax("GET", `/handle/shortcut/${tokenString}`, { target: "#shortcuts-modal", swap: "beforeend" });
+    htmx.ajax("GET", `/handle/navbar/home`, { target: "#navbar-profile", swap: "innerHTML" });
+    htmx.ajax("GET", `/handle/shortcut`, { target: "#shortcuts-modal", swap: "beforeend" });
 
     setTimeout(() => {
-        htmx.ajax("GET", `/handle/username/${tokenString}`, { target: "#terminal-console", swap: "beforeend" });
+        htmx.ajax("GET", `/handle/username`, { target: "#terminal-console", swap: "beforeend" });
     }, 600);
 
     setTimeout(() => {
-        htmx.ajax("GET", `/handle/request/new/${email}`, { target: "#terminal-console", swap: "beforeend" });
+        htmx.ajax("GET", `/handle/request/new`, { target: "#terminal-console", swap: "beforeend" });
     }, 1200);
 
     document.addEventListener("focusin", () => {});
+
+    document.addEventListener("click", (e) => {
+        const method = e.target.closest(".browser-method");
+
+        if (method && e.target.tagName !== "SUMMARY") {
+            method.focus();
+            fillForm();
+        };
+    });
+};
+
+let currentCall = null;
+
+const assignCall = (e) => {
+    currentCall = Array.from(crypto.getRandomValues(new Uint8Array(16)), (byte) => byte.toString(16).padStart(2, "0")).join("");
+    e.detail.parameters.call = currentCall;
+};
+
+const cancelRequest = () => {
+    if (currentCall) {
+        apiFetch(`/grpcurl/cancel/${currentCall}`, { method: "POST" });
+        currentCall = null;
+    };
 };
 
 const loading = () => {
@@ -43,7 +66,11 @@ const formatResponse = () => {
 
     if (responseTextarea) {
         if (responseTextarea.textContent.charAt(0) === "{" || responseTextarea.textContent.charAt(0) === "[") {
-            responseTextarea.textContent = JSON.stringify(JSON.parse(responseTextarea.textContent), null, 4) + "\n";
+            try {
+                responseTextarea.textContent = JSON.stringify(JSON.parse(responseTextarea.textContent), null, 4) + "\n";
+            } catch {
+                // streamed responses are several JSON messages back to back, already formatted by the server
+            };
         } else if (responseTextarea.textContent.charAt(0) === "<") {
 
             responseTextarea.textContent = html_beautify(responseTextarea.textContent);
@@ -55,6 +82,111 @@ const formatResponse = () => {
     };
 };
 
+let activeStream = null;
+
+const watchStream = () => {
+
+    const stream = document.getElementById("stream");
+
+    if (activeStream) {
+        activeStream.close();
+        activeStream = null;
+    };
+
+    if (stream) {
+
+        const responseTextarea = document.getElementById("response-textarea");
+        const streamStatus = document.getElementById("stream-status");
+        const streamMetadata = document.getElementById("stream-metadata");
+
+        activeStream = new EventSource(`/grpcurl/stream/${stream.dataset.streamId}`);
+
+        activeStream.onmessage = (e) => {
+
+            const event = JSON.parse(e.data);
+
+            if (event.type === "headers") {
+                addStreamMetadata("headers", event.headers);
+                streamMetadata.children[0].textContent = `$  first byte ${event.first_byte_ms.toFixed(2)} ms`;
+            } else if (event.type === "message") {
+                responseTextarea.textContent += event.body + "\n";
+            } else if (event.type === "status") {
+
+                streamStatus.textContent = event.message ? `${event.status} - ${event.message}` : event.status;
+                addStreamMetadata("status details", event.details);
+                addStreamMetadata("trailers", event.trailers);
+
+                if (document.getElementById("stream-message")) {
+                    document.getElementById("stream-message").remove();
+                };
+
+                activeStream.close();
+                activeStream = null;
+            };
+        };
+
+        activeStream.onerror = () => {
+            if (activeStream) {
+                activeStream.close();
+                activeStream = null;
+            };
+        };
+    };
+};
+
+const addStreamMetadata = (title, pairs) => {
+
+    const streamMetadata = document.getElementById("stream-metadata");
+
+    if (streamMetadata && pairs.length > 0) {
+
+        const heading = document.createElement("p");
+        heading.textContent = `$  ${title}:`;
+        streamMetadata.appendChild(heading);
+
+        pairs.forEach(([name, value]) => {
+            const pair = document.createElement("p");
+            pair.textContent = `\u00a0\u00a0${name}: ${value}`;
+            streamMetadata.appendChild(pair);
+        });
+    };
+};
+
+const sendStreamMessage = async (e) => {
+
+    if (e) {
+        e.preventDefault();
+    };
+
+    const stream = document.getElementById("stream");
+    const messageForm = document.getElementById("stream-message");
+
+    if (stream && messageForm) {
+
+        const messageRequest = await apiFetch(`/grpcurl/stream/${stream.dataset.streamId}/message`, {
+            method: "POST",
+            headers: { "Content-Type": "application/json" },
+            body: JSON.stringify({ "data": messageForm.children.data.value })
+        });
+
+        document.getElementById("stream-notice").innerHTML = await messageRequest.text();
+        messageForm.children.data.value = "";
+        messageForm.children.data.focus();
+    };
+};
+
+const closeStream = async () => {
+
+    const stream = document.getElementById("stream");
+
+    if (stream && document.getElementById("stream-message")) {
+
+        const closeRequest = await apiFetch(`/grpcurl/stream/${stream.dataset.streamId}/close`, { method: "POST" });
+
+        document.getElementById("stream-notice").innerHTML = await closeRequest.text();
+    };
+};
+
 const fillForm = () => {
 
     const selectedItem = document.activeElement;
@@ -62,30 +194,463 @@ const fillForm = () => {
 
     const commandField = curlForm.children.command;
 
-    if (selectedItem.className === "history-item" || selectedItem.className === "favorites-item") {
+    if (selectedItem.className === "history-item" || selectedItem.className === "favorites-item" || selectedItem.className === "collection-item") {
 
         commandField.value = selectedItem.children.command.value;
 
         document.getElementById("history-modal").style.display = "none";
         document.getElementById("favorites-modal").style.display = "none";
+        document.getElementById("collections-modal").style.display = "none";
+
+        commandField.focus();
+    } else if (selectedItem.className === "completion-item") {
+
+        applyCompletion(selectedItem);
+    } else if (selectedItem.className === "browser-method") {
 
+        commandField.value = selectedItem.dataset.command;
         commandField.focus();
+
+        generateSkeleton();
     };
 };
 
-const emptyForm = () => {
-    document.getElementById("new-request").reset();
+let completionTimer = null;
+let completionCursor = 0;
+
+const requestCompletions = () => {
+
+    clearTimeout(completionTimer);
+
+    completionTimer = setTimeout(async () => {
+
+        const commandField = document.getElementById("new-request").children.command;
+        const completionsList = document.getElementById("completions");
+
+        completionCursor = commandField.selectionStart;
+
+        const completionRequest = await apiFetch(`/grpcurl/complete`, {
+            method: "POST",
+            headers: { "Content-Type": "application/json" },
+            body: JSON.stringify({ "command": commandField.value, "cursor": completionCursor })
+        });
+
+        completionsList.innerHTML = "";
+
+        if (completionRequest.ok) {
+
+            const { from, completions } = await completionRequest.json();
+
+            completions.forEach((completion) => {
+                const item = document.createElement("div");
+                item.className = "completion-item";
+                item.tabIndex = 0;
+                item.dataset.from = from;
+                item.dataset.value = completion.value;
+                item.textContent = completion.detail ? `${completion.value}  ${completion.detail}` : completion.value;
+                item.onclick = () => applyCompletion(item);
+                completionsList.appendChild(item);
+            });
+        };
+    }, 200);
+};
+
+const applyCompletion = (item) => {
+
+    const commandField = document.getElementById("new-request").children.command;
+    const from = Number(item.dataset.from);
+    const command = commandField.value;
+
+    commandField.value = command.slice(0, from) + item.dataset.value + command.slice(completionCursor);
+    commandField.focus();
+    commandField.selectionStart = commandField.selectionEnd = from + item.dataset.value.length;
+
+    document.getElementById("completions").innerHTML = "";
+};
+
+const acceptCompletion = (e) => {
+
+    const firstCompletion = document.getElementsByClassName("completion-item")[0];
+
+    if (e.key === "Tab" && firstCompletion) {
+        e.preventDefault();
+        applyCompletion(firstCompletion);
+    } else if (e.key === "Escape") {
+        document.getElementById("completions").innerHTML = "";
+    };
+};
+
+const focusCompletions = () => {
+
+    const firstCompletion = document.getElementsByClassName("completion-item")[0];
+
+    if (firstCompletion) {
+        firstCompletion.focus();
+    } else {
+        requestCompletions();
+    };
+};
+
+const generateSkeleton = async () => {
+
+    const commandField = document.getElementById("new-request").children.command;
+    const requestResponse = document.getElementById("request-response");
+
+    const skeletonRequest = await apiFetch(`/grpcurl/skeleton`, {
+        method: "POST",
+        headers: { "Content-Type": "application/json" },
+        body: JSON.stringify({ "command": commandField.value })
+    });
+
+    if (skeletonRequest.ok) {
+
+        const skeleton = await skeletonRequest.json();
+
+        commandField.value = skeleton.command;
+        requestResponse.innerHTML = "";
+
+        skeleton.notes.forEach((note) => {
+            const line = document.createElement("p");
+            line.textContent = `$  ${note}`;
+            requestResponse.appendChild(line);
+        });
+    } else {
+        requestResponse.textContent = `$  error: ${await errorMessage(skeletonRequest)}`;
+    };
+
+    commandField.focus();
+};
+
+const viewResponse = () => {
+
+    const selectedItem = document.activeElement;
+
+    if (selectedItem.className === "history-item" || selectedItem.className === "favorites-item") {
+
+        document.getElementById("new-request").children.command.value = selectedItem.children.command.value;
+        document.getElementById("history-modal").style.display = "none";
+        document.getElementById("favorites-modal").style.display = "none";
+
+        htmx.ajax("GET", `/handle/request/response/${selectedItem.id}`, { target: "#request-response", swap: "innerHTML" })
+            .then(formatResponse);
+    };
+};
+
+const uploadProto = async (e) => {
+
+    e.preventDefault();
+
+    const uploadForm = document.getElementById("proto-upload");
+    const file = uploadForm.children.file.files[0];
+
+    if (!file) {
+        document.getElementById("proto-notice").textContent = "$  choose a .proto or protoset file";
+        return;
+    };
+
+    const name = uploadForm.children.name.value || file.name;
+    let content = "";
+
+    if (name.endsWith(".proto")) {
+        content = await file.text();
+    } else {
+        const bytes = new Uint8Array(await file.arrayBuffer());
+        content = btoa(bytes.reduce((binary, byte) => binary + String.fromCharCode(byte), ""));
+    };
+
+    const uploadRequest = await apiFetch(`/api/proto`, {
+        method: "POST",
+        headers: { "Content-Type": "application/json" },
+        body: JSON.stringify({ "target": uploadForm.children.target.value, "name": name, "content": content })
+    });
+
+    document.getElementById("proto-notice").textContent = uploadRequest.ok ? `$  uploaded ${name}` : `$  error: ${await errorMessage(uploadRequest)}`;
+    uploadForm.children.name.value = "";
+    uploadForm.children.file.value = "";
+
+    htmx.ajax("GET", `/handle/proto/list`, { target: "#proto-list", swap: "innerHTML" });
+};
+
+const deleteProto = async (id) => {
+
+    await apiFetch(`/api/proto/delete/${id}`, { method: "DELETE" });
+
+    htmx.ajax("GET", `/handle/proto/list`, { target: "#proto-list", swap: "innerHTML" });
 };
 
-const removeItem = (array, id) => {
-    array.splice(array.indexOf(id), 1);
-    return array;
+const reloadEnvironments = () => {
+    htmx.ajax("GET", `/handle/environment/list`, { target: "#environments", swap: "innerHTML" });
+};
+
+const switchEnvironment = async (name) => {
+
+    await apiFetch(`/api/environment/active`, {
+        method: "PATCH",
+        headers: { "Content-Type": "application/json" },
+        body: JSON.stringify(name ? { "name": name } : {})
+    });
+
+    reloadEnvironments();
+};
+
+const cycleEnvironment = () => {
+
+    const switcher = document.getElementById("environment-switcher");
+
+    if (!switcher || switcher.options.length === 0) {
+        return;
+    };
+
+    switcher.selectedIndex = (switcher.selectedIndex + 1) % switcher.options.length;
+    switchEnvironment(switcher.value);
+};
+
+const setVariable = async (e) => {
+
+    e.preventDefault();
+
+    const variableForm = document.getElementById("variable-form");
+    const environment = variableForm.children.environment.value;
+    const key = variableForm.children.key.value;
+
+    const variableRequest = await apiFetch(`/api/environment/variable`, {
+        method: "PUT",
+        headers: { "Content-Type": "application/json" },
+        body: JSON.stringify({
+            "environment": environment,
+            "key": key,
+            "value": variableForm.children.value.value,
+            "secret": variableForm.querySelector("[name=secret]").checked
+        })
+    });
+    const notice = variableRequest.ok ? `$  set {{${key}}} in ${environment}` : `$  error: ${await errorMessage(variableRequest)}`;
+
+    document.getElementById("environments").addEventListener("htmx:afterSwap", () => {
+        document.getElementById("environment-variables").open = true;
+        document.getElementById("environment-notice").innerHTML = notice;
+    }, { once: true });
+
+    reloadEnvironments();
+};
+
+const deleteVariable = async (id) => {
+
+    await apiFetch(`/api/environment/variable/${id}`, { method: "DELETE" });
+
+    reloadEnvironments();
+};
+
+const deleteEnvironment = async (id) => {
+
+    await apiFetch(`/api/environment/delete/${id}`, { method: "DELETE" });
+
+    reloadEnvironments();
+};
+
+const reloadCollections = () => {
+    return htmx.ajax("GET", `/handle/collection/list`, { target: "#collections-modal", swap: "innerHTML" });
+};
+
+const toggleCollectionsList = () => {
+
+    const collectionsModal = document.getElementById("collections-modal");
+
+    document.getElementById("history-modal").style.display = "none";
+    document.getElementById("favorites-modal").style.display = "none";
+
+    if (collectionsModal.style.display === "flex") {
+        collectionsModal.style.display = "none";
+    } else {
+
+        reloadCollections().then(() => {
+            if (document.getElementsByClassName("collection-summary")[0]) {
+                document.getElementsByClassName("collection-summary")[0].focus();
+            };
+        });
+
+        collectionsModal.style.display = "flex";
+    };
+};
+
+const showCollectionNotice = async (notice) => {
+
+    await reloadCollections();
+
+    document.getElementById("collection-notice").innerHTML = notice;
+};
+
+const createCollection = async (e) => {
+
+    e.preventDefault();
+
+    const collectionForm = document.getElementById("collection-form");
+    const parentID = collectionForm.querySelector("[name=parent_id]").value;
+
+    const collectionRequest = await apiFetch(`/api/collection`, {
+        method: "POST",
+        headers: { "Content-Type": "application/json" },
+        body: JSON.stringify({
+            "name": collectionForm.querySelector("[name=name]").value,
+            "parent_id": parentID ? Number(parentID) : null
+        })
+    });
+
+    showCollectionNotice(collectionRequest.ok ? "" : `$  error: ${await errorMessage(collectionRequest)}`);
+};
+
+const saveRequest = async (e) => {
+
+    e.preventDefault();
+
+    const savedRequestForm = document.getElementById("saved-request-form");
+    const collectionID = savedRequestForm.querySelector("[name=collection_id]").value;
+    const title = savedRequestForm.querySelector("[name=title]").value;
+
+    if (!collectionID) {
+        document.getElementById("collection-notice").innerHTML = "$  add a collection first";
+        return;
+    };
+
+    const savedRequest = await apiFetch(`/api/collection/request`, {
+        method: "POST",
+        headers: { "Content-Type": "application/json" },
+        body: JSON.stringify({
+            "collection_id": Number(collectionID),
+            "title": title,
+            "description": savedRequestForm.querySelector("[name=description]").value,
+            "command": document.getElementById("new-request").children.command.value
+        })
+    });
+
+    showCollectionNotice(savedRequest.ok ? `$  saved ${title}` : `$  error: ${await errorMessage(savedRequest)}`);
+};
+
+const renameCollection = async (id) => {
+
+    const folder = document.querySelector(`.collection-folder[data-id="${id}"]`);
+    const parentID = folder.parentElement.dataset.id;
+    const name = prompt("$  new name", folder.querySelector("summary").firstChild.textContent.trim());
+
+    if (!name) {
+        return;
+    };
+
+    const renameRequest = await apiFetch(`/api/collection/${id}`, {
+        method: "PUT",
+        headers: { "Content-Type": "application/json" },
+        body: JSON.stringify({ "name": name, "parent_id": parentID ? Number(parentID) : null })
+    });
+
+    showCollectionNotice(renameRequest.ok ? "" : `$  error: ${await errorMessage(renameRequest)}`);
+};
+
+const deleteCollection = async (id) => {
+
+    if (!confirm("$  delete this collection with everything in it?")) {
+        return;
+    };
+
+    await apiFetch(`/api/collection/delete/${id}`, { method: "DELETE" });
+
+    reloadCollections();
+};
+
+// Sends the new order of everything directly under a collection (or the top level)
+const sendCollectionOrder = async (container) => {
+
+    const parentID = container.dataset.id ? Number(container.dataset.id) : null;
+    const folders = [...container.querySelectorAll(":scope > .collection-folder")].map((folder) => Number(folder.dataset.id));
+    const requests = [...container.querySelectorAll(":scope > .collection-item")].map((item) => Number(item.dataset.id));
+
+    for (const [kind, ids] of [["collection", folders], ["request", requests]]) {
+        if (ids.length === 0 || (kind === "request" && parentID === null)) {
+            continue;
+        };
+
+        await apiFetch(`/api/collection/order`, {
+            method: "PATCH",
+            headers: { "Content-Type": "application/json" },
+            body: JSON.stringify({ "kind": kind, "parent_id": parentID, "ids": ids })
+        });
+    };
+};
+
+const moveCollectionItem = (direction) => {
+
+    const selectedItem = document.activeElement;
+    const item = selectedItem.className === "collection-summary" ? selectedItem.parentElement : selectedItem;
+
+    if (item.className !== "collection-item" && item.className !== "collection-folder") {
+        return;
+    };
+
+    const sibling = direction < 0 ? item.previousElementSibling : item.nextElementSibling;
+
+    if (!sibling || sibling.className !== item.className) {
+        return;
+    };
+
+    if (direction < 0) {
+        item.parentElement.insertBefore(item, sibling);
+    } else {
+        item.parentElement.insertBefore(sibling, item);
+    };
+
+    selectedItem.focus();
+    sendCollectionOrder(item.parentElement);
+};
+
+let draggedCollectionItem = null;
+
+document.addEventListener("dragstart", (e) => {
+    if (e.target.className === "collection-item" || e.target.className === "collection-folder") {
+        draggedCollectionItem = e.target;
+    };
+});
+
+document.addEventListener("dragover", (e) => {
+    if (draggedCollectionItem && e.target.closest && e.target.closest(".collection-item, .collection-summary")) {
+        e.preventDefault();
+    };
+});
+
+// Dropping on a folder's name moves into it, dropping on a saved request moves next to it
+document.addEventListener("drop", (e) => {
+
+    const target = draggedCollectionItem && e.target.closest ? e.target.closest(".collection-item, .collection-summary") : null;
+
+    if (!target) {
+        return;
+    };
+
+    e.preventDefault();
+
+    const container = target.className === "collection-summary" ?
+        target.parentElement.querySelector(":scope > .collection-children") : target.parentElement;
+    const firstRequest = container.querySelector(":scope > .collection-item");
+
+    if (draggedCollectionItem.contains(container)) {
+        return;
+    };
+
+    if (draggedCollectionItem.className === "collection-folder") {
+        container.insertBefore(draggedCollectionItem, firstRequest);
+    } else if (target.className === "collection-item") {
+        container.insertBefore(draggedCollectionItem, target);
+    } else {
+        container.appendChild(draggedCollectionItem);
+    };
+
+    draggedCollectionItem = null;
+    sendCollectionOrder(container);
+});
+
+const emptyForm = () => {
+    document.getElementById("new-request").reset();
 };
 
 const toggleHistoryList = () => {
 
-    const tokenString = localStorage.getItem("auth");
-    const email = tokenString ? parseJwt(tokenString).email : "anon";
     const favoritesModal = document.getElementById("favorites-modal");
     const historyModal = document.getElementById("history-modal");
 
@@ -97,7 +662,7 @@ const toggleHistoryList = () => {
         historyModal.style.display = "none";
     } else {
 
-        htmx.ajax("GET", `/handle/request/history/${email}`, { target: "#history-modal", swap: "innerHTML" });
+        htmx.ajax("GET", `/handle/request/history`, { target: "#history-modal", swap: "innerHTML" });
 
         historyModal.style.display = "flex";
 
@@ -111,8 +676,7 @@ const toggleHistoryList = () => {
 
 const toggleFavoritesList = () => {
 
-    const tokenString = localStorage.getItem("auth");
-    const email = tokenString ? parseJwt(tokenString).email : "anon";
+    const email = currentProfile()?.email ?? "anon";
     const historyModal = document.getElementById("history-modal");
     const favoritesModal = document.getElementById("favorites-modal");
 
@@ -138,7 +702,7 @@ const toggleFavoritesList = () => {
             }, 2000);
         } else {
 
-            htmx.ajax("GET", `/handle/request/favorites/${email}`, { target: "#favorites-modal", swap: "innerHTML" });
+            htmx.ajax("GET", `/handle/request/favorites`, { target: "#favorites-modal", swap: "innerHTML" });
 
             favoritesModal.style.display = "flex";
 
@@ -155,7 +719,7 @@ const toggleFavoriteItem = async () => {
 
     const selectedItem = document.activeElement;
     const requestID = Number(selectedItem.id);
-    const profile = localStorage.getItem("auth") !== null ? parseJwt(localStorage.getItem("auth")) : null;
+    const profile = currentProfile();
 
     if (selectedItem.className === "history-item" || selectedItem.className === "favorites-item") {
 
@@ -167,70 +731,30 @@ const toggleFavoriteItem = async () => {
                 document.getElementById(requestID).children[4].style.display = "none"; 
             }, 1000);
 
-        } else if (profile.favorites || (profile.favorites && profile.favorites.includes(requestID))) {
-
-            const favoritesBeforeUpdate = profile.favorites;
-            const updatedFavorites = profile.favorites.indexOf(requestID) !== -1 ? (
-                profile.favorites.length > 1 ? removeItem(profile.favorites, requestID) : [] 
-            ) : ( 
-                profile.favorites.length === 0 ? [requestID] : [...profile.favorites, requestID]
-            );
-
-            const favoriteRequest = await fetch("/api/user/favorites", {
-                method: "PATCH",
-                headers: { "Content-Type": "application/json" },
-                body: JSON.stringify({
-                    "username": profile.username,
-                    "email": profile.email,
-                    "password": profile.password,
-                    "favorites": updatedFavorites,
-                    "date": profile.date,
-                    "deleted": false
-                })
-            });
-            const favoriteResponse = await favoriteRequest.json();
-            localStorage.setItem("auth", favoriteResponse);
-
-            if (document.getElementById("favorites-modal").style.display === "flex") {
-                toggleFavoritesList();
-                toggleFavoritesList();
-            } else {
+        } else {
 
-                const responseMessage = updatedFavorites.length > favoritesBeforeUpdate.length ? 
-                    document.getElementById(requestID).children[2] : document.getElementById(requestID).children[3];
-                responseMessage.style.display = "flex";
+            const isFavorite = selectedItem.dataset.favorite === "true";
+            const favoriteRequest = await apiFetch(`/api/favorite/${requestID}`, {
+                method: isFavorite ? "DELETE" : "PUT"
+            });
 
-                setTimeout(() => {
-                    responseMessage.style.display = "none"; 
-                }, 1000);
+            if (!favoriteRequest.ok) {
+                return;
             };
-        } else {
 
-            const updatedFavorites = [requestID];
-            const favoriteRequest = await fetch("/api/user/favorites", {
-                method: "PATCH",
-                headers: { "Content-Type": "application/json" },
-                body: JSON.stringify({
-                    "username": profile.username,
-                    "email": profile.email,
-                    "password": profile.password,
-                    "favorites": updatedFavorites,
-                    "date": profile.date,
-                    "deleted": false
-                })
-            });
-            const favoriteResponse = await favoriteRequest.json();
-            localStorage.setItem("auth", favoriteResponse);
+            selectedItem.dataset.favorite = `${!isFavorite}`;
 
             if (document.getElementById("favorites-modal").style.display === "flex") {
                 toggleFavoritesList();
                 toggleFavoritesList();
             } else {
 
-                document.getElementById(requestID).children[2].style.display = "flex";
+                const responseMessage = isFavorite ? 
+                    document.getElementById(requestID).children[3] : document.getElementById(requestID).children[2];
+                responseMessage.style.display = "flex";
 
                 setTimeout(() => {
-                    document.getElementById(requestID).children[2].style.display = "none"; 
+                    responseMessage.style.display = "none"; 
                 }, 1000);
             };
         };
@@ -240,19 +764,22 @@ const toggleFavoriteItem = async () => {
 const hideRequest = async () => {
 
     const selectedItem = document.activeElement;
-    const tokenString = localStorage.getItem("auth");
-    const email = tokenString ? parseJwt(tokenString).email : "anon";
 
     if (selectedItem.className === "history-item" || selectedItem.className === "favorites-item") {
 
         const requestID = selectedItem.id;
 
-        fetch(`/api/request/delete/${email}/${requestID}`, { method: "DELETE" });
+        apiFetch(`/api/request/delete/${requestID}`, { method: "DELETE" });
 
         if (selectedItem.className === "history-item") {
             selectedItem.children[5].style.display = "flex";
         } else if (selectedItem.className === "favorites-item") {
             selectedItem.children[4].style.display = "flex";
         };
+    } else if (selectedItem.className === "collection-item") {
+
+        await apiFetch(`/api/collection/request/delete/${selectedItem.dataset.id}`, { method: "DELETE" });
+
+        reloadCollections();
     };
 };
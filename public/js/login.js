@@ -1,10 +1,11 @@
-window.onload = () => {
+window.onload = async () => {
+
+    await refreshSession();
 
     const shortcuts = localStorage.getItem("shortcuts");
     const shortcutsModal = document.getElementById("shortcuts-toggle-modal");
-    const tokenString = localStorage.getItem("auth");
 
-    htmx.ajax("GET", `/handle/navbar/login/${tokenString}`, { target: "#navbar-profile", swap: "innerHTML" });
+    htmx.ajax("GET", `/handle/navbar/login`, { target: "#navbar-profile", swap: "innerHTML" });
 
     if (shortcuts && shortcuts == "false") {
         hideShortcuts();
@@ -37,16 +38,15 @@ document.getElementById("login-form").addEventListener("submit", async (e) => {
             "email": email,
             "password": password,
             "username": "doesntmatter",
-            "favorites": null,
             "deleted": false
         })
     });
     const authenticated = authReq.ok ? await authReq.json() : null;
 
-    if (typeof authenticated === "string" && authReq.status == 200) {
+    if (authenticated?.email && authReq.status == 200) {
 
-        localStorage.setItem("auth", authenticated);
-        htmx.ajax("GET", `/handle/login/${authenticated}`, { target: "#login-response", swap: "innerHTML" });
+        storeSession(authenticated);
+        htmx.ajax("GET", `/handle/login`, { target: "#login-response", swap: "innerHTML" });
 
         setTimeout(() => {
             timer.innerHTML = "<p>$  redirecting in 3 secs.</p>";
@@ -66,7 +66,7 @@ document.getElementById("login-form").addEventListener("submit", async (e) => {
 
     } else {
 
-        htmx.ajax("GET", `/handle/login/null`, { target: "#login-response", swap: "innerHTML" });
+        htmx.ajax("GET", `/handle/login`, { target: "#login-response", swap: "innerHTML" });
 
         setTimeout(() => {
             response.innerHTML = "";
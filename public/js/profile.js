@@ -1,13 +1,16 @@
-window.onload = function() {
+window.onload = async function() {
+
+    await refreshSession();
 
     const shortcuts = localStorage.getItem("shortcuts");
     const shortcutsModal = document.getElementById("shortcuts-toggle-modal");
-    const tokenString = localStorage.getItem("auth");
-    const profile = parseJwt(tokenString);
+    const profile = currentProfile();
     const username = document.getElementById("profile-username");
 
-    htmx.ajax("GET", `/handle/navbar/profile/${tokenString}`, { target: "#navbar-profile", swap: "innerHTML" });
-    htmx.ajax("GET", `/handle/profile/info/${tokenString}`, { target: "#profile-info", swap: "innerHTML" });
+    htmx.ajax("GET", `/handle/navbar/profile`, { target: "#navbar-profile", swap: "innerHTML" });
+    htmx.ajax("GET", `/handle/profile/info`, { target: "#profile-info", swap: "innerHTML" });
+    reloadApiTokens();
+    reloadWorkspaces();
 
     username.value = profile.username;
 
@@ -30,31 +33,30 @@ document.getElementById("profile-form").addEventListener("submit", async (e) =>
 
     e.preventDefault();
 
-    const profile = parseJwt(localStorage.getItem("auth"));
+    const profile = currentProfile();
 
     const username = document.getElementById("profile-username");
     const password = document.getElementById("profile-password");
     const response = document.getElementById("profile-response");
     const timer = document.getElementById("profile-timer");
 
-    const updateReq = await fetch("/api/user", {
+    const updateReq = await apiFetch("/api/user", {
         method: "PUT",
         headers: { "Content-Type": "application/json" },
         body: JSON.stringify({
             "username": username.value,
             "email": profile.email,
             "password": password.value,
-            "favorites": profile.favorites,
             "date": profile.date,
             "deleted": false
         })
     });
     const updatedProfile = await updateReq.json();
 
-    if (typeof updatedProfile === "string" && updateReq.status == 200) {
+    if (updatedProfile?.email && updateReq.status == 200) {
 
-        localStorage.setItem("auth", updatedProfile);
-        htmx.ajax("GET", `/handle/profile/update/${updatedProfile}`, { target: "#profile-response", swap: "innerHTML" });
+        storeSession(updatedProfile);
+        htmx.ajax("GET", `/handle/profile/update`, { target: "#profile-response", swap: "innerHTML" });
 
         setTimeout(() => {
             timer.innerHTML = "<p>$  redirecting in 3 secs.</p>";
@@ -74,7 +76,7 @@ document.getElementById("profile-form").addEventListener("submit", async (e) =>
 
     } else {
 
-        htmx.ajax("GET", `/handle/profile/update/null`, { target: "#profile-response", swap: "innerHTML" });
+        htmx.ajax("GET", `/handle/profile/update`, { target: "#profile-response", swap: "innerHTML" });
 
         setTimeout(() => {
             response.innerHTML = "";
@@ -84,23 +86,10 @@ document.getElementById("profile-form").addEventListener("submit", async (e) =>
 
 const deleteProfile = async () => {
 
-    const profile = parseJwt(localStorage.getItem("auth"));
-
     const response = document.getElementById("profile-response");
     const timer = document.getElementById("profile-timer");
 
-    const deleteReq = await fetch("/api/user", {
-        method: "DELETE",
-        headers: { "Content-Type": "application/json" },
-        body: JSON.stringify({
-            "username": profile.username,
-            "email": profile.email,
-            "password": profile.password,
-            "favorites": profile.favorites,
-            "date": profile.date,
-            "deleted": true 
-        })
-    });
+    const deleteReq = await apiFetch("/api/user", { method: "DELETE" });
 
     if (deleteReq.status == 200) {
 
@@ -129,3 +118,122 @@ const deleteProfile = async () => {
         }, 1500);
     };
 };
+
+// Error text and names come from the server and the user, so they go in as text, never as html
+const showNotice = (id, text, code) => {
+    const notice = document.getElementById(id);
+    const line = document.createElement("p");
+
+    line.textContent = text;
+    if (code) {
+        const value = document.createElement("code");
+        value.textContent = code;
+        line.appendChild(value);
+    };
+
+    notice.replaceChildren(line);
+};
+
+const reloadApiTokens = () => {
+    htmx.ajax("GET", `/handle/profile/tokens`, { target: "#profile-tokens", swap: "innerHTML" });
+};
+
+// The token is only shown once, right after it's created
+const createApiToken = async (e) => {
+
+    e.preventDefault();
+
+    const form = new FormData(e.target);
+    const expiresInDays = form.get("expires_in_days");
+
+    const createReq = await apiFetch("/api/token", {
+        method: "POST",
+        headers: { "Content-Type": "application/json" },
+        body: JSON.stringify({
+            "name": form.get("name"),
+            "scopes": form.getAll("scopes"),
+            "expires_in_days": expiresInDays ? Number(expiresInDays) : null
+        })
+    });
+
+    if (createReq.ok) {
+        const created = await createReq.json();
+
+        reloadApiTokens();
+
+        setTimeout(() => {
+            showNotice("token-notice", "$  copy it now, it won't be shown again: ", created.token);
+        }, 200);
+    } else {
+        showNotice("token-notice", `$  error: ${await errorMessage(createReq)}`);
+    };
+};
+
+const revokeApiToken = async (id) => {
+
+    const revokeReq = await apiFetch(`/api/token/delete/${id}`, { method: "DELETE" });
+
+    if (revokeReq.ok) {
+        reloadApiTokens();
+    } else {
+        showNotice("token-notice", `$  error: ${await errorMessage(revokeReq)}`);
+    };
+};
+
+const reloadWorkspaces = () => {
+    htmx.ajax("GET", `/handle/profile/workspaces`, { target: "#profile-workspaces", swap: "innerHTML" });
+};
+
+const showWorkspaceNotice = async (request) => {
+    if (request.ok) {
+        reloadWorkspaces();
+    } else {
+        showNotice("workspace-notice", `$  error: ${await errorMessage(request)}`);
+    };
+};
+
+const createWorkspace = async (e) => {
+
+    e.preventDefault();
+
+    const createReq = await apiFetch("/api/workspace", {
+        method: "POST",
+        headers: { "Content-Type": "application/json" },
+        body: JSON.stringify({ "name": new FormData(e.target).get("name") })
+    });
+
+    await showWorkspaceNotice(createReq);
+};
+
+const setMember = async (e, workspaceID) => {
+
+    e.preventDefault();
+
+    const form = new FormData(e.target);
+
+    const memberReq = await apiFetch(`/api/workspace/${workspaceID}/member`, {
+        method: "PUT",
+        headers: { "Content-Type": "application/json" },
+        body: JSON.stringify({ "email": form.get("email"), "role": form.get("role") })
+    });
+
+    await showWorkspaceNotice(memberReq);
+};
+
+const removeMember = async (memberID) => {
+
+    const removeReq = await apiFetch(`/api/workspace/member/delete/${memberID}`, { method: "DELETE" });
+
+    await showWorkspaceNotice(removeReq);
+};
+
+const deleteWorkspace = async (workspaceID) => {
+
+    if (!confirm("$  delete this workspace with its history, collections, environments and protos?")) {
+        return;
+    };
+
+    const deleteReq = await apiFetch(`/api/workspace/delete/${workspaceID}`, { method: "DELETE" });
+
+    await showWorkspaceNotice(deleteReq);
+};
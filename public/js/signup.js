@@ -1,10 +1,11 @@
-window.onload = () => {
+window.onload = async () => {
+
+    await refreshSession();
 
     const shortcuts = localStorage.getItem("shortcuts");
     const shortcutsModal = document.getElementById("shortcuts-toggle-modal");
-    const tokenString = localStorage.getItem("auth");
 
-    htmx.ajax("GET", `/handle/navbar/signup/${tokenString}`, { target: "#navbar-profile", swap: "innerHTML" });
+    htmx.ajax("GET", `/handle/navbar/signup`, { target: "#navbar-profile", swap: "innerHTML" });
 
     if (shortcuts && shortcuts == "false") {
         hideShortcuts();
@@ -39,16 +40,15 @@ document.getElementById("signup-form").addEventListener("submit", async (e) => {
             "email": email,
             "password": password,
             "date": `${Date.now()}`,
-            "favorites": null,
             "deleted": false
         })
     });
     const created = await createRequest.json();
 
-    if (typeof created === "string" && createRequest.status == 200) {
+    if (created?.email && createRequest.status == 200) {
 
-        localStorage.setItem("auth", created);
-        htmx.ajax("GET", `/handle/signup/${created}`, { target: "#signup-response", swap: "innerHTML" });
+        storeSession(created);
+        htmx.ajax("GET", `/handle/signup`, { target: "#signup-response", swap: "innerHTML" });
 
         setTimeout(() => {
             timer.innerHTML = "<p>$  redirecting in 3 secs.</p>";
@@ -68,7 +68,7 @@ document.getElementById("signup-form").addEventListener("submit", async (e) => {
 
     } else {
 
-        htmx.ajax("GET", `/handle/signup/null`, { target: "#signup-response", swap: "innerHTML" });
+        htmx.ajax("GET", `/handle/signup`, { target: "#signup-response", swap: "innerHTML" });
 
         setTimeout(() => {
             response.innerHTML = "";
@@ -0,0 +1,120 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use miette::{Result, miette};
+use prost_reflect::DescriptorPool;
+
+use crate::grpc::{GrpcCommand, Verb, attach_protoset, load_descriptors, protoset_hash};
+
+// Reflection round trips add up on big servers, the browser reuses a target's descriptors
+// for a while unless asked to refresh
+const CACHE_TTL: Duration = Duration::from_secs(300);
+// Completion asks on every keystroke, so a target that can't be loaded isn't tried again
+// right away either
+const FAILURE_TTL: Duration = Duration::from_secs(10);
+// Targets past this push out the one loaded longest ago
+const CACHE_SIZE: usize = 256;
+// How long a load can take when the command doesn't set -max-time
+const LOAD_TIME: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone)]
+struct CacheEntry {
+    loaded: Instant,
+    pool: Result<DescriptorPool, String>,
+}
+
+impl CacheEntry {
+    fn is_fresh(&self) -> bool {
+        self.loaded.elapsed()
+            < match self.pool {
+                Ok(_) => CACHE_TTL,
+                Err(_) => FAILURE_TTL,
+            }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DescriptorCache(Arc<Mutex<HashMap<String, CacheEntry>>>);
+
+impl DescriptorCache {
+    // Registry files replace reflection for the target, so they're part of what gets cached
+    pub async fn get_or_load(
+        &self,
+        command: &GrpcCommand,
+        registry: Option<&[u8]>,
+        refresh: bool,
+    ) -> Result<DescriptorPool> {
+        let key = match registry {
+            Some(protoset) => format!("{} {:x}", target_key(command), protoset_hash(protoset)),
+            None => target_key(command),
+        };
+
+        if !refresh
+            && let Some(entry) = self.lock()?.get(&key)
+            && entry.is_fresh()
+        {
+            return entry.pool.clone().map_err(|e| miette!("{e}"));
+        }
+
+        let max_time = command.max_time().unwrap_or(LOAD_TIME);
+        let load = async {
+            match registry {
+                Some(protoset) => {
+                    let mut command = command.clone();
+                    let _file = attach_protoset(&mut command, protoset)?;
+                    load_descriptors(&command).await
+                }
+                None => load_descriptors(command).await,
+            }
+        };
+        let pool = match tokio::time::timeout(max_time, load).await {
+            Ok(pool) => pool.map_err(|e| e.to_string()),
+            Err(_) => Err(format!(
+                "Loading descriptors took longer than {}s",
+                max_time.as_secs_f64()
+            )),
+        };
+
+        let mut entries = self.lock()?;
+        entries.retain(|_, entry| entry.is_fresh());
+        if entries.len() >= CACHE_SIZE
+            && let Some(oldest) = entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.loaded)
+                .map(|(key, _)| key.clone())
+        {
+            entries.remove(&oldest);
+        }
+        entries.insert(
+            key,
+            CacheEntry {
+                loaded: Instant::now(),
+                pool: pool.clone(),
+            },
+        );
+
+        pool.map_err(|e| miette!("{e}"))
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, HashMap<String, CacheEntry>>> {
+        self.0
+            .lock()
+            .map_err(|e| miette!("Descriptor cache can't block current thread {e}"))
+    }
+}
+
+// The address plus every flag that changes what reflection returns, so listing a target and
+// calling one of its methods share an entry
+fn target_key(command: &GrpcCommand) -> String {
+    let target = GrpcCommand {
+        verb: Verb::List,
+        symbol: None,
+        data: None,
+        ..command.clone()
+    };
+
+    target.to_args().join(" ")
+}
@@ -0,0 +1,82 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use miette::{Result, miette};
+use ring::rand::{SecureRandom, SystemRandom};
+use tokio::sync::oneshot::{Receiver, Sender, channel};
+
+const CALL_ID_MAX_LEN: usize = 64;
+
+// Owner and call id
+type CallKey = (String, String);
+
+// Ids are handed to the browser and are the only thing needed to follow a stream, so they
+// can't be guessable
+pub fn new_call_id() -> Result<String> {
+    let mut id = [0_u8; 16];
+    SystemRandom::new()
+        .fill(&mut id)
+        .map_err(|_| miette!("Could not generate a call id"))?;
+
+    Ok(URL_SAFE_NO_PAD.encode(id))
+}
+
+// Calls that are still running, by owner and the id the browser sent along with the command, so
+// the cancel endpoint can stop them while the request that started them is still pending
+#[derive(Debug, Clone, Default)]
+pub struct InFlightCalls(Arc<Mutex<HashMap<CallKey, Sender<()>>>>);
+
+impl InFlightCalls {
+    pub fn register(&self, owner: &str, id: Option<String>) -> Result<(String, Receiver<()>)> {
+        let id = match id {
+            Some(id) if id.is_empty() || id.len() > CALL_ID_MAX_LEN => {
+                return Err(miette!(
+                    "Call id must be between 1 and {CALL_ID_MAX_LEN} characters"
+                ));
+            }
+            Some(id)
+                if !id
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') =>
+            {
+                return Err(miette!(
+                    "Call id can only contain letters, digits, '-' and '_'"
+                ));
+            }
+            Some(id) => id,
+            None => new_call_id()?,
+        };
+        let (cancel, cancelled) = channel();
+
+        let mut calls = self.lock()?;
+        let key = (owner.to_string(), id.clone());
+        if calls.contains_key(&key) {
+            return Err(miette!("Call {id} is already running"));
+        }
+        calls.insert(key, cancel);
+
+        Ok((id, cancelled))
+    }
+
+    pub fn cancel(&self, owner: &str, id: &str) -> Result<()> {
+        self.lock()?
+            .remove(&(owner.to_string(), id.to_string()))
+            .ok_or_else(|| miette!("Call {id} isn't running"))?
+            .send(())
+            .map_err(|_| miette!("Call {id} already finished"))
+    }
+
+    pub fn remove(&self, owner: &str, id: &str) -> Result<()> {
+        self.lock()?.remove(&(owner.to_string(), id.to_string()));
+        Ok(())
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, HashMap<CallKey, Sender<()>>>> {
+        self.0
+            .lock()
+            .map_err(|e| miette!("In flight calls can't block current thread {e}"))
+    }
+}
@@ -0,0 +1,421 @@
+use std::{iter::Peekable, str::Chars, time::Duration};
+
+use miette::{Result, miette};
+use serde::{Deserialize, Serialize};
+
+// Every grpcurl flag that doesn't take a value, the rest consume the next argument
+pub const BOOL_FLAGS: [&str; 14] = [
+    "plaintext",
+    "insecure",
+    "emit-defaults",
+    "allow-unknown-fields",
+    "use-reflection",
+    "format-error",
+    "expand-headers",
+    "msg-template",
+    "alts",
+    "help",
+    "version",
+    "v",
+    "vv",
+    "vvv",
+];
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Verb {
+    List,
+    Describe,
+    Invoke,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Flag {
+    pub name: String,
+    pub value: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct GrpcCommand {
+    pub flags: Vec<Flag>,
+    pub address: Option<String>,
+    pub verb: Verb,
+    pub symbol: Option<String>,
+    pub data: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Tokens {
+    pub args: Vec<String>,
+    pub stdin: Option<String>,
+}
+
+impl GrpcCommand {
+    pub fn parse(command: &str) -> Result<GrpcCommand> {
+        let mut tokens = tokenize(command)?;
+
+        // The form already prints "$  grpcurl", but pasted commands usually include it too
+        if tokens.args.first().map(String::as_str) == Some("grpcurl") {
+            tokens.args.remove(0);
+        }
+
+        GrpcCommand::from_tokens(tokens)
+    }
+
+    pub fn from_tokens(tokens: Tokens) -> Result<GrpcCommand> {
+        let mut flags = Vec::new();
+        let mut positional = Vec::new();
+        let mut data = None;
+        let mut args = tokens.args.into_iter();
+
+        while let Some(arg) = args.next() {
+            let Some((name, value)) = split_flag(&arg) else {
+                positional.push(arg);
+                continue;
+            };
+
+            let value = match value {
+                Some(value) => Some(value),
+                None if BOOL_FLAGS.contains(&name.as_str()) => None,
+                None => Some(
+                    args.next()
+                        .ok_or_else(|| miette!("Flag -{name} needs a value"))?,
+                ),
+            };
+
+            if name == "d" {
+                data = match value.as_deref() {
+                    Some("@") => Some(tokens.stdin.clone().ok_or_else(|| {
+                        miette!("-d @ reads the request data from a heredoc, add <<EOF ... EOF")
+                    })?),
+                    _ => value,
+                };
+            } else {
+                flags.push(Flag { name, value });
+            }
+        }
+
+        let mut positional = positional.into_iter().peekable();
+        let address = match positional.peek().map(String::as_str) {
+            Some("list" | "describe") => None,
+            Some(_) => positional.next(),
+            None => return Err(miette!("Too few arguments, missing address")),
+        };
+        let (verb, symbol) = match positional.next().as_deref() {
+            Some("list") => (Verb::List, positional.next()),
+            Some("describe") => (Verb::Describe, positional.next()),
+            Some(symbol) => (Verb::Invoke, Some(symbol.to_string())),
+            None => {
+                return Err(miette!(
+                    "Too few arguments, missing symbol or list/describe"
+                ));
+            }
+        };
+
+        if let Some(extra) = positional.next() {
+            return Err(miette!("Unexpected argument \"{extra}\" after {verb:?}"));
+        }
+
+        Ok(GrpcCommand {
+            flags,
+            address,
+            verb,
+            symbol,
+            data,
+        })
+    }
+
+    // The same command with the text of every argument passed through fill, one argument at a
+    // time so nothing fill returns can become more arguments
+    pub fn map_args(self, fill: impl Fn(&str) -> Result<String>) -> Result<GrpcCommand> {
+        Ok(GrpcCommand {
+            flags: self
+                .flags
+                .into_iter()
+                .map(|flag| {
+                    Ok(Flag {
+                        name: fill(&flag.name)?,
+                        value: flag.value.as_deref().map(&fill).transpose()?,
+                    })
+                })
+                .collect::<Result<_>>()?,
+            address: self.address.as_deref().map(&fill).transpose()?,
+            verb: self.verb,
+            symbol: self.symbol.as_deref().map(&fill).transpose()?,
+            data: self.data.as_deref().map(&fill).transpose()?,
+        })
+    }
+
+    pub fn flag(&self, name: &str) -> Option<&str> {
+        self.flags
+            .iter()
+            .rev()
+            .find(|flag| flag.name == name)
+            .and_then(|flag| flag.value.as_deref())
+    }
+
+    pub fn flag_values(&self, name: &str) -> Vec<&str> {
+        self.flags
+            .iter()
+            .filter(|flag| flag.name == name)
+            .filter_map(|flag| flag.value.as_deref())
+            .collect()
+    }
+
+    pub fn max_time(&self) -> Option<Duration> {
+        self.flag("max-time")
+            .and_then(|seconds| seconds.parse::<f64>().ok())
+            .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+    }
+
+    // Caps -max-time at the server wide maximum, both backends turn it into the call deadline
+    pub fn limit_max_time(&mut self, max: Duration) {
+        if self.max_time().is_none_or(|max_time| max_time > max) {
+            self.flags.retain(|flag| flag.name != "max-time");
+            self.flags.push(Flag {
+                name: "max-time".to_string(),
+                value: Some(max.as_secs_f64().to_string()),
+            });
+        }
+    }
+
+    pub fn service(&self) -> Option<String> {
+        let symbol = self.symbol.as_deref()?;
+
+        match self.verb {
+            Verb::Invoke => symbol
+                .rsplit_once('/')
+                .or_else(|| symbol.rsplit_once('.'))
+                .map(|(service, _)| service.to_string()),
+            _ => Some(symbol.to_string()),
+        }
+    }
+
+    pub fn method(&self) -> String {
+        match self.verb {
+            Verb::List => "list".to_string(),
+            Verb::Describe => "describe".to_string(),
+            Verb::Invoke => {
+                let symbol = self.symbol.clone().unwrap_or_default();
+                symbol
+                    .rsplit(['/', '.'])
+                    .next()
+                    .unwrap_or_default()
+                    .to_string()
+            }
+        }
+    }
+
+    // What the history and favorites lists show next to the method
+    pub fn summary(&self) -> String {
+        let verb = match self.verb {
+            Verb::List => Some("list"),
+            Verb::Describe => Some("describe"),
+            Verb::Invoke => None,
+        };
+
+        [self.address.as_deref(), verb, self.symbol.as_deref()]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    // Argument vector for the grpcurl binary, with heredoc data inlined into -d
+    pub fn to_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+
+        for flag in &self.flags {
+            match &flag.value {
+                Some(value) if BOOL_FLAGS.contains(&flag.name.as_str()) => {
+                    args.push(format!("-{}={value}", flag.name))
+                }
+                Some(value) => args.extend([format!("-{}", flag.name), value.clone()]),
+                None => args.push(format!("-{}", flag.name)),
+            }
+        }
+        if let Some(data) = &self.data {
+            args.extend(["-d".to_string(), data.clone()]);
+        }
+
+        args.extend(self.address.clone());
+        match self.verb {
+            Verb::List => args.push("list".to_string()),
+            Verb::Describe => args.push("describe".to_string()),
+            Verb::Invoke => {}
+        }
+        args.extend(self.symbol.clone());
+
+        args
+    }
+
+    // Back to something the command form (and a shell) parses into the same command
+    pub fn to_command_line(&self) -> String {
+        self.to_args()
+            .iter()
+            .map(|arg| shell_quote(arg))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+fn shell_quote(arg: &str) -> String {
+    let plain = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./:=@,+%".contains(c));
+
+    match plain {
+        true => arg.to_string(),
+        false => format!("'{}'", arg.replace('\'', r"'\''")),
+    }
+}
+
+// POSIX shell word splitting: single quotes are literal, double quotes allow \" \\ \$ \`
+// escapes, a bare backslash escapes the next character and a backslash before a newline
+// continues the line. <<EOF heredocs and <<< here-strings become stdin for -d @
+pub fn tokenize(command: &str) -> Result<Tokens> {
+    let mut tokens = Tokens::default();
+    let mut heredocs = Vec::new();
+    let mut redirect = None::<Redirect>;
+    let mut word = None::<String>;
+    let mut chars = command.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('\n') => {}
+                Some(escaped) => word.get_or_insert_default().push(escaped),
+                None => return Err(miette!("Command ends with an unfinished escape")),
+            },
+            '\'' => {
+                let word = word.get_or_insert_default();
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err(miette!("Unclosed single quote in command")),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_default();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$' | '`')) => word.push(c),
+                            Some('\n') => {}
+                            Some(c) => word.extend(['\\', c]),
+                            None => return Err(miette!("Unclosed double quote in command")),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err(miette!("Unclosed double quote in command")),
+                    }
+                }
+            }
+            '<' if word.is_none() && chars.next_if_eq(&'<').is_some() => {
+                redirect = Some(if chars.next_if_eq(&'<').is_some() {
+                    Redirect::HereString
+                } else {
+                    Redirect::Heredoc {
+                        strip_tabs: chars.next_if_eq(&'-').is_some(),
+                    }
+                });
+            }
+            c if c.is_whitespace() => {
+                if let Some(word) = word.take() {
+                    match redirect.take() {
+                        Some(Redirect::HereString) => tokens.stdin = Some(word),
+                        Some(Redirect::Heredoc { strip_tabs }) => heredocs.push((word, strip_tabs)),
+                        None => tokens.args.push(word),
+                    }
+                }
+
+                if c == '\n' {
+                    if let Some(redirect) = redirect.take() {
+                        return Err(miette!(
+                            "{redirect:?} is missing its word before the newline"
+                        ));
+                    }
+                    for (delimiter, strip_tabs) in heredocs.drain(..) {
+                        tokens.stdin = Some(read_heredoc(&mut chars, &delimiter, strip_tabs)?);
+                    }
+                }
+            }
+            c => word.get_or_insert_default().push(c),
+        }
+    }
+
+    if let Some(word) = word {
+        match redirect.take() {
+            Some(Redirect::HereString) => tokens.stdin = Some(word),
+            Some(Redirect::Heredoc { .. }) => heredocs.push((word, false)),
+            None => tokens.args.push(word),
+        }
+    }
+    if let Some(redirect) = redirect {
+        return Err(miette!(
+            "{redirect:?} is missing its word at the end of the command"
+        ));
+    }
+    if let Some((delimiter, _)) = heredocs.first() {
+        return Err(miette!(
+            "Heredoc is missing its body, put it on the lines after <<{delimiter}"
+        ));
+    }
+
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Redirect {
+    HereString,
+    Heredoc { strip_tabs: bool },
+}
+
+fn read_heredoc(
+    chars: &mut Peekable<Chars<'_>>,
+    delimiter: &str,
+    strip_tabs: bool,
+) -> Result<String> {
+    let mut lines = Vec::new();
+
+    loop {
+        if chars.peek().is_none() {
+            return Err(miette!("Heredoc is missing its closing {delimiter} line"));
+        }
+
+        let mut line = String::new();
+        for c in chars.by_ref() {
+            if c == '\n' {
+                break;
+            }
+            line.push(c);
+        }
+
+        let line = line.strip_suffix('\r').unwrap_or(&line);
+        let line = match strip_tabs {
+            true => line.trim_start_matches('\t'),
+            false => line,
+        };
+
+        if line == delimiter {
+            return Ok(lines.join("\n"));
+        }
+        lines.push(line.to_string());
+    }
+}
+
+pub fn split_flag(arg: &str) -> Option<(String, Option<String>)> {
+    let flag = arg.strip_prefix("--").or_else(|| arg.strip_prefix('-'))?;
+
+    if flag.is_empty() {
+        return None;
+    }
+
+    Some(match flag.split_once('=') {
+        Some((name, value)) => (name.to_string(), Some(value.to_string())),
+        None => (flag.to_string(), None),
+    })
+}
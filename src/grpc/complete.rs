@@ -0,0 +1,505 @@
+use prost_reflect::{DescriptorPool, Kind, MessageDescriptor};
+use serde::{Deserialize, Serialize};
+
+use crate::grpc::{BOOL_FLAGS, GrpcCommand, Tokens, split_flag};
+
+const MAX_COMPLETIONS: usize = 50;
+
+pub const GRPCURL_FLAGS: [(&str, &str); 28] = [
+    ("plaintext", "use plain-text HTTP/2, no TLS"),
+    (
+        "insecure",
+        "skip server certificate and domain verification",
+    ),
+    ("d", "request data as JSON"),
+    ("H", "additional header for every request, 'name: value'"),
+    ("rpc-header", "additional header for the RPC only"),
+    (
+        "reflect-header",
+        "additional header for reflection requests only",
+    ),
+    ("format", "json or text"),
+    (
+        "emit-defaults",
+        "include fields with default values in the response",
+    ),
+    (
+        "allow-unknown-fields",
+        "ignore unknown fields in the request data",
+    ),
+    (
+        "use-reflection",
+        "use server reflection, true unless protos are given",
+    ),
+    ("proto", "proto source file to use instead of reflection"),
+    (
+        "protoset",
+        "compiled descriptor set to use instead of reflection",
+    ),
+    ("import-path", "directory to resolve -proto imports from"),
+    ("authority", "value of the :authority pseudo header"),
+    ("servername", "server name for TLS verification"),
+    ("connect-timeout", "seconds to wait for the connection"),
+    ("max-time", "seconds the whole call may take"),
+    ("keepalive-time", "seconds between keepalive pings"),
+    ("max-msg-sz", "largest response message size in bytes"),
+    ("cacert", "CA certificate file for the server"),
+    ("cert", "client certificate file"),
+    ("key", "client private key file"),
+    ("user-agent", "extra user-agent value"),
+    ("unix", "address is a unix socket path"),
+    ("format-error", "print errors in the chosen -format"),
+    ("expand-headers", "expand ${VAR} in header values"),
+    ("v", "verbose output"),
+    ("vv", "very verbose output"),
+];
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CompletionKind {
+    Flag,
+    Address,
+    Verb,
+    Service,
+    Method,
+    Field,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Completion {
+    // Inserted in place of the partial word
+    pub value: String,
+    pub kind: CompletionKind,
+    pub detail: Option<String>,
+}
+
+// What the word under the cursor is, `from` is the char offset the completion replaces from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CompletionContext {
+    Flag {
+        from: usize,
+        prefix: String,
+    },
+    Address {
+        from: usize,
+        prefix: String,
+    },
+    Symbol {
+        from: usize,
+        prefix: String,
+        target: Option<GrpcCommand>,
+        methods: bool,
+    },
+    Field {
+        from: usize,
+        prefix: String,
+        // Field names from the request message down to the object the cursor is in
+        path: Vec<String>,
+        used: Vec<String>,
+        quoted: bool,
+        command: Option<GrpcCommand>,
+    },
+    None,
+}
+
+#[derive(Debug, Clone)]
+struct Word {
+    value: String,
+    raw: String,
+    start: usize,
+    end: usize,
+}
+
+// Looks at the command the way the shell would, but never fails on half typed quotes
+pub fn completion_context(command: &str, cursor: usize) -> CompletionContext {
+    let chars = command.chars().collect::<Vec<_>>();
+    let cursor = cursor.min(chars.len());
+    let mut words = split_words(&chars);
+    if words.first().is_some_and(|word| word.value == "grpcurl") {
+        words.remove(0);
+    }
+
+    let current = words
+        .iter()
+        .position(|word| word.start <= cursor && cursor <= word.end);
+    let index = current.unwrap_or_else(|| words.iter().filter(|word| word.end < cursor).count());
+    let (from, prefix) = match current.and_then(|index| words.get(index)) {
+        Some(word) => (
+            word.start,
+            word.raw
+                .chars()
+                .take(cursor - word.start)
+                .collect::<String>(),
+        ),
+        None => (cursor, String::new()),
+    };
+
+    let values = |words: &[Word]| {
+        words
+            .iter()
+            .map(|word| word.value.clone())
+            .collect::<Vec<_>>()
+    };
+    let (flags, positional, pending) = walk(&values(&words[..index]));
+
+    if let Some(flag) = pending {
+        if flag != "d" {
+            return CompletionContext::None;
+        }
+        let Some(position) = unquote_prefix(&prefix).and_then(|json| json_position(&json)) else {
+            return CompletionContext::None;
+        };
+
+        // The rest of the command, without -d and its value, names the method
+        let rest = words
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i + 1 != index && Some(*i) != current)
+            .map(|(_, word)| word.value.clone())
+            .collect::<Vec<_>>();
+        let (flags, positional, _) = walk(&rest);
+
+        return CompletionContext::Field {
+            from: cursor - position.prefix.chars().count(),
+            prefix: position.prefix,
+            path: position.path,
+            used: position.used,
+            quoted: position.quoted,
+            command: parse_target(flags, positional),
+        };
+    }
+
+    if prefix.starts_with('-') {
+        return CompletionContext::Flag { from, prefix };
+    }
+
+    match positional.as_slice() {
+        [] => CompletionContext::Address { from, prefix },
+        [address] => CompletionContext::Symbol {
+            from,
+            prefix,
+            target: parse_target(flags, vec![address.clone(), "list".to_string()]),
+            methods: true,
+        },
+        [address, verb] if verb == "list" || verb == "describe" => CompletionContext::Symbol {
+            from,
+            prefix,
+            target: parse_target(flags, vec![address.clone(), "list".to_string()]),
+            methods: verb == "describe",
+        },
+        _ => CompletionContext::None,
+    }
+}
+
+// Splits words into flags (with their values) and positional arguments, and returns the flag
+// still waiting for its value at the end
+fn walk(words: &[String]) -> (Vec<String>, Vec<String>, Option<String>) {
+    let mut flags = Vec::new();
+    let mut positional = Vec::new();
+    let mut pending = None;
+
+    for word in words {
+        if pending.take().is_some() {
+            flags.push(word.clone());
+            continue;
+        }
+        match split_flag(word) {
+            Some((name, None)) if !BOOL_FLAGS.contains(&name.as_str()) => {
+                flags.push(word.clone());
+                pending = Some(name);
+            }
+            Some(_) => flags.push(word.clone()),
+            None => positional.push(word.clone()),
+        }
+    }
+
+    (flags, positional, pending)
+}
+
+fn parse_target(mut args: Vec<String>, positional: Vec<String>) -> Option<GrpcCommand> {
+    args.extend(positional);
+
+    GrpcCommand::from_tokens(Tokens { args, stdin: None })
+        .ok()
+        .filter(|command| command.address.is_some())
+}
+
+pub fn complete_flags(prefix: &str) -> Vec<Completion> {
+    let name = prefix.trim_start_matches('-');
+
+    GRPCURL_FLAGS
+        .iter()
+        .filter(|(flag, _)| flag.starts_with(name))
+        .map(|(flag, detail)| Completion {
+            value: format!("-{flag}"),
+            kind: CompletionKind::Flag,
+            detail: Some(detail.to_string()),
+        })
+        .collect()
+}
+
+pub fn complete_addresses(prefix: &str, known: &[String]) -> Vec<Completion> {
+    let mut addresses = known
+        .iter()
+        .filter(|address| address.starts_with(prefix))
+        .collect::<Vec<_>>();
+    addresses.dedup();
+
+    addresses
+        .into_iter()
+        .take(MAX_COMPLETIONS)
+        .map(|address| Completion {
+            value: address.clone(),
+            kind: CompletionKind::Address,
+            detail: Some("from history".to_string()),
+        })
+        .collect()
+}
+
+pub fn complete_verbs(prefix: &str) -> Vec<Completion> {
+    ["list", "describe"]
+        .into_iter()
+        .filter(|verb| verb.starts_with(prefix))
+        .map(|verb| Completion {
+            value: verb.to_string(),
+            kind: CompletionKind::Verb,
+            detail: None,
+        })
+        .collect()
+}
+
+// Services as `pkg.Service` and, when a method can go there, methods as `pkg.Service/Method`
+pub fn complete_symbols(pool: &DescriptorPool, prefix: &str, methods: bool) -> Vec<Completion> {
+    let mut completions = Vec::new();
+
+    for service in pool.services() {
+        if !methods && service.full_name().starts_with(prefix) {
+            completions.push(Completion {
+                value: service.full_name().to_string(),
+                kind: CompletionKind::Service,
+                detail: None,
+            });
+        }
+        if !methods {
+            continue;
+        }
+
+        for method in service.methods() {
+            let symbol = format!("{}/{}", service.full_name(), method.name());
+            if symbol.starts_with(prefix) {
+                completions.push(Completion {
+                    value: symbol,
+                    kind: CompletionKind::Method,
+                    detail: Some(format!(
+                        "{} -> {}",
+                        method.input().full_name(),
+                        method.output().full_name()
+                    )),
+                });
+            }
+        }
+    }
+
+    completions.truncate(MAX_COMPLETIONS);
+    completions
+}
+
+// Field names of the message the cursor is in, skipping the ones that are already set
+pub fn complete_fields(
+    input: &MessageDescriptor,
+    path: &[String],
+    prefix: &str,
+    used: &[String],
+    quoted: bool,
+) -> Vec<Completion> {
+    let mut message = input.clone();
+    for name in path {
+        let Some(field) = message
+            .fields()
+            .find(|field| field.json_name() == name || field.name() == name)
+        else {
+            return Vec::new();
+        };
+        match field.kind() {
+            Kind::Message(child) if !field.is_map() => message = child,
+            _ => return Vec::new(),
+        }
+    }
+
+    message
+        .fields()
+        .filter(|field| {
+            field.json_name().starts_with(prefix)
+                && !used
+                    .iter()
+                    .any(|name| name == field.json_name() || name == field.name())
+        })
+        .map(|field| Completion {
+            value: match quoted {
+                true => format!("{}\": ", field.json_name()),
+                false => format!("\"{}\": ", field.json_name()),
+            },
+            kind: CompletionKind::Field,
+            detail: Some(kind_label(&field.kind())),
+        })
+        .collect()
+}
+
+fn kind_label(kind: &Kind) -> String {
+    match kind {
+        Kind::Message(message) => message.full_name().to_string(),
+        Kind::Enum(enumeration) => enumeration.full_name().to_string(),
+        kind => format!("{kind:?}").to_lowercase(),
+    }
+}
+
+fn split_words(chars: &[char]) -> Vec<Word> {
+    let mut words = Vec::new();
+    let mut word = None::<Word>;
+    let mut quote = None::<char>;
+    let mut chars_iter = chars.iter().enumerate().peekable();
+
+    while let Some((i, &c)) = chars_iter.next() {
+        // Heredoc bodies aren't completed
+        if quote.is_none() && c == '<' && chars.get(i + 1) == Some(&'<') {
+            break;
+        }
+        if quote.is_none() && c.is_whitespace() {
+            if let Some(word) = word.take() {
+                words.push(word);
+            }
+            continue;
+        }
+
+        let current = word.get_or_insert_with(|| Word {
+            value: String::new(),
+            raw: String::new(),
+            start: i,
+            end: i,
+        });
+        current.raw.push(c);
+        current.end = i + 1;
+
+        match (quote, c) {
+            (None, '\'' | '"') => quote = Some(c),
+            (Some(open), c) if c == open => quote = None,
+            (Some('"') | None, '\\') => {
+                if let Some((_, &next)) = chars_iter.next() {
+                    current.raw.push(next);
+                    current.end = i + 2;
+                    current.value.push(next);
+                }
+            }
+            (_, c) => current.value.push(c),
+        }
+    }
+    words.extend(word);
+
+    words
+}
+
+// The JSON typed so far inside a (possibly still open) quoted -d value
+fn unquote_prefix(raw: &str) -> Option<String> {
+    match raw.chars().next() {
+        Some('\'') => Some(raw[1..].replace(r"'\''", "'")),
+        Some('"') => Some(raw[1..].replace("\\\"", "\"").replace("\\\\", "\\")),
+        Some(_) => Some(raw.to_string()),
+        None => None,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct JsonPosition {
+    prefix: String,
+    path: Vec<String>,
+    used: Vec<String>,
+    quoted: bool,
+}
+
+#[derive(Debug, Default)]
+struct Frame {
+    object: bool,
+    key: Option<String>,
+    expecting_key: bool,
+    used: Vec<String>,
+}
+
+// Where in the object tree the end of a JSON prefix is, if it's where a key goes
+fn json_position(json: &str) -> Option<JsonPosition> {
+    let mut frames = Vec::<Frame>::new();
+    let mut string = None::<(String, bool)>;
+    let mut escaped = false;
+
+    for c in json.chars() {
+        if let Some((text, is_key)) = string.as_mut() {
+            match (escaped, c) {
+                (true, c) => {
+                    text.push(c);
+                    escaped = false;
+                }
+                (false, '\\') => escaped = true,
+                (false, '"') => {
+                    if *is_key && let Some(frame) = frames.last_mut() {
+                        frame.key = Some(text.clone());
+                        frame.used.push(text.clone());
+                    }
+                    string = None;
+                }
+                (false, c) => text.push(c),
+            }
+            continue;
+        }
+
+        match c {
+            '{' => frames.push(Frame {
+                object: true,
+                expecting_key: true,
+                ..Frame::default()
+            }),
+            '[' => frames.push(Frame::default()),
+            '}' | ']' => {
+                frames.pop();
+            }
+            ':' => {
+                if let Some(frame) = frames.last_mut() {
+                    frame.expecting_key = false;
+                }
+            }
+            ',' => {
+                if let Some(frame) = frames.last_mut() {
+                    frame.expecting_key = frame.object;
+                }
+            }
+            '"' => {
+                let is_key = frames
+                    .last()
+                    .is_some_and(|frame| frame.object && frame.expecting_key);
+                string = Some((String::new(), is_key));
+            }
+            _ => {}
+        }
+    }
+
+    let (prefix, quoted) = match string {
+        Some((text, true)) => (text, true),
+        Some((_, false)) => return None,
+        None => (String::new(), false),
+    };
+    let innermost = frames.last()?;
+    if !innermost.object || !innermost.expecting_key {
+        return None;
+    }
+
+    let path = frames[..frames.len() - 1]
+        .iter()
+        .filter(|frame| frame.object)
+        .map(|frame| frame.key.clone())
+        .collect::<Option<Vec<_>>>()?;
+
+    Some(JsonPosition {
+        prefix,
+        path,
+        used: innermost.used.clone(),
+        quoted,
+    })
+}
@@ -0,0 +1,168 @@
+use miette::{Result, miette};
+use prost_reflect::{
+    DescriptorPool, EnumDescriptor, FieldDescriptor, Kind, MessageDescriptor, MethodDescriptor,
+    ServiceDescriptor,
+};
+
+// Mirrors grpcurl's describe output so existing habits (and history) still read the same
+pub fn describe_symbol(pool: &DescriptorPool, symbol: &str) -> Result<String> {
+    let symbol = symbol.trim_start_matches('.').replace('/', ".");
+
+    if let Some(service) = pool.get_service_by_name(&symbol) {
+        return Ok(format!(
+            "{} is a service:\n{}",
+            service.full_name(),
+            describe_service(&service)
+        ));
+    }
+    if let Some(message) = pool.get_message_by_name(&symbol) {
+        return Ok(format!(
+            "{} is a message:\n{}",
+            message.full_name(),
+            describe_message(&message, 0)
+        ));
+    }
+    if let Some(enumeration) = pool.get_enum_by_name(&symbol) {
+        return Ok(format!(
+            "{} is an enum:\n{}",
+            enumeration.full_name(),
+            describe_enum(&enumeration, 0)
+        ));
+    }
+    if let Some(method) = find_method(pool, &symbol) {
+        return Ok(format!(
+            "{} is a method:\n{}",
+            method.full_name(),
+            describe_method(&method)
+        ));
+    }
+
+    Err(miette!("Symbol not found: {symbol}"))
+}
+
+pub fn find_method(pool: &DescriptorPool, symbol: &str) -> Option<MethodDescriptor> {
+    let symbol = symbol.trim_start_matches('.').replace('/', ".");
+    let (service, method) = symbol.rsplit_once('.')?;
+
+    pool.get_service_by_name(service)?
+        .methods()
+        .find(|m| m.name() == method)
+}
+
+pub fn describe_service(service: &ServiceDescriptor) -> String {
+    let methods = service
+        .methods()
+        .map(|method| format!("  {}\n", describe_method(&method)))
+        .collect::<String>();
+
+    format!("service {} {{\n{methods}}}", service.name())
+}
+
+pub fn describe_method(method: &MethodDescriptor) -> String {
+    let stream = |streaming: bool| if streaming { "stream " } else { "" };
+
+    format!(
+        "rpc {} ( {}.{} ) returns ( {}.{} );",
+        method.name(),
+        stream(method.is_client_streaming()),
+        method.input().full_name(),
+        stream(method.is_server_streaming()),
+        method.output().full_name()
+    )
+}
+
+pub fn describe_message(message: &MessageDescriptor, depth: usize) -> String {
+    let indent = "  ".repeat(depth);
+    let mut lines = vec![format!("{indent}message {} {{", message.name())];
+
+    for child in message.child_messages().filter(|m| !m.is_map_entry()) {
+        lines.push(describe_message(&child, depth + 1));
+    }
+    for child in message.child_enums() {
+        lines.push(describe_enum(&child, depth + 1));
+    }
+
+    let mut open_oneof = None;
+    for field in message.fields() {
+        let oneof = field.containing_oneof().filter(|o| !o.is_synthetic());
+        let oneof_name = oneof.as_ref().map(|o| o.name().to_string());
+
+        if open_oneof != oneof_name {
+            if open_oneof.is_some() {
+                lines.push(format!("{indent}  }}"));
+            }
+            if let Some(name) = &oneof_name {
+                lines.push(format!("{indent}  oneof {name} {{"));
+            }
+            open_oneof = oneof_name;
+        }
+
+        let field_indent = if open_oneof.is_some() { "    " } else { "  " };
+        lines.push(format!(
+            "{indent}{field_indent}{} {} = {};",
+            field_type(&field),
+            field.name(),
+            field.number()
+        ));
+    }
+    if open_oneof.is_some() {
+        lines.push(format!("{indent}  }}"));
+    }
+
+    lines.push(format!("{indent}}}"));
+    lines.join("\n")
+}
+
+pub fn describe_enum(enumeration: &EnumDescriptor, depth: usize) -> String {
+    let indent = "  ".repeat(depth);
+    let values = enumeration
+        .values()
+        .map(|value| format!("{indent}  {} = {};\n", value.name(), value.number()))
+        .collect::<String>();
+
+    format!("{indent}enum {} {{\n{values}{indent}}}", enumeration.name())
+}
+
+fn field_type(field: &FieldDescriptor) -> String {
+    if field.is_map()
+        && let Kind::Message(entry) = field.kind()
+    {
+        return format!(
+            "map<{}, {}>",
+            kind_name(&entry.map_entry_key_field().kind()),
+            kind_name(&entry.map_entry_value_field().kind())
+        );
+    }
+
+    let label = if field.is_list() {
+        "repeated "
+    } else if field.field_descriptor_proto().proto3_optional() {
+        "optional "
+    } else {
+        ""
+    };
+
+    format!("{label}{}", kind_name(&field.kind()))
+}
+
+fn kind_name(kind: &Kind) -> String {
+    match kind {
+        Kind::Double => "double".to_string(),
+        Kind::Float => "float".to_string(),
+        Kind::Int32 => "int32".to_string(),
+        Kind::Int64 => "int64".to_string(),
+        Kind::Uint32 => "uint32".to_string(),
+        Kind::Uint64 => "uint64".to_string(),
+        Kind::Sint32 => "sint32".to_string(),
+        Kind::Sint64 => "sint64".to_string(),
+        Kind::Fixed32 => "fixed32".to_string(),
+        Kind::Fixed64 => "fixed64".to_string(),
+        Kind::Sfixed32 => "sfixed32".to_string(),
+        Kind::Sfixed64 => "sfixed64".to_string(),
+        Kind::Bool => "bool".to_string(),
+        Kind::String => "string".to_string(),
+        Kind::Bytes => "bytes".to_string(),
+        Kind::Message(message) => format!(".{}", message.full_name()),
+        Kind::Enum(enumeration) => format!(".{}", enumeration.full_name()),
+    }
+}
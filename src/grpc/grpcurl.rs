@@ -0,0 +1,104 @@
+use std::{process::Stdio, time::Duration};
+
+use miette::{Result, miette};
+use regex::Regex;
+use tokio::{process::Command, time::timeout};
+
+use crate::grpc::{Backend, ERROR_STATUS, GrpcCommand, GrpcResponse, status_name};
+
+pub async fn grpcurl_installed() -> bool {
+    Command::new("grpcurl")
+        .arg("-version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .status()
+        .await
+        .is_ok()
+}
+
+// Fallback backend, shells out to the grpcurl binary and scrapes its output. Dropping the
+// future kills the process, which is how calls get cancelled
+pub async fn execute_with_grpcurl(command: &GrpcCommand) -> Result<GrpcResponse> {
+    let process = Command::new("grpcurl")
+        .args(command.to_args())
+        .kill_on_drop(true)
+        .output();
+
+    // grpcurl enforces -max-time itself, this only catches it hanging past that
+    let output = match command.max_time() {
+        Some(max_time) => match timeout(max_time + Duration::from_secs(1), process).await {
+            Ok(output) => output,
+            Err(_) => {
+                return Ok(GrpcResponse {
+                    backend: Backend::Grpcurl,
+                    status: status_name(tonic::Code::DeadlineExceeded).to_string(),
+                    message: Some(format!(
+                        "grpcurl didn't finish within {}s",
+                        max_time.as_secs_f64()
+                    )),
+                    method: Some(command.method()),
+                    ..GrpcResponse::default()
+                });
+            }
+        },
+        None => process.await,
+    }
+    .map_err(|e| miette!("Failed to execute grpcurl command, may not be installed: {e}"))?;
+
+    let response = String::from_utf8_lossy(&output.stdout).to_string();
+    let error = String::from_utf8_lossy(&output.stderr).to_string();
+
+    let status_regex =
+        Regex::new(r"Code:\s*(\w+)").map_err(|e| miette!("Could not unwrap status regex: {e}"))?;
+    let status = if let Some(caps) = status_regex.captures(&error) {
+        caps.get(1)
+            .map(|m| screaming_snake_case(m.as_str()))
+            .unwrap_or("UNKNOWN".to_string())
+    } else if output.status.success() {
+        "OK".to_string()
+    } else {
+        ERROR_STATUS.to_string()
+    };
+
+    let error_response_regex = Regex::new(r"(?i)error|failed|exception")
+        .map_err(|e| miette!("Could not unwrap error regex: {e}"))?;
+    let failed = response.is_empty()
+        || !output.status.success()
+        || error_response_regex.is_match(&response)
+        || error_response_regex.is_match(&error);
+
+    if response.is_empty() && !error.contains("Code:") {
+        return Err(miette!("{}", error.trim()));
+    }
+
+    Ok(GrpcResponse {
+        backend: Backend::Grpcurl,
+        status: if failed && status == "OK" {
+            ERROR_STATUS.to_string()
+        } else {
+            status
+        },
+        message: failed.then(|| error.trim().to_string()),
+        body: if response.is_empty() {
+            Vec::new()
+        } else {
+            vec![response.trim_end().to_string()]
+        },
+        method: Some(command.method()),
+        ..GrpcResponse::default()
+    })
+}
+
+// grpcurl prints codes like NotFound, history and colors use the canonical NOT_FOUND
+fn screaming_snake_case(code: &str) -> String {
+    code.chars()
+        .enumerate()
+        .fold(String::new(), |mut name, (i, c)| {
+            if c.is_uppercase() && i > 0 {
+                name.push('_');
+            }
+            name.push(c.to_ascii_uppercase());
+            name
+        })
+}
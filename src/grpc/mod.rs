@@ -0,0 +1,272 @@
+use std::time::{Duration, Instant};
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use miette::{Result, miette};
+use prost::Message;
+use prost_types::Any;
+use serde::{Deserialize, Serialize};
+use tokio::time::timeout;
+use tonic::{Code, Status, metadata::MetadataMap};
+
+pub mod browse;
+pub mod calls;
+pub mod codec;
+pub mod command;
+pub mod complete;
+pub mod describe;
+pub mod grpcurl;
+pub mod native;
+pub mod reflection;
+pub mod registry;
+pub mod skeleton;
+pub mod stream;
+
+pub use browse::*;
+pub use calls::*;
+pub use codec::*;
+pub use command::*;
+pub use complete::*;
+pub use describe::*;
+pub use grpcurl::*;
+pub use native::*;
+pub use reflection::*;
+pub use registry::*;
+pub use skeleton::*;
+pub use stream::*;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    #[default]
+    Native,
+    Grpcurl,
+}
+
+impl Backend {
+    pub fn from_name(name: &str) -> Result<Backend> {
+        match name.trim().to_lowercase().as_str() {
+            "" | "native" => Ok(Backend::Native),
+            "grpcurl" => Ok(Backend::Grpcurl),
+            other => Err(miette!(
+                "Unknown gRPC backend \"{other}\", expected native or grpcurl"
+            )),
+        }
+    }
+}
+
+// Milliseconds, connect and first byte are only known to the native engine. First byte counts
+// from the moment the request is sent
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct Timings {
+    pub total_ms: Option<f64>,
+    pub connect_ms: Option<f64>,
+    pub first_byte_ms: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct GrpcResponse {
+    pub backend: Backend,
+    pub status: String,
+    pub message: Option<String>,
+    pub details: Vec<(String, String)>,
+    pub method: Option<String>,
+    pub headers: Vec<(String, String)>,
+    pub trailers: Vec<(String, String)>,
+    pub body: Vec<String>,
+    pub timings: Timings,
+}
+
+// google.rpc.Status, what servers pack into grpc-status-details-bin for rich errors
+#[derive(Clone, PartialEq, Message)]
+struct RpcStatus {
+    #[prost(int32, tag = "1")]
+    code: i32,
+    #[prost(string, tag = "2")]
+    message: String,
+    #[prost(message, repeated, tag = "3")]
+    details: Vec<Any>,
+}
+
+impl GrpcResponse {
+    // A call that never reached the server, or was given up on before it answered
+    pub fn from_status(backend: Backend, command: &GrpcCommand, status: &Status) -> Self {
+        let mut response = GrpcResponse {
+            backend,
+            method: Some(command.method()),
+            ..GrpcResponse::default()
+        };
+        response.apply(status_event(status));
+
+        response
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.status == "OK"
+    }
+
+    pub fn body_text(&self) -> String {
+        self.body.join("\n")
+    }
+
+    pub fn apply(&mut self, event: StreamEvent) {
+        match event {
+            StreamEvent::Headers {
+                headers,
+                first_byte_ms,
+            } => {
+                self.headers = headers;
+                self.timings.first_byte_ms = Some(first_byte_ms);
+            }
+            StreamEvent::Message { body } => self.body.push(body),
+            StreamEvent::Status {
+                status,
+                message,
+                details,
+                trailers,
+            } => {
+                self.status = status;
+                self.message = message;
+                self.details = details;
+                self.trailers = trailers;
+            }
+        }
+    }
+}
+
+// Runs a grpcurl style command with the configured backend, the native engine hands off to
+// the grpcurl binary for flags it doesn't understand yet (if grpcurl is installed)
+pub async fn execute(command: &GrpcCommand, backend: Backend) -> Result<GrpcResponse> {
+    let started = Instant::now();
+
+    let mut response = match backend {
+        Backend::Grpcurl => execute_with_grpcurl(command).await,
+        Backend::Native => match unsupported_flag(command) {
+            Some(_) if grpcurl_installed().await => execute_with_grpcurl(command).await,
+            Some(flag) => Err(miette!(
+                "{flag} is not supported by the native gRPC engine, install grpcurl to use it"
+            )),
+            None => execute_natively(command).await,
+        },
+    }?;
+    response.timings.total_ms = Some(millis(started.elapsed()));
+
+    Ok(response)
+}
+
+// A call that finished before the handler returned, or one that keeps streaming afterwards
+#[derive(Debug)]
+pub enum Execution {
+    Complete(GrpcResponse),
+    Streaming(StreamCall),
+}
+
+// Like execute, but streaming methods on the native engine come back as soon as the call is
+// open instead of after the server is done
+pub async fn start(command: &GrpcCommand, backend: Backend) -> Result<Execution> {
+    let started = Instant::now();
+
+    if backend != Backend::Native
+        || command.verb != Verb::Invoke
+        || unsupported_flag(command).is_some()
+    {
+        return Ok(Execution::Complete(execute(command, backend).await?));
+    }
+
+    let resolved = resolve_method(command).await?;
+    let mut call = start_call(resolved.channel, &resolved.method, &resolved.options).await?;
+    call.started = started;
+    call.timings.connect_ms = Some(millis(resolved.connect_time));
+
+    match resolved.method.is_client_streaming() || resolved.method.is_server_streaming() {
+        true => Ok(Execution::Streaming(call)),
+        false => Ok(Execution::Complete(call.collect().await)),
+    }
+}
+
+// Dialing and reflection happen before the call carries a deadline of its own, so the whole
+// start is bounded as well
+pub async fn start_with_deadline(
+    command: &GrpcCommand,
+    backend: Backend,
+    max_time: Duration,
+) -> Result<Execution> {
+    match timeout(max_time, start(command, backend)).await {
+        Ok(execution) => execution,
+        Err(_) => Ok(Execution::Complete(GrpcResponse::from_status(
+            backend,
+            command,
+            &Status::deadline_exceeded(format!(
+                "Call exceeded its {}s deadline",
+                max_time.as_secs_f64()
+            )),
+        ))),
+    }
+}
+
+// Recorded by the grpcurl backend when the command fails without a gRPC status
+pub const ERROR_STATUS: &str = "ERROR";
+
+pub fn status_name(code: Code) -> &'static str {
+    match code {
+        Code::Ok => "OK",
+        Code::Cancelled => "CANCELLED",
+        Code::Unknown => "UNKNOWN",
+        Code::InvalidArgument => "INVALID_ARGUMENT",
+        Code::DeadlineExceeded => "DEADLINE_EXCEEDED",
+        Code::NotFound => "NOT_FOUND",
+        Code::AlreadyExists => "ALREADY_EXISTS",
+        Code::PermissionDenied => "PERMISSION_DENIED",
+        Code::ResourceExhausted => "RESOURCE_EXHAUSTED",
+        Code::FailedPrecondition => "FAILED_PRECONDITION",
+        Code::Aborted => "ABORTED",
+        Code::OutOfRange => "OUT_OF_RANGE",
+        Code::Unimplemented => "UNIMPLEMENTED",
+        Code::Internal => "INTERNAL",
+        Code::Unavailable => "UNAVAILABLE",
+        Code::DataLoss => "DATA_LOSS",
+        Code::Unauthenticated => "UNAUTHENTICATED",
+    }
+}
+
+// Statuses that come from outside gURL are checked against every status gURL records, the code
+// names, ERROR from grpcurl and the numeric codes older history holds
+pub fn is_status_name(name: &str) -> bool {
+    name == ERROR_STATUS
+        || (0..=16).any(|code| status_name(Code::from(code)) == name || code.to_string() == name)
+}
+
+pub fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+// Type URL and base64 payload of every detail, the payloads are usually google.rpc error types
+pub fn status_details(details: &[u8]) -> Vec<(String, String)> {
+    if details.is_empty() {
+        return Vec::new();
+    }
+
+    match RpcStatus::decode(details) {
+        Ok(status) => status
+            .details
+            .into_iter()
+            .map(|detail| (detail.type_url, STANDARD.encode(detail.value)))
+            .collect(),
+        Err(_) => vec![("bytes".to_string(), STANDARD.encode(details))],
+    }
+}
+
+pub fn metadata_to_pairs(metadata: &MetadataMap) -> Vec<(String, String)> {
+    metadata
+        .clone()
+        .into_headers()
+        .iter()
+        .map(|(name, value)| {
+            let value = if name.as_str().ends_with("-bin") {
+                STANDARD.encode(value.as_bytes())
+            } else {
+                String::from_utf8_lossy(value.as_bytes()).to_string()
+            };
+            (name.to_string(), value)
+        })
+        .collect()
+}
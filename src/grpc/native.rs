@@ -0,0 +1,471 @@
+use std::{
+    fs,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use miette::{Result, miette};
+use prost_reflect::{
+    DescriptorPool, DeserializeOptions, DynamicMessage, MessageDescriptor, MethodDescriptor,
+    SerializeOptions,
+};
+use rustls::{
+    DigitallySignedStruct, SignatureScheme,
+    client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+    crypto::ring::default_provider,
+    pki_types::{CertificateDer, ServerName, UnixTime},
+};
+use tonic::{
+    metadata::{MetadataKey, MetadataMap, MetadataValue},
+    transport::{Channel, ClientTlsConfig, Endpoint},
+};
+
+use crate::grpc::{
+    Backend, Flag, GrpcCommand, GrpcResponse, ReflectionClient, Timings, Verb, describe_symbol,
+    find_method, millis, start_call,
+};
+
+const NATIVE_FLAGS: [&str; 16] = [
+    "plaintext",
+    "insecure",
+    "emit-defaults",
+    "allow-unknown-fields",
+    "use-reflection",
+    "v",
+    "vv",
+    "H",
+    "rpc-header",
+    "reflect-header",
+    "authority",
+    "servername",
+    "protoset",
+    "connect-timeout",
+    "max-time",
+    "format",
+];
+
+#[derive(Debug, Clone, Default)]
+pub struct NativeOptions {
+    pub plaintext: bool,
+    pub insecure: bool,
+    pub emit_defaults: bool,
+    pub allow_unknown_fields: bool,
+    pub use_reflection: Option<bool>,
+    pub authority: Option<String>,
+    pub servername: Option<String>,
+    pub data: Option<String>,
+    pub headers: Vec<String>,
+    pub rpc_headers: Vec<String>,
+    pub reflect_headers: Vec<String>,
+    pub protosets: Vec<String>,
+    pub connect_timeout: Option<Duration>,
+    pub max_time: Option<Duration>,
+}
+
+// Returns the first flag the native engine can't honor, so it can be handed to grpcurl
+pub fn unsupported_flag(command: &GrpcCommand) -> Option<String> {
+    command.flags.iter().find_map(|flag| {
+        if !NATIVE_FLAGS.contains(&flag.name.as_str()) {
+            Some(format!("-{}", flag.name))
+        } else if flag.name == "format" && flag.value.as_deref() != Some("json") {
+            Some(format!(
+                "-format {}",
+                flag.value.clone().unwrap_or_default()
+            ))
+        } else {
+            None
+        }
+    })
+}
+
+impl NativeOptions {
+    pub fn from_command(command: &GrpcCommand) -> Result<NativeOptions> {
+        let mut options = NativeOptions {
+            data: command.data.clone(),
+            ..NativeOptions::default()
+        };
+
+        for Flag { name, value } in &command.flags {
+            let enabled = || match value {
+                Some(value) => value
+                    .parse::<bool>()
+                    .map_err(|e| miette!("Invalid value for -{name}: {e}")),
+                None => Ok(true),
+            };
+            let value = || {
+                value
+                    .clone()
+                    .ok_or_else(|| miette!("Flag -{name} needs a value"))
+            };
+
+            match name.as_str() {
+                "plaintext" => options.plaintext = enabled()?,
+                "insecure" => options.insecure = enabled()?,
+                "emit-defaults" => options.emit_defaults = enabled()?,
+                "allow-unknown-fields" => options.allow_unknown_fields = enabled()?,
+                "use-reflection" => options.use_reflection = Some(enabled()?),
+                "H" => options.headers.push(value()?),
+                "rpc-header" => options.rpc_headers.push(value()?),
+                "reflect-header" => options.reflect_headers.push(value()?),
+                "authority" => options.authority = Some(value()?),
+                "servername" => options.servername = Some(value()?),
+                "protoset" => options.protosets.push(value()?),
+                "connect-timeout" => {
+                    options.connect_timeout = Some(parse_seconds(name, &value()?)?)
+                }
+                "max-time" => options.max_time = Some(parse_seconds(name, &value()?)?),
+                "v" | "vv" | "format" => {}
+                _ => return Err(miette!("Unknown flag -{name}")),
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ResolvedMethod {
+    pub channel: Channel,
+    pub method: MethodDescriptor,
+    pub options: NativeOptions,
+    pub connect_time: Duration,
+}
+
+pub async fn execute_natively(command: &GrpcCommand) -> Result<GrpcResponse> {
+    let options = NativeOptions::from_command(command)?;
+
+    let connecting = Instant::now();
+    let channel = match &command.address {
+        Some(address) => Some(connect(address, &options).await?),
+        None => None,
+    };
+    let connect_ms = channel.as_ref().map(|_| millis(connecting.elapsed()));
+    let mut source = DescriptorSource::new(channel.clone(), &options)?;
+
+    let response = GrpcResponse {
+        backend: Backend::Native,
+        status: "OK".to_string(),
+        method: Some(command.method()),
+        timings: Timings {
+            connect_ms,
+            ..Timings::default()
+        },
+        ..GrpcResponse::default()
+    };
+
+    match (command.verb, command.symbol.clone()) {
+        (Verb::List, None) => Ok(GrpcResponse {
+            body: vec![source.list_services().await?.join("\n")],
+            ..response
+        }),
+        (Verb::List, Some(service)) => {
+            let pool = source
+                .descriptor_pool(std::slice::from_ref(&service))
+                .await?;
+            let service = pool
+                .get_service_by_name(&service)
+                .ok_or_else(|| miette!("Service not found: {service}"))?;
+            let methods = service
+                .methods()
+                .map(|method| method.full_name().to_string())
+                .collect::<Vec<_>>();
+
+            Ok(GrpcResponse {
+                body: vec![methods.join("\n")],
+                ..response
+            })
+        }
+        (Verb::Describe, symbol) => {
+            let symbols = match symbol {
+                Some(symbol) => vec![symbol],
+                None => source.list_services().await?,
+            };
+            let pool = source.descriptor_pool(&symbols).await?;
+            let described = symbols
+                .iter()
+                .map(|symbol| describe_symbol(&pool, symbol))
+                .collect::<Result<Vec<_>>>()?;
+
+            Ok(GrpcResponse {
+                body: vec![described.join("\n\n")],
+                ..response
+            })
+        }
+        (Verb::Invoke, symbol) => {
+            let symbol = symbol.unwrap_or_default();
+            let channel = channel.ok_or_else(|| miette!("Missing address to invoke {symbol}"))?;
+            let method = source.method(&symbol).await?;
+
+            let mut response = invoke(channel, &method, &options).await?;
+            response.timings.connect_ms = connect_ms;
+
+            Ok(response)
+        }
+    }
+}
+
+// Connects and finds the descriptor of the method a command invokes
+pub async fn resolve_method(command: &GrpcCommand) -> Result<ResolvedMethod> {
+    let options = NativeOptions::from_command(command)?;
+    let symbol = command.symbol.clone().unwrap_or_default();
+    let address = command
+        .address
+        .as_deref()
+        .ok_or_else(|| miette!("Missing address to invoke {symbol}"))?;
+    let connecting = Instant::now();
+    let channel = connect(address, &options).await?;
+    let connect_time = connecting.elapsed();
+
+    let method = DescriptorSource::new(Some(channel.clone()), &options)?
+        .method(&symbol)
+        .await?;
+
+    Ok(ResolvedMethod {
+        channel,
+        method,
+        options,
+        connect_time,
+    })
+}
+
+// Every service a target exposes, with the files they need, for browsing instead of calling
+pub async fn load_descriptors(command: &GrpcCommand) -> Result<DescriptorPool> {
+    let options = NativeOptions::from_command(command)?;
+    let channel = match &command.address {
+        Some(address) => Some(connect(address, &options).await?),
+        None => None,
+    };
+
+    let mut source = DescriptorSource::new(channel, &options)?;
+    let services = source.list_services().await?;
+
+    source.descriptor_pool(&services).await
+}
+
+pub async fn invoke(
+    channel: Channel,
+    method: &MethodDescriptor,
+    options: &NativeOptions,
+) -> Result<GrpcResponse> {
+    Ok(start_call(channel, method, options).await?.collect().await)
+}
+
+pub fn parse_messages(
+    descriptor: &MessageDescriptor,
+    data: Option<&str>,
+    client_streaming: bool,
+    allow_unknown_fields: bool,
+) -> Result<Vec<DynamicMessage>> {
+    let data = data.unwrap_or_default().trim();
+
+    if data.is_empty() {
+        return Ok(match client_streaming {
+            true => Vec::new(),
+            false => vec![DynamicMessage::new(descriptor.clone())],
+        });
+    }
+
+    let options = DeserializeOptions::new().deny_unknown_fields(!allow_unknown_fields);
+
+    serde_json::Deserializer::from_str(data)
+        .into_iter::<serde_json::Value>()
+        .map(|value| {
+            let value = value.map_err(|e| miette!("Invalid JSON request data: {e}"))?;
+            DynamicMessage::deserialize_with_options(descriptor.clone(), value, &options)
+                .map_err(|e| miette!("Request data doesn't match {}: {e}", descriptor.full_name()))
+        })
+        .collect()
+}
+
+pub fn message_to_json(message: &DynamicMessage, emit_defaults: bool) -> Result<String> {
+    let mut serializer = serde_json::Serializer::pretty(Vec::new());
+
+    message
+        .serialize_with_options(
+            &mut serializer,
+            &SerializeOptions::new().skip_default_fields(!emit_defaults),
+        )
+        .map_err(|e| miette!("Could not format response message as JSON: {e}"))?;
+
+    String::from_utf8(serializer.into_inner())
+        .map_err(|e| miette!("Response message JSON is not valid UTF-8: {e}"))
+}
+
+pub fn build_metadata<'header>(
+    headers: impl Iterator<Item = &'header String>,
+) -> Result<MetadataMap> {
+    let mut metadata = MetadataMap::new();
+
+    for header in headers {
+        let (name, value) = header
+            .split_once(':')
+            .ok_or_else(|| miette!("Invalid header \"{header}\", expected name: value"))?;
+        let (name, value) = (name.trim().to_lowercase(), value.trim());
+
+        if name.ends_with("-bin") {
+            let key = MetadataKey::from_bytes(name.as_bytes())
+                .map_err(|e| miette!("Invalid header name {name}: {e}"))?;
+            metadata.append_bin(key, MetadataValue::from_bytes(value.as_bytes()));
+        } else {
+            let key = MetadataKey::from_bytes(name.as_bytes())
+                .map_err(|e| miette!("Invalid header name {name}: {e}"))?;
+            let value = MetadataValue::try_from(value)
+                .map_err(|e| miette!("Invalid header value for {name}: {e}"))?;
+            metadata.append(key, value);
+        }
+    }
+
+    Ok(metadata)
+}
+
+async fn connect(address: &str, options: &NativeOptions) -> Result<Channel> {
+    let scheme = if options.plaintext { "http" } else { "https" };
+    let mut endpoint = Endpoint::from_shared(format!("{scheme}://{address}"))
+        .map_err(|e| miette!("Invalid address \"{address}\": {e}"))?
+        .connect_timeout(options.connect_timeout.unwrap_or(Duration::from_secs(10)));
+
+    if let Some(authority) = &options.authority {
+        let origin = format!("{scheme}://{authority}")
+            .parse()
+            .map_err(|e| miette!("Invalid authority \"{authority}\": {e}"))?;
+        endpoint = endpoint.origin(origin);
+    }
+
+    if !options.plaintext {
+        let mut tls = ClientTlsConfig::new();
+        if let Some(servername) = options.servername.as_ref().or(options.authority.as_ref()) {
+            tls = tls.domain_name(servername.split(':').next().unwrap_or(servername));
+        }
+
+        endpoint = if options.insecure {
+            endpoint.tls_config_with_verifier(tls, Arc::new(SkipVerification))
+        } else {
+            endpoint.tls_config(tls.with_webpki_roots())
+        }
+        .map_err(|e| miette!("Invalid TLS configuration: {e}"))?;
+    }
+
+    endpoint.connect().await.map_err(|e| {
+        miette!(
+            "Failed to dial target host \"{address}\": {}",
+            source_chain(&e)
+        )
+    })
+}
+
+enum DescriptorSource {
+    Reflection(Box<ReflectionClient>),
+    Protoset(DescriptorPool),
+}
+
+impl DescriptorSource {
+    fn new(channel: Option<Channel>, options: &NativeOptions) -> Result<DescriptorSource> {
+        if !options.protosets.is_empty() || options.use_reflection == Some(false) {
+            let mut pool = DescriptorPool::new();
+            for protoset in &options.protosets {
+                let bytes = fs::read(protoset)
+                    .map_err(|e| miette!("Could not read protoset {protoset}: {e}"))?;
+                pool.decode_file_descriptor_set(bytes.as_slice())
+                    .map_err(|e| miette!("Invalid protoset {protoset}: {e}"))?;
+            }
+
+            return Ok(DescriptorSource::Protoset(pool));
+        }
+
+        let channel =
+            channel.ok_or_else(|| miette!("No address or protoset to resolve symbols with"))?;
+        let metadata = build_metadata(options.headers.iter().chain(&options.reflect_headers))?;
+
+        Ok(DescriptorSource::Reflection(Box::new(
+            ReflectionClient::new(channel, metadata),
+        )))
+    }
+
+    async fn list_services(&mut self) -> Result<Vec<String>> {
+        match self {
+            DescriptorSource::Reflection(client) => client.list_services().await,
+            DescriptorSource::Protoset(pool) => {
+                let mut services = pool
+                    .services()
+                    .map(|service| service.full_name().to_string())
+                    .collect::<Vec<_>>();
+                services.sort();
+                Ok(services)
+            }
+        }
+    }
+
+    async fn descriptor_pool(&mut self, symbols: &[String]) -> Result<DescriptorPool> {
+        match self {
+            DescriptorSource::Reflection(client) => client.descriptor_pool(symbols).await,
+            DescriptorSource::Protoset(pool) => Ok(pool.clone()),
+        }
+    }
+
+    async fn method(&mut self, symbol: &str) -> Result<MethodDescriptor> {
+        let pool = self.descriptor_pool(&[symbol.to_string()]).await?;
+
+        find_method(&pool, symbol).ok_or_else(|| miette!("Symbol not found: {symbol}"))
+    }
+}
+
+fn parse_seconds(name: &str, value: &str) -> Result<Duration> {
+    let seconds = value
+        .parse::<f64>()
+        .map_err(|e| miette!("Invalid value for -{name}: {e}"))?;
+
+    Duration::try_from_secs_f64(seconds).map_err(|e| miette!("Invalid value for -{name}: {e}"))
+}
+
+fn source_chain(error: &dyn std::error::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+
+    while let Some(error) = source {
+        message.push_str(&format!(": {error}"));
+        source = error.source();
+    }
+
+    message
+}
+
+// Backs grpcurl's -insecure flag, only meant for local servers with self signed certs
+#[derive(Debug)]
+struct SkipVerification;
+
+impl ServerCertVerifier for SkipVerification {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        _message: &[u8],
+        _cert: &CertificateDer<'_>,
+        _dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        Ok(HandshakeSignatureValid::assertion())
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        _message: &[u8],
+        _cert: &CertificateDer<'_>,
+        _dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        Ok(HandshakeSignatureValid::assertion())
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        default_provider()
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
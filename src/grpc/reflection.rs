@@ -0,0 +1,180 @@
+use std::collections::{HashMap, HashSet};
+
+use axum::http::uri::PathAndQuery;
+use miette::{Result, miette};
+use prost::Message;
+use prost_reflect::DescriptorPool;
+use prost_types::FileDescriptorProto;
+use tonic::{Code, Request, Status, client::Grpc, metadata::MetadataMap, transport::Channel};
+use tonic_prost::ProstCodec;
+use tonic_reflection::pb::v1::{
+    ServerReflectionRequest, ServerReflectionResponse, server_reflection_request::MessageRequest,
+    server_reflection_response::MessageResponse,
+};
+
+const REFLECTION_V1: &str = "/grpc.reflection.v1.ServerReflection/ServerReflectionInfo";
+const REFLECTION_V1ALPHA: &str = "/grpc.reflection.v1alpha.ServerReflection/ServerReflectionInfo";
+
+// v1 and v1alpha share the same messages, so only the path changes when a server is too old
+// for v1
+#[derive(Debug, Clone)]
+pub struct ReflectionClient {
+    grpc: Grpc<Channel>,
+    metadata: MetadataMap,
+    path: &'static str,
+}
+
+impl ReflectionClient {
+    pub fn new(channel: Channel, metadata: MetadataMap) -> ReflectionClient {
+        ReflectionClient {
+            grpc: Grpc::new(channel),
+            metadata,
+            path: REFLECTION_V1,
+        }
+    }
+
+    pub async fn list_services(&mut self) -> Result<Vec<String>> {
+        match self
+            .send(MessageRequest::ListServices(String::new()))
+            .await?
+        {
+            MessageResponse::ListServicesResponse(list) => {
+                let mut services = list
+                    .service
+                    .into_iter()
+                    .map(|service| service.name)
+                    .collect::<Vec<_>>();
+                services.sort();
+                Ok(services)
+            }
+            _ => Err(miette!(
+                "Server sent an unexpected reflection response to list"
+            )),
+        }
+    }
+
+    // Builds a pool out of the files defining the symbols and everything they import
+    pub async fn descriptor_pool(&mut self, symbols: &[String]) -> Result<DescriptorPool> {
+        let mut files = HashMap::new();
+
+        for symbol in symbols {
+            for file in self.file_containing_symbol(symbol).await? {
+                files.entry(file.name().to_string()).or_insert(file);
+            }
+        }
+
+        let mut requested = HashSet::new();
+        loop {
+            let missing = files
+                .values()
+                .flat_map(|file| file.dependency.clone())
+                .filter(|dependency| !files.contains_key(dependency))
+                .filter(|dependency| requested.insert(dependency.clone()))
+                .collect::<Vec<_>>();
+
+            if missing.is_empty() {
+                break;
+            }
+
+            for dependency in missing {
+                let response = self
+                    .send(MessageRequest::FileByFilename(dependency.clone()))
+                    .await?;
+                for file in decode_files(response)? {
+                    files.entry(file.name().to_string()).or_insert(file);
+                }
+            }
+        }
+
+        let mut pool = DescriptorPool::new();
+        pool.add_file_descriptor_protos(files.into_values())
+            .map_err(|e| miette!("Server sent invalid descriptors through reflection: {e}"))?;
+
+        Ok(pool)
+    }
+
+    // Servers don't always index methods or nested types, so walk up to the enclosing symbol
+    async fn file_containing_symbol(&mut self, symbol: &str) -> Result<Vec<FileDescriptorProto>> {
+        let mut symbol = symbol.replace('/', ".");
+
+        loop {
+            match self
+                .send(MessageRequest::FileContainingSymbol(symbol.clone()))
+                .await
+            {
+                Ok(response) => return decode_files(response),
+                Err(e) => match symbol.rsplit_once('.') {
+                    Some((parent, _)) if !parent.is_empty() => symbol = parent.to_string(),
+                    _ => return Err(e),
+                },
+            }
+        }
+    }
+
+    async fn send(&mut self, request: MessageRequest) -> Result<MessageResponse> {
+        match self.send_with_path(request.clone()).await {
+            Err(status) if status.code() == Code::Unimplemented && self.path == REFLECTION_V1 => {
+                self.path = REFLECTION_V1ALPHA;
+                self.send_with_path(request).await
+            }
+            response => response,
+        }
+        .map_err(|status| match status.code() {
+            Code::Unimplemented => {
+                miette!(
+                    "Server does not support the reflection API: {}",
+                    status.message()
+                )
+            }
+            Code::NotFound => miette!("Symbol not found: {}", status.message()),
+            Code::Unavailable => miette!("Failed to dial target host: {}", status.message()),
+            _ => miette!("Failed to query reflection API: {}", status.message()),
+        })
+    }
+
+    async fn send_with_path(&mut self, request: MessageRequest) -> Result<MessageResponse, Status> {
+        let mut message = Request::new(tokio_stream::once(ServerReflectionRequest {
+            host: String::new(),
+            message_request: Some(request),
+        }));
+        *message.metadata_mut() = self.metadata.clone();
+
+        self.grpc
+            .ready()
+            .await
+            .map_err(|e| Status::unavailable(e.to_string()))?;
+
+        let mut responses = self
+            .grpc
+            .streaming(
+                message,
+                PathAndQuery::from_static(self.path),
+                ProstCodec::<ServerReflectionRequest, ServerReflectionResponse>::default(),
+            )
+            .await?
+            .into_inner();
+
+        match responses.message().await?.and_then(|r| r.message_response) {
+            Some(MessageResponse::ErrorResponse(error)) => Err(Status::new(
+                Code::from_i32(error.error_code),
+                error.error_message,
+            )),
+            Some(response) => Ok(response),
+            None => Err(Status::unknown("Server sent an empty reflection response")),
+        }
+    }
+}
+
+fn decode_files(response: MessageResponse) -> Result<Vec<FileDescriptorProto>> {
+    match response {
+        MessageResponse::FileDescriptorResponse(response) => response
+            .file_descriptor_proto
+            .iter()
+            .map(|bytes| {
+                FileDescriptorProto::decode(bytes.as_slice())
+                    .map_err(|e| miette!("Could not decode file descriptor from reflection: {e}"))
+            })
+            .collect(),
+        _ => Err(miette!("Server sent an unexpected reflection response")),
+    }
+}
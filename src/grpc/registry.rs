@@ -0,0 +1,139 @@
+use std::{
+    collections::HashSet,
+    fs,
+    hash::{DefaultHasher, Hash, Hasher},
+    io::Write,
+    path::Path,
+};
+
+use miette::{Result, miette};
+use prost::Message;
+use prost_types::{FileDescriptorProto, FileDescriptorSet};
+use tempfile::{NamedTempFile, TempDir};
+
+use crate::grpc::{Flag, GrpcCommand};
+
+// Flags that already tell grpcurl (or the native client) where the descriptors come from
+const DESCRIPTOR_FLAGS: [&str; 3] = ["proto", "protoset", "import-path"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProtoSource {
+    // Source text, named by the path other files import it with
+    Proto { name: String, source: String },
+    // A compiled FileDescriptorSet, like `protoc --descriptor_set_out` or `buf build` write
+    Protoset { name: String, bytes: Vec<u8> },
+}
+
+// Compiles every uploaded .proto together so they can import each other, then merges them
+// with the uploaded protosets into one descriptor set
+pub fn build_protoset(sources: &[ProtoSource]) -> Result<Vec<u8>> {
+    let mut files = compile_protos(sources)?;
+
+    for source in sources {
+        if let ProtoSource::Protoset { name, bytes } = source {
+            let set = FileDescriptorSet::decode(bytes.as_slice())
+                .map_err(|e| miette!("Invalid protoset {name}: {e}"))?;
+            files.extend(set.file);
+        }
+    }
+
+    let mut seen = HashSet::new();
+    files.retain(|file| seen.insert(file.name().to_string()));
+
+    Ok(FileDescriptorSet { file: files }.encode_to_vec())
+}
+
+fn compile_protos(sources: &[ProtoSource]) -> Result<Vec<FileDescriptorProto>> {
+    let protos = sources
+        .iter()
+        .filter_map(|source| match source {
+            ProtoSource::Proto { name, source } => Some((name, source)),
+            ProtoSource::Protoset { .. } => None,
+        })
+        .collect::<Vec<_>>();
+    if protos.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // The parser only reads from disk, so the files are laid out by import path in a scratch dir
+    let root = TempDir::new().map_err(|e| miette!("Could not create proto directory: {e}"))?;
+    let mut inputs = Vec::new();
+    for (name, source) in protos {
+        let path = root.path().join(import_path(name)?);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| miette!("Could not create directory for {name}: {e}"))?;
+        }
+        fs::write(&path, source).map_err(|e| miette!("Could not write {name}: {e}"))?;
+        inputs.push(path);
+    }
+
+    let parsed = protobuf_parse::Parser::new()
+        .pure()
+        .include(root.path())
+        .inputs(&inputs)
+        .parse_and_typecheck()
+        .map_err(|e| miette!("Could not compile proto files: {e:#}"))?;
+
+    parsed
+        .file_descriptors
+        .iter()
+        .map(|file| {
+            let bytes = protobuf::Message::write_to_bytes(file)
+                .map_err(|e| miette!("Could not encode {}: {e}", file.name()))?;
+            FileDescriptorProto::decode(bytes.as_slice())
+                .map_err(|e| miette!("Could not decode {}: {e}", file.name()))
+        })
+        .collect()
+}
+
+// Import paths are relative and can't climb out of the scratch dir
+fn import_path(name: &str) -> Result<&Path> {
+    let path = Path::new(name);
+
+    if name.is_empty()
+        || path.is_absolute()
+        || path
+            .components()
+            .any(|component| !matches!(component, std::path::Component::Normal(_)))
+    {
+        return Err(miette!("Invalid import path \"{name}\""));
+    }
+
+    Ok(path)
+}
+
+// Registry files are only used when the command doesn't bring its own descriptors
+pub fn uses_registry(command: &GrpcCommand) -> bool {
+    command.address.is_some()
+        && !command
+            .flags
+            .iter()
+            .any(|flag| DESCRIPTOR_FLAGS.contains(&flag.name.as_str()))
+}
+
+// Writes the protoset where both backends can read it and points the command at it, the file
+// is removed when the returned handle is dropped
+pub fn attach_protoset(command: &mut GrpcCommand, protoset: &[u8]) -> Result<NamedTempFile> {
+    let mut file = tempfile::Builder::new()
+        .prefix("gurl-")
+        .suffix(".protoset")
+        .tempfile()
+        .map_err(|e| miette!("Could not create protoset file: {e}"))?;
+    file.write_all(protoset)
+        .map_err(|e| miette!("Could not write protoset file: {e}"))?;
+
+    command.flags.push(Flag {
+        name: "protoset".to_string(),
+        value: Some(file.path().to_string_lossy().to_string()),
+    });
+
+    Ok(file)
+}
+
+pub fn protoset_hash(protoset: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    protoset.hash(&mut hasher);
+
+    hasher.finish()
+}
@@ -0,0 +1,152 @@
+use prost_reflect::{FieldDescriptor, Kind, MessageDescriptor};
+use serde_json::{Map, Value};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Skeleton {
+    pub json: Value,
+    // What the JSON can't say by itself, like the other enum values or oneof members
+    pub notes: Vec<String>,
+}
+
+// A request body with every field set to a placeholder, in the proto3 JSON mapping so it can
+// be sent back as is
+pub fn message_skeleton(message: &MessageDescriptor) -> Skeleton {
+    let mut notes = Vec::new();
+    let json = message_value(message, "", &mut Vec::new(), &mut notes);
+
+    Skeleton { json, notes }
+}
+
+fn message_value(
+    message: &MessageDescriptor,
+    path: &str,
+    stack: &mut Vec<String>,
+    notes: &mut Vec<String>,
+) -> Value {
+    if let Some(value) = well_known_value(message) {
+        if message.full_name() == "google.protobuf.Any" {
+            notes.push(format!(
+                "{path}: set @type to the packed message's type URL and add its fields"
+            ));
+        }
+        return value;
+    }
+    // Recursive messages stop at the first repeat
+    if stack.iter().any(|name| name == message.full_name()) {
+        return Value::Object(Map::new());
+    }
+    stack.push(message.full_name().to_string());
+
+    let mut object = Map::new();
+    for field in message.fields() {
+        let field_path = join_path(path, field.json_name());
+
+        if let Some(oneof) = field.containing_oneof().filter(|o| !o.is_synthetic()) {
+            if oneof.fields().next().is_some_and(|first| first != field) {
+                continue;
+            }
+            if oneof.fields().len() > 1 {
+                notes.push(format!(
+                    "{}: set only one of {}",
+                    join_path(path, oneof.name()),
+                    oneof
+                        .fields()
+                        .map(|member| member.json_name().to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ));
+            }
+        }
+
+        object.insert(
+            field.json_name().to_string(),
+            field_value(&field, &field_path, stack, notes),
+        );
+    }
+
+    stack.pop();
+    Value::Object(object)
+}
+
+fn field_value(
+    field: &FieldDescriptor,
+    path: &str,
+    stack: &mut Vec<String>,
+    notes: &mut Vec<String>,
+) -> Value {
+    if field.is_map()
+        && let Kind::Message(entry) = field.kind()
+    {
+        let key = match kind_value(&entry.map_entry_key_field().kind(), path, stack, notes) {
+            Value::String(key) if key.is_empty() => "key".to_string(),
+            Value::String(key) => key,
+            key => key.to_string(),
+        };
+        let value = kind_value(&entry.map_entry_value_field().kind(), path, stack, notes);
+
+        return Value::Object(Map::from_iter([(key, value)]));
+    }
+
+    let value = kind_value(&field.kind(), path, stack, notes);
+    match field.is_list() {
+        true => Value::Array(vec![value]),
+        false => value,
+    }
+}
+
+fn kind_value(kind: &Kind, path: &str, stack: &mut Vec<String>, notes: &mut Vec<String>) -> Value {
+    match kind {
+        Kind::Double | Kind::Float => Value::from(0.0),
+        Kind::Int32 | Kind::Sint32 | Kind::Sfixed32 | Kind::Uint32 | Kind::Fixed32 => {
+            Value::from(0)
+        }
+        // 64 bit integers are strings in proto3 JSON
+        Kind::Int64 | Kind::Sint64 | Kind::Sfixed64 | Kind::Uint64 | Kind::Fixed64 => {
+            Value::from("0")
+        }
+        Kind::Bool => Value::from(false),
+        Kind::String | Kind::Bytes => Value::from(""),
+        Kind::Message(message) => message_value(message, path, stack, notes),
+        Kind::Enum(enumeration) => {
+            let values = enumeration
+                .values()
+                .map(|value| value.name().to_string())
+                .collect::<Vec<_>>();
+            notes.push(format!("{path}: one of {}", values.join(", ")));
+
+            Value::from(values.first().cloned().unwrap_or_default())
+        }
+    }
+}
+
+// Types that have their own JSON form instead of an object of their fields
+fn well_known_value(message: &MessageDescriptor) -> Option<Value> {
+    let value = match message.full_name() {
+        "google.protobuf.Timestamp" => Value::from("1970-01-01T00:00:00Z"),
+        "google.protobuf.Duration" => Value::from("0s"),
+        "google.protobuf.Struct" | "google.protobuf.Empty" => Value::Object(Map::new()),
+        "google.protobuf.ListValue" => Value::Array(Vec::new()),
+        "google.protobuf.Value" => Value::Null,
+        "google.protobuf.Any" => Value::Object(Map::from_iter([(
+            "@type".to_string(),
+            Value::from("type.googleapis.com/"),
+        )])),
+        "google.protobuf.DoubleValue" | "google.protobuf.FloatValue" => Value::from(0.0),
+        "google.protobuf.Int32Value" | "google.protobuf.UInt32Value" => Value::from(0),
+        "google.protobuf.Int64Value" | "google.protobuf.UInt64Value" => Value::from("0"),
+        "google.protobuf.BoolValue" => Value::from(false),
+        "google.protobuf.StringValue"
+        | "google.protobuf.BytesValue"
+        | "google.protobuf.FieldMask" => Value::from(""),
+        _ => return None,
+    };
+
+    Some(value)
+}
+
+fn join_path(path: &str, name: &str) -> String {
+    match path.is_empty() {
+        true => name.to_string(),
+        false => format!("{path}.{name}"),
+    }
+}
@@ -0,0 +1,334 @@
+use std::{
+    collections::HashMap,
+    future::pending,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use axum::http::uri::PathAndQuery;
+use miette::{Result, miette};
+use prost_reflect::{DynamicMessage, MessageDescriptor, MethodDescriptor};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel},
+    time::sleep,
+};
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use tonic::{Code, Request, Status, client::Grpc, transport::Channel};
+
+use crate::grpc::{
+    Backend, DynamicCodec, GrpcResponse, NativeOptions, Timings, build_metadata, message_to_json,
+    metadata_to_pairs, millis, new_call_id, parse_messages, status_details, status_name,
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum StreamEvent {
+    Headers {
+        headers: Vec<(String, String)>,
+        first_byte_ms: f64,
+    },
+    Message {
+        body: String,
+    },
+    Status {
+        status: String,
+        message: Option<String>,
+        details: Vec<(String, String)>,
+        trailers: Vec<(String, String)>,
+    },
+}
+
+// A call that is already on the wire, events arrive as the server sends them and, for client
+// and bidi streaming methods, more requests can be pushed through the sender until it's dropped
+#[derive(Debug)]
+pub struct StreamCall {
+    pub method: MethodDescriptor,
+    pub events: UnboundedReceiver<StreamEvent>,
+    pub sender: Option<UnboundedSender<DynamicMessage>>,
+    pub started: Instant,
+    pub timings: Timings,
+}
+
+impl StreamCall {
+    // Half closes the request stream and waits for the call to finish
+    pub async fn collect(mut self) -> GrpcResponse {
+        self.sender = None;
+
+        let mut response = GrpcResponse {
+            backend: Backend::Native,
+            method: Some(self.method.name().to_string()),
+            timings: self.timings,
+            ..GrpcResponse::default()
+        };
+        while let Some(event) = self.events.recv().await {
+            response.apply(event);
+        }
+        response.timings.total_ms = Some(millis(self.started.elapsed()));
+
+        response
+    }
+}
+
+pub async fn start_call(
+    channel: Channel,
+    method: &MethodDescriptor,
+    options: &NativeOptions,
+) -> Result<StreamCall> {
+    let started = Instant::now();
+    let messages = parse_messages(
+        &method.input(),
+        options.data.as_deref(),
+        method.is_client_streaming(),
+        options.allow_unknown_fields,
+    )?;
+    if !method.is_client_streaming() && messages.len() > 1 {
+        return Err(miette!(
+            "Method {} is not client streaming, provide only one request message",
+            method.full_name()
+        ));
+    }
+
+    let metadata = build_metadata(options.headers.iter().chain(&options.rpc_headers))?;
+    let path = PathAndQuery::from_str(&format!(
+        "/{}/{}",
+        method.parent_service().full_name(),
+        method.name()
+    ))
+    .map_err(|e| miette!("Invalid method path: {e}"))?;
+    let codec = DynamicCodec::new(method.output());
+
+    let mut grpc = Grpc::new(channel);
+    grpc.ready()
+        .await
+        .map_err(|e| miette!("Failed to dial target host: {e}"))?;
+
+    // Unary and server streaming calls take exactly one message, everything else goes through
+    // the request stream so the UI can keep adding to it
+    let client_streaming = method.is_client_streaming();
+    let mut messages = messages.into_iter();
+    let single_message = match client_streaming {
+        true => None,
+        false => Some(
+            messages
+                .next()
+                .ok_or_else(|| miette!("No request message provided"))?,
+        ),
+    };
+    let (request_sender, request_receiver) = unbounded_channel();
+    for message in messages {
+        request_sender
+            .send(message)
+            .map_err(|e| miette!("Could not queue request message: {e}"))?;
+    }
+    let sender = client_streaming.then_some(request_sender);
+
+    let (event_sender, events) = unbounded_channel();
+    let max_time = options.max_time;
+    let emit_defaults = options.emit_defaults;
+
+    tokio::spawn(async move {
+        // The receiving side going away only means nobody is watching anymore
+        let send = |event| event_sender.send(event).is_ok();
+
+        let call = async {
+            let sent = Instant::now();
+            let result = match single_message {
+                None => {
+                    let mut request = Request::new(UnboundedReceiverStream::new(request_receiver));
+                    *request.metadata_mut() = metadata;
+                    if let Some(max_time) = max_time {
+                        request.set_timeout(max_time);
+                    }
+                    grpc.streaming(request, path, codec).await
+                }
+                Some(message) => {
+                    let mut request = Request::new(message);
+                    *request.metadata_mut() = metadata;
+                    if let Some(max_time) = max_time {
+                        request.set_timeout(max_time);
+                    }
+                    grpc.server_streaming(request, path, codec).await
+                }
+            };
+
+            let streaming = match result {
+                Ok(streaming) => streaming,
+                Err(status) => return status_event(&status),
+            };
+            send(StreamEvent::Headers {
+                headers: metadata_to_pairs(streaming.metadata()),
+                first_byte_ms: millis(sent.elapsed()),
+            });
+
+            let mut stream = streaming.into_inner();
+            loop {
+                match stream.message().await {
+                    Ok(Some(message)) => match message_to_json(&message, emit_defaults) {
+                        Ok(body) => {
+                            send(StreamEvent::Message { body });
+                        }
+                        Err(e) => return status_event(&Status::internal(e.to_string())),
+                    },
+                    Ok(None) => break,
+                    Err(status) => return status_event(&status),
+                }
+            }
+
+            match stream.trailers().await {
+                Ok(trailers) => StreamEvent::Status {
+                    status: status_name(Code::Ok).to_string(),
+                    message: None,
+                    details: Vec::new(),
+                    trailers: trailers
+                        .map(|trailers| metadata_to_pairs(&trailers))
+                        .unwrap_or_default(),
+                },
+                Err(status) => status_event(&status),
+            }
+        };
+
+        // Servers don't always honor grpc-timeout, so the deadline is enforced here as well
+        let deadline = async {
+            match max_time {
+                Some(max_time) => sleep(max_time).await,
+                None => pending().await,
+            }
+        };
+
+        // Dropping the call future resets the HTTP/2 stream, which cancels it on the server
+        tokio::select! {
+            status = call => {
+                send(status);
+            }
+            () = deadline => {
+                send(status_event(&Status::deadline_exceeded(format!(
+                    "Call exceeded its {}s deadline",
+                    max_time.unwrap_or_default().as_secs_f64()
+                ))));
+            }
+            () = event_sender.closed() => {}
+        }
+    });
+
+    Ok(StreamCall {
+        method: method.clone(),
+        events,
+        sender,
+        started,
+        timings: Timings::default(),
+    })
+}
+
+pub fn status_event(status: &Status) -> StreamEvent {
+    StreamEvent::Status {
+        status: status_name(status.code()).to_string(),
+        message: Some(status.message().to_string()),
+        details: status_details(status.details()),
+        trailers: metadata_to_pairs(status.metadata()),
+    }
+}
+
+#[derive(Debug)]
+pub struct StreamSession {
+    pub owner: String,
+    pub input: MessageDescriptor,
+    pub allow_unknown_fields: bool,
+    pub events: Option<UnboundedReceiver<StreamEvent>>,
+    pub sender: Option<UnboundedSender<DynamicMessage>>,
+    pub finished: bool,
+}
+
+// Open calls by id, so the browser can follow one over SSE and keep writing into it. Someone
+// else's stream reads as missing, the id alone doesn't give access to it
+#[derive(Debug, Clone, Default)]
+pub struct StreamSessions(Arc<Mutex<HashMap<String, StreamSession>>>);
+
+impl StreamSessions {
+    pub fn insert(&self, session: StreamSession) -> Result<String> {
+        let id = new_call_id()?;
+        self.lock()?.insert(id.clone(), session);
+
+        Ok(id)
+    }
+
+    // Only one watcher per stream, a finished call is forgotten once its events are handed out
+    pub fn take_events(&self, owner: &str, id: &str) -> Result<UnboundedReceiver<StreamEvent>> {
+        let mut sessions = self.lock()?;
+        let session = sessions
+            .get_mut(id)
+            .filter(|session| session.owner == owner)
+            .ok_or_else(|| miette!("Stream {id} doesn't exist or already finished"))?;
+        let events = session
+            .events
+            .take()
+            .ok_or_else(|| miette!("Stream {id} is already being watched"))?;
+
+        if session.finished {
+            sessions.remove(id);
+        }
+
+        Ok(events)
+    }
+
+    // Parses one or more JSON messages and pushes them into the open request stream
+    pub fn send(&self, owner: &str, id: &str, data: &str) -> Result<usize> {
+        let sessions = self.lock()?;
+        let session = sessions
+            .get(id)
+            .filter(|session| session.owner == owner)
+            .ok_or_else(|| miette!("Stream {id} doesn't exist or already finished"))?;
+        let sender = session
+            .sender
+            .as_ref()
+            .ok_or_else(|| miette!("Stream {id} doesn't accept more request messages"))?;
+
+        let messages = parse_messages(
+            &session.input,
+            Some(data),
+            true,
+            session.allow_unknown_fields,
+        )?;
+        let count = messages.len();
+        for message in messages {
+            sender
+                .send(message)
+                .map_err(|_| miette!("Stream {id} already finished"))?;
+        }
+
+        Ok(count)
+    }
+
+    pub fn close_send(&self, owner: &str, id: &str) -> Result<()> {
+        self.lock()?
+            .get_mut(id)
+            .filter(|session| session.owner == owner)
+            .and_then(|session| session.sender.take())
+            .map(drop)
+            .ok_or_else(|| miette!("Stream {id} doesn't accept more request messages"))
+    }
+
+    // Called once the server is done, the session stays around until someone watched it
+    pub fn finish(&self, id: &str) -> Result<()> {
+        let mut sessions = self.lock()?;
+
+        match sessions.get_mut(id) {
+            Some(session) if session.events.is_some() => {
+                session.sender = None;
+                session.finished = true;
+            }
+            _ => {
+                sessions.remove(id);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, HashMap<String, StreamSession>>> {
+        self.0
+            .lock()
+            .map_err(|e| miette!("Stream sessions can't block current thread {e}"))
+    }
+}
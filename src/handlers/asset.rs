@@ -0,0 +1,34 @@
+use axum::{
+    Extension,
+    extract::Path,
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+};
+
+use crate::utils::{AssetOverride, VENDOR_CACHE_CONTROL, public_asset};
+
+pub async fn serve_public_asset(
+    Extension(assets): Extension<AssetOverride>,
+    Path(path): Path<String>,
+) -> Response {
+    match public_asset(assets.0.as_deref(), &path) {
+        Some(contents) => {
+            let mime = mime_guess::from_path(&path).first_or_octet_stream();
+            // Our own files keep their names between releases, so they're checked every time
+            let cache_control = match path.starts_with("vendor/") {
+                true => VENDOR_CACHE_CONTROL,
+                false => "no-cache",
+            };
+
+            (
+                [
+                    (header::CONTENT_TYPE, mime.to_string()),
+                    (header::CACHE_CONTROL, cache_control.to_string()),
+                ],
+                contents,
+            )
+                .into_response()
+        }
+        None => (StatusCode::NOT_FOUND).into_response(),
+    }
+}
@@ -0,0 +1,258 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use axum::{
+    extract::{Json, Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+
+use crate::{
+    handlers::{
+        ApiResult, AppError, AuthUser, CollectionBody, ConnectionState, OrderBody, PathParams,
+        SavedRequestBody, get_all_collections_from_db,
+    },
+    models::collection::OrderKind,
+    repository::Repository,
+    utils::mask_sensitive_headers,
+};
+
+pub async fn get_all_collections(state: ConnectionState, auth: AuthUser) -> ApiResult {
+    let collections = get_all_collections_from_db(state, auth.owner()).await?;
+
+    Ok((StatusCode::OK, Json(collections)).into_response())
+}
+
+// New collections and folders go after their siblings
+pub async fn create_collection(
+    State(state): ConnectionState,
+    auth: AuthUser,
+    Json(collection): Json<CollectionBody>,
+) -> ApiResult {
+    let email = auth.owner();
+    let name = collection.name.trim().to_string();
+
+    if name.is_empty() {
+        return Err(AppError::bad_request("Collection name is required"));
+    }
+    if let Some(parent_id) = collection.parent_id
+        && !owns_collection(&state, &email, parent_id).await?
+    {
+        return Err(AppError::not_found("Collection not found"));
+    }
+
+    match state
+        .create_collection(email, collection.parent_id, name, now())
+        .await?
+    {
+        Some(collection) => Ok((StatusCode::OK, Json(collection)).into_response()),
+        None => Err(AppError::not_found("Collection not found")),
+    }
+}
+
+// Renames a collection, or moves it (to the end of its new parent) when the parent changes
+pub async fn update_collection(
+    State(state): ConnectionState,
+    auth: AuthUser,
+    Path(path): Path<PathParams>,
+    Json(collection): Json<CollectionBody>,
+) -> ApiResult {
+    let email = auth.owner();
+    let collection_id = path
+        .id
+        .ok_or_else(|| AppError::bad_request("Missing collection id"))?
+        .get();
+    let name = collection.name.trim().to_string();
+
+    if name.is_empty() {
+        return Err(AppError::bad_request("Collection name is required"));
+    }
+    if !owns_collection(&state, &email, collection_id).await? {
+        return Err(AppError::not_found("Collection not found"));
+    }
+    check_parent(&state, &email, collection_id, collection.parent_id).await?;
+
+    match state
+        .update_collection(email, collection_id, name, collection.parent_id)
+        .await?
+    {
+        Some(collection) => Ok((StatusCode::OK, Json(collection)).into_response()),
+        None => Err(AppError::not_found("Collection not found")),
+    }
+}
+
+// Removes the collection with every folder and saved request under it
+pub async fn delete_collection(
+    State(state): ConnectionState,
+    auth: AuthUser,
+    Path(path): Path<PathParams>,
+) -> ApiResult {
+    let collection_id = path
+        .id
+        .ok_or_else(|| AppError::bad_request("Missing collection id"))?;
+
+    match state
+        .delete_collection(auth.owner(), collection_id.get())
+        .await?
+    {
+        true => Ok((StatusCode::OK).into_response()),
+        false => Err(AppError::not_found("Collection not found")),
+    }
+}
+
+// Saves a command under a collection or folder, credentials in headers are masked like in
+// the history
+pub async fn create_saved_request(
+    State(state): ConnectionState,
+    auth: AuthUser,
+    Json(request): Json<SavedRequestBody>,
+) -> ApiResult {
+    let email = auth.owner();
+    let title = request.title.trim().to_string();
+
+    if title.is_empty() || request.command.trim().is_empty() {
+        return Err(AppError::bad_request("Title and command are required"));
+    }
+    if !owns_collection(&state, &email, request.collection_id).await? {
+        return Err(AppError::not_found("Collection not found"));
+    }
+
+    match state
+        .create_saved_request(
+            request.collection_id,
+            title,
+            request.description,
+            mask_sensitive_headers(&request.command)?,
+            now(),
+        )
+        .await?
+    {
+        Some(request) => Ok((StatusCode::OK, Json(request)).into_response()),
+        None => Err(AppError::not_found("Saved request not found")),
+    }
+}
+
+pub async fn update_saved_request(
+    State(state): ConnectionState,
+    auth: AuthUser,
+    Path(path): Path<PathParams>,
+    Json(request): Json<SavedRequestBody>,
+) -> ApiResult {
+    let email = auth.owner();
+    let request_id = path
+        .id
+        .ok_or_else(|| AppError::bad_request("Missing saved request id"))?
+        .get();
+    let title = request.title.trim().to_string();
+
+    if title.is_empty() || request.command.trim().is_empty() {
+        return Err(AppError::bad_request("Title and command are required"));
+    }
+    if state
+        .saved_request_collection(email.clone(), request_id)
+        .await?
+        .is_none()
+    {
+        return Err(AppError::not_found("Saved request not found"));
+    }
+    if !owns_collection(&state, &email, request.collection_id).await? {
+        return Err(AppError::not_found("Collection not found"));
+    }
+
+    match state
+        .update_saved_request(
+            email,
+            request_id,
+            request.collection_id,
+            title,
+            request.description,
+            mask_sensitive_headers(&request.command)?,
+        )
+        .await?
+    {
+        Some(request) => Ok((StatusCode::OK, Json(request)).into_response()),
+        None => Err(AppError::not_found("Saved request not found")),
+    }
+}
+
+pub async fn delete_saved_request(
+    State(state): ConnectionState,
+    auth: AuthUser,
+    Path(path): Path<PathParams>,
+) -> ApiResult {
+    let request_id = path
+        .id
+        .ok_or_else(|| AppError::bad_request("Missing saved request id"))?;
+
+    match state
+        .delete_saved_request(auth.owner(), request_id.get())
+        .await?
+    {
+        true => Ok((StatusCode::OK).into_response()),
+        false => Err(AppError::not_found("Saved request not found")),
+    }
+}
+
+// Puts the items under the parent in the given order, moving in the ones that were elsewhere,
+// so a drop and a keyboard move are the same request
+pub async fn reorder_collection(
+    State(state): ConnectionState,
+    auth: AuthUser,
+    Json(order): Json<OrderBody>,
+) -> ApiResult {
+    if order.kind == OrderKind::Request && order.parent_id.is_none() {
+        return Err(AppError::bad_request("Saved requests need a collection"));
+    }
+
+    // Ownership and the items under the parent are checked with the writes, so nothing can change
+    // in between
+    match state
+        .reorder_collection(auth.owner(), order.kind, order.parent_id, order.ids)
+        .await?
+    {
+        true => Ok((StatusCode::OK).into_response()),
+        false => Err(AppError::bad_request(
+            "The order has to list every item under the collection once, and only items that can move there",
+        )),
+    }
+}
+
+async fn owns_collection(
+    state: &Repository,
+    email: &str,
+    collection_id: i32,
+) -> Result<bool, AppError> {
+    Ok(state
+        .collection_parent(email.to_string(), collection_id)
+        .await?
+        .is_some())
+}
+
+// The new parent has to belong to the user and can't be the collection or one of its folders
+async fn check_parent(
+    state: &Repository,
+    email: &str,
+    collection_id: i32,
+    parent_id: Option<i32>,
+) -> Result<(), AppError> {
+    let Some(parent_id) = parent_id else {
+        return Ok(());
+    };
+    if !owns_collection(state, email, parent_id).await? {
+        return Err(AppError::not_found("Collection not found"));
+    }
+
+    match state.collection_contains(collection_id, parent_id).await? {
+        true => Err(AppError::bad_request(
+            "A collection can't be moved into itself or one of its folders",
+        )),
+        false => Ok(()),
+    }
+}
+
+fn now() -> String {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis()
+        .to_string()
+}
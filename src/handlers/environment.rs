@@ -0,0 +1,147 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use axum::{
+    extract::{Json, Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+
+use crate::{
+    handlers::{
+        ApiResult, AppError, AuthUser, ConnectionState, EnvironmentBody, PathParams, VariableBody,
+        get_all_environments_from_db, mask_variable,
+    },
+    utils::{encrypt_secret, secret_key},
+};
+
+pub async fn get_all_environments(state: ConnectionState, auth: AuthUser) -> ApiResult {
+    let environments = get_all_environments_from_db(state, auth.owner()).await?;
+
+    Ok((StatusCode::OK, Json(environments)).into_response())
+}
+
+pub async fn create_environment(
+    State(state): ConnectionState,
+    auth: AuthUser,
+    Json(environment): Json<EnvironmentBody>,
+) -> ApiResult {
+    let email = auth.owner();
+    let Some(name) = environment
+        .name
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+    else {
+        return Err(AppError::bad_request("Environment name is required"));
+    };
+
+    match state.create_environment(email, name, now()).await? {
+        Some(environment) => Ok((StatusCode::OK, Json(environment)).into_response()),
+        None => Err(AppError::not_found("Environment not found")),
+    }
+}
+
+// Setting a key that already exists in the environment replaces its value, secret values are
+// encrypted with the server key before they're stored
+pub async fn set_variable(
+    State(state): ConnectionState,
+    auth: AuthUser,
+    Json(variable): Json<VariableBody>,
+) -> ApiResult {
+    let email = auth.owner();
+    let name = variable.environment.trim().to_string();
+    let key = variable.key.trim().to_string();
+
+    if name.is_empty() || key.is_empty() {
+        return Err(AppError::bad_request("Environment and key are required"));
+    }
+    if !key
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-'))
+    {
+        return Err(AppError::bad_request(
+            "Keys can only use letters, digits, _, . and -",
+        ));
+    }
+
+    let value = match variable.secret {
+        true => match secret_key() {
+            Ok(secret_key) => encrypt_secret(&secret_key, &variable.value)?,
+            Err(e) => return Err(AppError::bad_request(e.to_string())),
+        },
+        false => variable.value,
+    };
+
+    match state
+        .set_variable(email, name, key, value, variable.secret, now())
+        .await?
+    {
+        Some(variable) => Ok((StatusCode::OK, Json(mask_variable(variable))).into_response()),
+        None => Err(AppError::not_found("Variable not found")),
+    }
+}
+
+pub async fn delete_variable(
+    State(state): ConnectionState,
+    auth: AuthUser,
+    Path(path): Path<PathParams>,
+) -> ApiResult {
+    let email = auth.owner();
+    let variable_id = path
+        .id
+        .ok_or_else(|| AppError::bad_request("Missing variable id"))?;
+
+    match state.delete_variable(email, variable_id.get()).await? {
+        true => Ok((StatusCode::OK).into_response()),
+        false => Err(AppError::not_found("Variable not found")),
+    }
+}
+
+pub async fn delete_environment(
+    State(state): ConnectionState,
+    auth: AuthUser,
+    Path(path): Path<PathParams>,
+) -> ApiResult {
+    let email = auth.owner();
+    let environment_id = path
+        .id
+        .ok_or_else(|| AppError::bad_request("Missing environment id"))?;
+
+    match state
+        .delete_environment(email, environment_id.get())
+        .await?
+    {
+        true => Ok((StatusCode::OK).into_response()),
+        false => Err(AppError::not_found("Environment not found")),
+    }
+}
+
+// Makes the named environment the only active one, no name turns variables off
+pub async fn activate_environment(
+    State(state): ConnectionState,
+    auth: AuthUser,
+    Json(environment): Json<EnvironmentBody>,
+) -> ApiResult {
+    let email = auth.owner();
+    let name = environment.name.unwrap_or_default();
+
+    if !name.is_empty()
+        && state
+            .environment(email.clone(), name.clone())
+            .await?
+            .is_none()
+    {
+        return Err(AppError::not_found("Environment not found"));
+    }
+
+    state.activate_environment(email, name).await?;
+
+    Ok((StatusCode::OK).into_response())
+}
+
+fn now() -> String {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis()
+        .to_string()
+}
@@ -0,0 +1,116 @@
+use std::fmt;
+
+use axum::{
+    Json,
+    http::StatusCode,
+    response::{Html, IntoResponse, Response},
+};
+use miette::Report;
+use serde::{Deserialize, Serialize};
+
+use crate::handlers::escape_html;
+
+pub type ApiResult = Result<Response, AppError>;
+pub type HtmlResult = Result<Response, HtmlError>;
+
+// Anything a handler can fail with, /api routes answer with it as json
+#[derive(Debug)]
+pub enum AppError {
+    BadRequest(String),
+    Unauthorized(String),
+    Forbidden(String),
+    NotFound(String),
+    Unprocessable(String),
+    Internal(Report),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ErrorBody {
+    pub error: String,
+}
+
+impl AppError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Unprocessable(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        AppError::NotFound(message.into())
+    }
+
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        AppError::BadRequest(message.into())
+    }
+
+    // A token that can't be read is the client's problem, not a server error
+    pub fn unauthorized(error: Report) -> Self {
+        AppError::Unauthorized(error.to_string())
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::BadRequest(message)
+            | AppError::Unauthorized(message)
+            | AppError::Forbidden(message)
+            | AppError::NotFound(message)
+            | AppError::Unprocessable(message) => write!(f, "{message}"),
+            AppError::Internal(e) => write!(f, "Server Error: {e}"),
+        }
+    }
+}
+
+impl From<Report> for AppError {
+    fn from(error: Report) -> Self {
+        AppError::Internal(error)
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        (
+            self.status(),
+            Json(ErrorBody {
+                error: self.to_string(),
+            }),
+        )
+            .into_response()
+    }
+}
+
+// The same errors for /handle and /grpcurl routes, printed in the terminal like any other output
+#[derive(Debug)]
+pub struct HtmlError(pub AppError);
+
+impl From<AppError> for HtmlError {
+    fn from(error: AppError) -> Self {
+        HtmlError(error)
+    }
+}
+
+impl From<Report> for HtmlError {
+    fn from(error: Report) -> Self {
+        HtmlError(AppError::Internal(error))
+    }
+}
+
+impl IntoResponse for HtmlError {
+    fn into_response(self) -> Response {
+        (
+            self.0.status(),
+            Html(format!(
+                "<p>$  error: {}</p>",
+                escape_html(&self.0.to_string())
+            )),
+        )
+            .into_response()
+    }
+}
@@ -0,0 +1,46 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+
+use crate::handlers::{ApiResult, AppError, AuthUser, ConnectionState, PathParams};
+
+pub async fn add_favorite(
+    State(state): ConnectionState,
+    auth: AuthUser,
+    Path(path): Path<PathParams>,
+) -> ApiResult {
+    let email = auth.owner();
+    let request_id = path
+        .id
+        .ok_or_else(|| AppError::bad_request("Missing request id"))?;
+    let date = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis()
+        .to_string();
+
+    match state.add_favorite(email, request_id.get(), date).await? {
+        true => Ok((StatusCode::OK).into_response()),
+        false => Err(AppError::not_found("Request not found")),
+    }
+}
+
+pub async fn remove_favorite(
+    State(state): ConnectionState,
+    auth: AuthUser,
+    Path(path): Path<PathParams>,
+) -> ApiResult {
+    let email = auth.owner();
+    let request_id = path
+        .id
+        .ok_or_else(|| AppError::bad_request("Missing request id"))?;
+
+    match state.remove_favorite(email, request_id.get()).await? {
+        true => Ok((StatusCode::OK).into_response()),
+        false => Err(AppError::not_found("Favorite not found")),
+    }
+}
@@ -1,147 +1,503 @@
-#[cfg(windows)]
-use std::os::windows::process::CommandExt;
-
-use std::{
-    process::Command,
-    time::{SystemTime, UNIX_EPOCH},
-};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use axum::{
+    Extension,
     extract::{Json, Path, State},
     http::StatusCode,
-    response::{Html, IntoResponse, Response},
+    response::{
+        Html, IntoResponse, Response,
+        sse::{Event, KeepAlive, Sse},
+    },
 };
 use miette::{Result, miette};
-use regex::Regex;
+use tokio::sync::{mpsc::unbounded_channel, oneshot::Receiver};
+use tokio_stream::{StreamExt, wrappers::UnboundedReceiverStream};
+use tonic::Status;
 
 use crate::{
-    handlers::{ConnectionState, PathParams, RequestBody, create_request},
+    grpc::{
+        Backend, CompletionContext, DescriptorCache, Execution, GrpcCommand, GrpcResponse,
+        InFlightCalls, NativeOptions, StreamCall, StreamEvent, StreamSession, StreamSessions, Verb,
+        attach_protoset, complete_addresses, complete_fields, complete_flags, complete_symbols,
+        complete_verbs, completion_context, find_method, message_skeleton, millis,
+        start_with_deadline, status_event,
+    },
+    handlers::{
+        ApiResult, AppError, AuthUser, CompletionBody, CompletionResponse, ConnectionState,
+        HtmlResult, RequestBody, SkeletonResponse, StreamMessageBody, escape_html,
+        get_command_protoset, get_history_addresses_from_db, render_response_metadata,
+        resolve_command_variables, response_max_size,
+    },
     models::request::Request,
+    repository::Repository,
+    utils::{env::env, mask_sensitive_headers},
 };
 
 pub async fn execute_grpcurl_request(
     State(state): ConnectionState,
-    Path(path): Path<PathParams>,
+    Extension(streams): Extension<StreamSessions>,
+    Extension(calls): Extension<InFlightCalls>,
+    auth: AuthUser,
     Json(request): Json<RequestBody>,
-) -> Response {
-    let res: Result<Response> = (async || {
-        #[cfg(windows)]
-        let output = Command::new("grpcurl").raw_arg(&request.command.trim_end())
-            .output()
-            .map_err(|e| miette!("Failed to execute grpcurl command, may not be installed: {}", e))?;
-
-        #[cfg(not(windows))]
-        let output = Command::new("grpcurl").args(&request.command.trim_end().split_whitespace().collect::<Vec<_>>())
-            .output()
-            .map_err(|e| miette!("Failed to execute grpcurl command, may not be installed: {}", e))?;
-
-        let response = String::from_utf8_lossy(&output.stdout).to_string();
-        let error = String::from_utf8_lossy(&output.stderr).to_string();
-
-        let status_regex = Regex::new(r"Code:\s*(\w+)").map_err(|e| miette!("Could not unwrap status regex: {e}"))?;
-        let status = if let Some(caps) = status_regex.captures(&error) {
-            caps.get(1).map(|m| m.as_str()).unwrap_or("UNKNOWN")
-        } else if output.status.success() {
-            "OK"
-        } else {
-            "ERROR"
+) -> HtmlResult {
+    let email = auth.owner();
+    let env = env()?;
+    let backend = Backend::from_name(&env.grpc_backend)?;
+    let max_time = env
+        .grpc_max_time
+        .parse::<f64>()
+        .ok()
+        .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+        .ok_or_else(|| miette!("Invalid GRPC_MAX_TIME \"{}\"", env.grpc_max_time))?;
+
+    // History keeps the template, the resolved command is only what gets sent
+    let resolved = resolve_command_variables(State(state.clone()), email.clone(), &request.command)
+        .await
+        .map_err(|e| AppError::Unprocessable(e.to_string()))?;
+    // Neither secret variables nor credentials typed into headers make it into the history
+    let commands = (
+        mask_sensitive_headers(&request.command)?,
+        mask_sensitive_headers(&resolved.masked)?,
+    );
+    let mut command = GrpcCommand::parse(&resolved.command)
+        .map_err(|e| AppError::Unprocessable(e.to_string()))?;
+    command.limit_max_time(max_time);
+
+    // Kept alive until the call is started, both backends read the file while resolving
+    let _protoset =
+        match get_command_protoset(State(state.clone()), email.clone(), &command).await? {
+            Some(protoset) => Some(attach_protoset(&mut command, &protoset)?),
+            None => None,
         };
 
-        if response.is_empty() {
-            if cfg!(feature = "docker") && error.contains("Failed to dial target host") {
-                return Ok((
-                        StatusCode::OK,
-                        Html(format!("$  error: may not be using host.docker.internal. {}<br /><br />status: {}", error, status)),
-                )
-                    .into_response());
-            } else {
-                return Ok((
-                        StatusCode::OK,
-                        Html(format!("$  error: {}<br /><br />status: {}", error, status)),
-                )
-                    .into_response());
-            }
+    // Losing the race drops the call future, which resets the stream or kills grpcurl
+    let (call_id, mut cancelled) = calls
+        .register(&email, request.call.clone())
+        .map_err(|e| AppError::bad_request(e.to_string()))?;
+    let execution = tokio::select! {
+        execution = start_with_deadline(&command, backend, command.max_time().unwrap_or(max_time)) => execution,
+        Ok(()) = &mut cancelled => Ok(Execution::Complete(GrpcResponse::from_status(
+            backend,
+            &command,
+            &Status::cancelled("Call cancelled from gURL"),
+        ))),
+    };
+
+    let response = match execution {
+        Ok(Execution::Complete(response)) => {
+            calls.remove(&email, &call_id)?;
+            response
+        }
+        Ok(Execution::Streaming(call)) => {
+            let html = watch_stream(
+                state,
+                streams,
+                (calls, call_id, cancelled),
+                email,
+                commands,
+                &command,
+                call,
+            )?;
+            return Ok((StatusCode::OK, Html(html)).into_response());
         }
+        Err(e) => {
+            calls.remove(&email, &call_id)?;
+            let error = escape_html(&e.to_string());
 
-        let method = if request.command.contains("list") {
-            Some("list".to_string())
-        } else if let Some(service) = request.command.split(".").last() {
-            if service.contains("/") {
-                Some(service.split("/").last().unwrap_or_default().to_string())
-            } else {
-                Some(service.to_string())
-            }
-        } else { None };
-
-        create_request(
-            State(state),
-            Path(path.clone()),
-            Json(Request {
-                id: None,
-                user_email: path.email,
-                command: request.command,
-                status: Some(status.to_string()),
-                method,
-                date: SystemTime::now()
-                    .duration_since(UNIX_EPOCH).unwrap_or_default()
-                    .as_millis().to_string(),
-                hidden: false
-            }),
-        ).await;
-
-        let exit_code = output.status.code().unwrap_or(0);
-        if exit_code == 1 && error.contains("connection refused") {
-            return Ok((StatusCode::OK, Html(format!(
-                "<p>$  error: Connection refused, probably can't connect to gRPC server. Check if the server is running and the address is correct.</p>"
-            ))).into_response());
-        } else if exit_code != 0 && !output.status.success() {
-            if error.contains("Unimplemented") {
+            if error.to_lowercase().contains("connection refused") {
+                return Ok((StatusCode::OK, Html(
+                    "<p>$  error: Connection refused, probably can't connect to gRPC server. Check if the server is running and the address is correct.</p>"
+                )).into_response());
+            } else if cfg!(feature = "docker") && error.contains("Failed to dial target host") {
                 return Ok((
                     StatusCode::OK,
                     Html(format!(
-                        "<p>$  error: gRPC method not implemented on the server</p>"
+                        "$  error: may not be using host.docker.internal. {error}"
                     )),
                 )
                     .into_response());
-            } else if error.contains("InvalidArgument") {
-                return Ok((
-                    StatusCode::OK,
-                    Html(format!(
-                        "<p>$  error: Invalid arguments provided to gRPC call</p>"
-                    )),
-                )
-                    .into_response());
-            } else {
-                return Ok((
-                    StatusCode::OK,
-                    Html(format!("<p>$  error: gRPC call failed - {}</p>", error)),
-                )
-                    .into_response());
             }
+
+            return Ok((StatusCode::OK, Html(format!("$  error: {error}"))).into_response());
+        }
+    };
+
+    let record = history_request(email.clone(), commands, &command, &response);
+    // A history row that can't be written doesn't hide the response
+    state
+        .create_request(email, record.clone(), response_max_size())
+        .await
+        .ok();
+    let metadata = render_response_metadata(&record);
+
+    Ok((
+        StatusCode::OK,
+        Html(render_call_result(&response, &metadata)),
+    )
+        .into_response())
+}
+
+// Everything the server sent back is its own text, grpc-message included, never markup
+pub fn render_call_result(response: &GrpcResponse, metadata: &str) -> String {
+    let status = escape_html(&response.status);
+    let message = escape_html(&response.message.clone().unwrap_or_default());
+
+    if !response.is_ok() {
+        let error = match status.as_str() {
+            "UNIMPLEMENTED" => "gRPC method not implemented on the server".to_string(),
+            "DEADLINE_EXCEEDED" => format!("gRPC call timed out - {message}"),
+            "CANCELLED" => format!("gRPC call cancelled - {message}"),
+            "INVALID_ARGUMENT" => {
+                format!("Invalid arguments provided to gRPC call - {message}")
+            }
+            _ => format!("gRPC call failed - {message}"),
+        };
+
+        return format!("$  error: {error}<br /><br />status: {status}<br /><br />{metadata}");
+    }
+
+    format!(
+        r#"
+        $  status: {status}
+        <br /><br />
+        {metadata}
+        <textarea id="response-textarea" readonly>{}&#013;</textarea>
+    "#,
+        escape_html(&response.body_text())
+    )
+}
+
+// Fills the command's -d with a placeholder request for its method, resolved the same way
+// the service browser does
+pub async fn generate_request_skeleton(
+    state: ConnectionState,
+    Extension(cache): Extension<DescriptorCache>,
+    auth: AuthUser,
+    Json(request): Json<RequestBody>,
+) -> ApiResult {
+    // Anything that stops the skeleton is something to fix in the command
+    let skeleton = request_skeleton(state, cache, auth.owner(), request)
+        .await
+        .map_err(|e| AppError::Unprocessable(e.to_string()))?;
+
+    Ok(Json(skeleton).into_response())
+}
+
+async fn request_skeleton(
+    state: ConnectionState,
+    cache: DescriptorCache,
+    email: String,
+    request: RequestBody,
+) -> Result<SkeletonResponse> {
+    // The descriptors come from the resolved target, the template keeps its placeholders
+    let mut command = GrpcCommand::parse(&request.command)?;
+    let resolved = GrpcCommand::parse(
+        &resolve_command_variables(State(state.0.clone()), email.clone(), &request.command)
+            .await?
+            .command,
+    )?;
+    let symbol = match (resolved.verb, &resolved.symbol) {
+        (Verb::Invoke, Some(symbol)) => symbol.clone(),
+        _ => {
+            return Err(miette!(
+                "Pick a method to call first, e.g. pkg.Service/Method"
+            ));
+        }
+    };
+
+    let registry = get_command_protoset(state, email, &resolved).await?;
+    let pool = cache
+        .get_or_load(&resolved, registry.as_deref(), false)
+        .await?;
+    let method =
+        find_method(&pool, &symbol).ok_or_else(|| miette!("Symbol not found: {symbol}"))?;
+
+    let skeleton = message_skeleton(&method.input());
+    command.data = Some(
+        serde_json::to_string_pretty(&skeleton.json)
+            .map_err(|e| miette!("Could not format request skeleton: {e}"))?,
+    );
+
+    Ok(SkeletonResponse {
+        command: command.to_command_line(),
+        notes: skeleton.notes,
+    })
+}
+
+// Suggestions for the word under the cursor, anything that can't be looked up (like an
+// unreachable target) just doesn't suggest anything
+pub async fn complete_grpcurl_command(
+    state: ConnectionState,
+    Extension(cache): Extension<DescriptorCache>,
+    auth: AuthUser,
+    Json(request): Json<CompletionBody>,
+) -> Response {
+    let email = auth.owner();
+    let load = async |command: &GrpcCommand| {
+        let command = &resolve_target(State(state.0.clone()), email.clone(), command).await?;
+        let registry = get_command_protoset(State(state.0.clone()), email.clone(), command)
+            .await
+            .ok()?;
+        cache
+            .get_or_load(command, registry.as_deref(), false)
+            .await
+            .ok()
+    };
+
+    let (from, completions) = match completion_context(&request.command, request.cursor) {
+        CompletionContext::Flag { from, prefix } => (from, complete_flags(&prefix)),
+        CompletionContext::Address { from, prefix } => {
+            let known = get_history_addresses_from_db(State(state.0.clone()), email.clone())
+                .await
+                .unwrap_or_default();
+            (from, complete_addresses(&prefix, &known))
+        }
+        CompletionContext::Symbol {
+            from,
+            prefix,
+            target,
+            methods,
+        } => {
+            let mut completions = match methods {
+                true => complete_verbs(&prefix),
+                false => Vec::new(),
+            };
+            if let Some(target) = target
+                && let Some(pool) = load(&target).await
+            {
+                completions.extend(complete_symbols(&pool, &prefix, methods));
+            }
+            (from, completions)
+        }
+        CompletionContext::Field {
+            from,
+            prefix,
+            path,
+            used,
+            quoted,
+            command: Some(command),
+        } => {
+            let input = match (&command.symbol, load(&command).await) {
+                (Some(symbol), Some(pool)) => {
+                    find_method(&pool, symbol).map(|method| method.input())
+                }
+                _ => None,
+            };
+            let completions = input
+                .map(|input| complete_fields(&input, &path, &prefix, &used, quoted))
+                .unwrap_or_default();
+            (from, completions)
         }
+        CompletionContext::Field { from, .. } => (from, Vec::new()),
+        CompletionContext::None => (request.cursor, Vec::new()),
+    };
+
+    Json(CompletionResponse { from, completions }).into_response()
+}
+
+// Placeholders in the address or flags are filled in before looking the target up, anything
+// still unresolved can't be looked up
+async fn resolve_target(
+    state: ConnectionState,
+    email: String,
+    command: &GrpcCommand,
+) -> Option<GrpcCommand> {
+    let target = GrpcCommand {
+        data: None,
+        ..command.clone()
+    };
+    let resolved = resolve_command_variables(state, email, &target.to_command_line())
+        .await
+        .ok()?;
+
+    GrpcCommand::parse(&resolved.command).ok()
+}
+
+pub async fn cancel_grpcurl_request(
+    Extension(calls): Extension<InFlightCalls>,
+    auth: AuthUser,
+    Path(id): Path<String>,
+) -> HtmlResult {
+    calls
+        .cancel(&auth.owner(), &id)
+        .map_err(|e| AppError::not_found(e.to_string()))?;
+
+    Ok(Html("$  cancelling call").into_response())
+}
+
+pub async fn stream_grpcurl_events(
+    Extension(streams): Extension<StreamSessions>,
+    auth: AuthUser,
+    Path(id): Path<String>,
+) -> HtmlResult {
+    let events = streams
+        .take_events(&auth.owner(), &id)
+        .map_err(|e| AppError::not_found(e.to_string()))?;
+
+    Ok(Sse::new(
+        UnboundedReceiverStream::new(events).map(|event| Event::default().json_data(event)),
+    )
+    .keep_alive(KeepAlive::default())
+    .into_response())
+}
 
-        let error_response_regex = Regex::new(r"(?i)error|failed|exception").unwrap();
-        if error_response_regex.is_match(&response) || error_response_regex.is_match(&error) {
-            return Ok((
-                StatusCode::OK,
-                Html(format!("$  error: {}<br /><br />status: {}", error, status)),
-            )
-                .into_response());
+pub async fn send_grpcurl_stream_message(
+    Extension(streams): Extension<StreamSessions>,
+    auth: AuthUser,
+    Path(id): Path<String>,
+    Json(message): Json<StreamMessageBody>,
+) -> HtmlResult {
+    let count = streams
+        .send(&auth.owner(), &id, &message.data)
+        .map_err(|e| AppError::Unprocessable(e.to_string()))?;
+
+    Ok(Html(format!("$  sent {count} message(s)")).into_response())
+}
+
+pub async fn close_grpcurl_stream(
+    Extension(streams): Extension<StreamSessions>,
+    auth: AuthUser,
+    Path(id): Path<String>,
+) -> HtmlResult {
+    streams
+        .close_send(&auth.owner(), &id)
+        .map_err(|e| AppError::not_found(e.to_string()))?;
+
+    Ok(Html("$  request stream closed, waiting for the server").into_response())
+}
+
+// Registers the call so the browser can follow it, the history row is written when the server
+// is done so it carries the final status
+fn watch_stream(
+    state: Repository,
+    streams: StreamSessions,
+    (calls, call_id, mut cancelled): (InFlightCalls, String, Receiver<()>),
+    email: String,
+    commands: (String, String),
+    command: &GrpcCommand,
+    call: StreamCall,
+) -> Result<String> {
+    let StreamCall {
+        method,
+        mut events,
+        sender,
+        started,
+        timings,
+    } = call;
+    let client_streaming = sender.is_some();
+    let (forward, watched) = unbounded_channel();
+
+    let id = streams.insert(StreamSession {
+        owner: email.clone(),
+        input: method.input(),
+        allow_unknown_fields: NativeOptions::from_command(command)?.allow_unknown_fields,
+        events: Some(watched),
+        sender,
+        finished: false,
+    })?;
+
+    let stream_id = id.clone();
+    let command = command.clone();
+    tokio::spawn(async move {
+        let mut response = GrpcResponse {
+            backend: Backend::Native,
+            method: Some(method.name().to_string()),
+            timings,
+            ..GrpcResponse::default()
+        };
+
+        loop {
+            let event = tokio::select! {
+                event = events.recv() => event,
+                Ok(()) = &mut cancelled => {
+                    Some(status_event(&Status::cancelled("Call cancelled from gURL")))
+                }
+            };
+            let Some(event) = event else {
+                break;
+            };
+            let done = matches!(event, StreamEvent::Status { .. });
+
+            response.apply(event.clone());
+            forward.send(event).ok();
+
+            if done {
+                break;
+            }
         }
 
-        let html_response = format!(
+        response.timings.total_ms = Some(millis(started.elapsed()));
+
+        // Dropping the events stops the call if it was cancelled
+        drop(events);
+        calls.remove(&email, &call_id).ok();
+        streams.finish(&stream_id).ok();
+
+        let record = history_request(email.clone(), commands, &command, &response);
+        state
+            .create_request(email, record, response_max_size())
+            .await
+            .ok();
+    });
+
+    let message_form = match client_streaming {
+        true => {
             r#"
-            $  status: {status}
-            <br /><br />
-            <textarea id="response-textarea" readonly>{response}&#013;</textarea>
+            <form id="stream-message" onsubmit="sendStreamMessage(event)">
+                $  message <textarea name="data" type="text" placeholder="request message json"></textarea>
+                <input type="submit" value="send" />
+                <input type="button" value="end stream" onclick="closeStream()" />
+            </form>
+            <div id="stream-notice"></div>
         "#
-        );
+        }
+        false => "",
+    };
 
-        Ok((StatusCode::OK, Html(html_response)).into_response())
-    })().await;
+    Ok(format!(
+        r#"
+            $  status: <span id="stream-status">STREAMING</span>
+            <br /><br />
+            <details id="stream-metadata" class="response-metadata"><summary>$  metadata</summary></details>
+            <textarea id="response-textarea" readonly></textarea>
+            <div id="stream" data-stream-id="{id}"></div>
+            {message_form}
+        "#
+    ))
+}
 
-    match res {
-        Ok(res) => res,
-        Err(e) => panic!("{e}"),
+// Both commands come in masked
+fn history_request(
+    email: String,
+    (template, resolved): (String, String),
+    command: &GrpcCommand,
+    response: &GrpcResponse,
+) -> Request {
+    Request {
+        id: None,
+        user_email: Some(email),
+        resolved_command: (resolved != template).then_some(resolved),
+        command: template,
+        status: Some(response.status.clone()),
+        method: response.method.clone().or(Some(command.method())),
+        date: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis()
+            .to_string(),
+        hidden: false,
+        status_message: response
+            .message
+            .clone()
+            .filter(|message| !message.is_empty()),
+        status_details: response.details.clone(),
+        headers: response.headers.clone(),
+        trailers: response.trailers.clone(),
+        total_ms: response.timings.total_ms,
+        connect_ms: response.timings.connect_ms,
+        first_byte_ms: response.timings.first_byte_ms,
+        response: (!response.body.is_empty()).then(|| response.body_text()),
+        response_size: None,
+        response_truncated: false,
     }
 }
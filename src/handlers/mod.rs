@@ -1,30 +1,55 @@
 use std::num::NonZeroI32;
-use std::sync::{Arc, Mutex};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use axum::extract::{Path, State};
+use axum::extract::State;
 use humantime::format_duration;
 use miette::{Result, miette};
-use rusqlite::{Connection, Statement, params_from_iter};
 use serde::{Deserialize, Serialize};
 use serde_with::{serde_as, skip_serializing_none};
 use validator::Validate;
 
+pub mod asset;
+pub mod collection;
+pub mod environment;
+pub mod error;
+pub mod favorite;
 pub mod grpcurl;
+pub mod proto;
 pub mod request;
+pub mod session;
 pub mod template;
+pub mod token;
 pub mod user;
+pub mod workspace;
 
+pub use asset::*;
+pub use collection::*;
+pub use environment::*;
+pub use error::*;
+pub use favorite::*;
 pub use grpcurl::*;
+pub use proto::*;
 pub use request::*;
+pub use session::*;
 pub use template::*;
+pub use token::*;
 pub use user::*;
+pub use workspace::*;
 
-use crate::models::{
-    deserialize_bool_from_db, deserialize_favorites_from_db, request::Request, user::User,
+use crate::{
+    env::env,
+    grpc::{Completion, GrpcCommand, uses_registry},
+    models::{
+        collection::{Collection, OrderKind},
+        environment::{Environment, Variable},
+        proto::ProtoFile,
+        request::Request,
+    },
+    repository::Repository,
+    utils::{SECRET_MASK, decrypt_secret, has_variables, interpolate_command, secret_key},
 };
 
-pub type ConnectionState = State<Arc<Mutex<Connection>>>;
+pub type ConnectionState = State<Repository>;
 
 #[serde_as]
 #[skip_serializing_none]
@@ -32,133 +57,309 @@ pub type ConnectionState = State<Arc<Mutex<Connection>>>;
 pub struct RequestBody {
     #[validate(length(min = 1))]
     command: String,
+    call: Option<String>,
 }
 
 #[serde_as]
 #[skip_serializing_none]
 #[derive(Serialize, Deserialize, Debug, Clone, Validate)]
-pub struct PathParams {
-    id: Option<NonZeroI32>,
+pub struct CompletionBody {
+    command: String,
+    // Char offset of the cursor in the command
+    cursor: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CompletionResponse {
+    from: usize,
+    completions: Vec<Completion>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SkeletonResponse {
+    command: String,
+    notes: Vec<String>,
+}
+
+#[serde_as]
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct StreamMessageBody {
+    #[validate(length(min = 1))]
+    data: String,
+}
+
+#[serde_as]
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct BrowseBody {
+    #[validate(length(min = 1))]
+    target: String,
+    // Unchecked boxes aren't sent at all
+    refresh: Option<String>,
+}
+
+#[serde_as]
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct ProtoBody {
     #[validate(length(min = 1))]
-    email: Option<String>,
+    target: String,
+    // The path other files import it with, e.g. acme/v1/service.proto
     #[validate(length(min = 1))]
-    password: Option<String>,
+    name: String,
+    // Source text for .proto files, base64 for protosets
+    content: String,
+}
+
+#[serde_as]
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct EnvironmentBody {
+    // Unset deactivates every environment
     #[validate(length(min = 1))]
-    token: Option<String>,
+    name: Option<String>,
+}
+
+#[serde_as]
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct VariableBody {
+    // Environment name, created when it doesn't exist yet
+    #[validate(length(min = 1))]
+    environment: String,
+    #[validate(length(min = 1))]
+    key: String,
+    value: String,
+    // Stored encrypted and masked anywhere it's shown
+    #[serde(default)]
+    secret: bool,
+}
+
+#[serde_as]
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct CollectionBody {
+    #[validate(length(min = 1))]
+    name: String,
+    // Unset for a top level collection, a collection id for a folder
+    parent_id: Option<i32>,
+}
+
+#[serde_as]
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct SavedRequestBody {
+    collection_id: i32,
+    #[validate(length(min = 1))]
+    title: String,
+    description: Option<String>,
+    #[validate(length(min = 1))]
+    command: String,
+}
+
+#[serde_as]
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct OrderBody {
+    kind: OrderKind,
+    // Where the items end up, unset only for top level collections
+    parent_id: Option<i32>,
+    // Every item of the parent in their new order
+    ids: Vec<i32>,
+}
+
+#[serde_as]
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct PathParams {
+    id: Option<NonZeroI32>,
     #[validate(length(min = 1))]
     page: Option<String>,
     deleted: Option<bool>,
 }
 
 // Request/User utils
+// The registry protoset for the command's target, unless it already names its own descriptors
+pub async fn get_command_protoset(
+    state: ConnectionState,
+    email: String,
+    command: &GrpcCommand,
+) -> Result<Option<Vec<u8>>> {
+    match (&command.address, uses_registry(command)) {
+        (Some(target), true) => get_registry_protoset_from_db(state, email, target).await,
+        _ => Ok(None),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedCommand {
+    // What gets sent
+    pub command: String,
+    // What gets stored and shown, with secret values as ****
+    pub masked: String,
+}
+
+// The command with the active environment's variables filled in, commands without any
+// placeholders don't touch the db
+pub async fn resolve_command_variables(
+    state: ConnectionState,
+    email: String,
+    template: &str,
+) -> Result<ResolvedCommand> {
+    if !has_variables(template) {
+        return Ok(ResolvedCommand {
+            command: template.to_string(),
+            masked: template.to_string(),
+        });
+    }
+
+    let variables = get_active_variables_from_db(state, email).await?;
+
+    resolve_command(template, &variables)
+}
+
+// Secrets are masked where they're filled in, the same text typed anywhere else stays
+pub fn resolve_command(template: &str, variables: &[Variable]) -> Result<ResolvedCommand> {
+    let values = |mask: bool| {
+        variables
+            .iter()
+            .map(|variable| match mask && variable.secret {
+                true => (variable.key.clone(), SECRET_MASK.to_string()),
+                false => (variable.key.clone(), variable.value.clone()),
+            })
+            .collect::<Vec<_>>()
+    };
+
+    Ok(ResolvedCommand {
+        command: interpolate_command(template, &values(false))?,
+        masked: interpolate_command(template, &values(true))?,
+    })
+}
+
+pub fn response_max_size() -> usize {
+    env()
+        .ok()
+        .and_then(|env| env.response_max_size.parse().ok())
+        .unwrap_or(1_048_576)
+}
+
+// A single history row with its stored response body
+pub async fn get_request_from_db(
+    state: ConnectionState,
+    email: String,
+    id: NonZeroI32,
+) -> Result<Option<Request>> {
+    state.request(email, id).await
+}
+
 pub async fn get_all_requests_from_db(
     state: ConnectionState,
-    Path(path): Path<PathParams>,
+    email: String,
 ) -> Result<Vec<Request>> {
-    let email = path.email.unwrap_or("anon".to_string());
-    let db = state
-        .lock()
-        .map_err(|e| miette!("Global db can't block current thread {e}"))?;
-
-    Ok(map_requests(
-        db.prepare(
-            "SELECT * FROM request WHERE user_email = ?1 AND hidden = false ORDER BY id DESC",
-        )
-        .map_err(|e| miette!("Invalid statement: {e}"))?,
-        &[email],
-    )?)
+    state.requests(email).await
 }
 
 pub async fn get_all_favorites_from_db(
     state: ConnectionState,
-    Path(path): Path<PathParams>,
+    email: String,
 ) -> Result<Vec<Request>> {
-    let mut favorite_requests = Vec::new();
-    let email = path.email.unwrap_or("anon".to_string());
-    let db = state
-        .lock()
-        .map_err(|e| miette!("Global db can't block current thread {e}"))?;
-
-    let favorite_ids = map_favorites(
-        db.prepare(r#"SELECT favorites FROM "user" WHERE email = ?1"#)
-            .map_err(|e| miette!("Invalid statement: {e}"))?,
-        &[email.clone()],
-    )?
-    .unwrap_or(Vec::new());
-
-    for favorite in favorite_ids {
-        let res = map_requests(
-            db.prepare(
-                r#"
-                SELECT * FROM request WHERE user_email = ?1 AND id = ?2 AND hidden = false 
-                ORDER BY id DESC
-            "#,
-            )
-            .map_err(|e| miette!("Invalid statement: {e}"))?,
-            &[email.clone(), favorite.to_string()],
-        )?;
-
-        if let Some(favorite_request) = res.first() {
-            favorite_requests.push(favorite_request.clone());
+    state.favorite_requests(email).await
+}
+
+// Ids of the requests the user marked as favorite, to flag them in the history list
+pub async fn get_favorite_ids_from_db(state: ConnectionState, email: String) -> Result<Vec<i32>> {
+    state.favorite_ids(email).await
+}
+
+// Addresses the user called before, most recent first
+pub async fn get_history_addresses_from_db(
+    state: ConnectionState,
+    email: String,
+) -> Result<Vec<String>> {
+    let commands = state.recent_commands(email, 500).await?;
+
+    let mut addresses = Vec::new();
+    for address in commands
+        .iter()
+        .filter_map(|command| GrpcCommand::parse(command).ok()?.address)
+    {
+        if !addresses.contains(&address) {
+            addresses.push(address);
         }
     }
 
-    Ok(favorite_requests)
+    Ok(addresses)
 }
 
-pub fn map_requests(mut statement: Statement<'_>, args: &[String]) -> Result<Vec<Request>> {
-    let parsed_rows = statement
-        .query_map(params_from_iter(args), |row| {
-            Ok(Request {
-                id: row.get(0)?,
-                user_email: row.get::<_, Option<String>>(1)?,
-                command: row.get(2)?,
-                status: row.get(3)?,
-                method: row.get(4)?,
-                date: row.get(5)?,
-                hidden: deserialize_bool_from_db(row.get(6)?),
-            })
-        })
-        .map_err(|e| miette!("Error mapping rows to Request: {e}"))?
-        .map(|item| item.expect("Cannot unwrap Request row item"))
-        .collect::<Vec<_>>();
+pub async fn get_all_protos_from_db(
+    state: ConnectionState,
+    email: String,
+) -> Result<Vec<ProtoFile>> {
+    state.protos(email).await
+}
 
-    Ok(parsed_rows)
+// Everything a user uploaded for a target compiled into one protoset, None if nothing was. It's
+// compiled when the files change, not on every call
+pub async fn get_registry_protoset_from_db(
+    state: ConnectionState,
+    email: String,
+    target: &str,
+) -> Result<Option<Vec<u8>>> {
+    state.proto_build(email, target.to_string()).await
 }
 
-pub fn map_favorites(mut statement: Statement<'_>, args: &[String]) -> Result<Option<Vec<i32>>> {
-    let parsed_rows = statement
-        .query_map(params_from_iter(args), |row| {
-            Ok(deserialize_favorites_from_db(row.get(0)?))
-        })
-        .map_err(|e| miette!("Error mapping favorite: {e}"))?
-        .map(|item| item.expect("Cannot unwrap favorite row item"))
-        .collect::<Vec<_>>();
+pub async fn get_all_environments_from_db(
+    state: ConnectionState,
+    email: String,
+) -> Result<Vec<Environment>> {
+    let environments = state.environments(email).await?;
 
-    if let Some(favorites) = parsed_rows.first() {
-        Ok(favorites.clone())
-    } else {
-        Ok(None)
-    }
+    Ok(environments
+        .into_iter()
+        .map(|environment| Environment {
+            variables: environment
+                .variables
+                .into_iter()
+                .map(mask_variable)
+                .collect(),
+            ..environment
+        })
+        .collect())
 }
 
-pub fn map_user(mut statement: Statement<'_>, args: &[&String]) -> Result<Vec<User>> {
-    let parsed_rows = statement
-        .query_map(params_from_iter(args), |row| {
-            Ok(User {
-                username: row.get(0)?,
-                email: row.get(1)?,
-                password: row.get(2)?,
-                favorites: deserialize_favorites_from_db(row.get(3)?),
-                date: row.get::<_, Option<String>>(4)?,
-                deleted: deserialize_bool_from_db(row.get(5)?),
-            })
+// Variables of the active environment with secrets decrypted, empty when none is active
+pub async fn get_active_variables_from_db(
+    state: ConnectionState,
+    email: String,
+) -> Result<Vec<Variable>> {
+    let variables = state.active_variables(email).await?;
+
+    variables
+        .into_iter()
+        .map(|mut variable| {
+            if variable.secret {
+                variable.value = decrypt_secret(&secret_key()?, &variable.value)?;
+            }
+            Ok(variable)
         })
-        .map_err(|e| miette!("Error mapping rows to User: {e}"))?
-        .map(|item| item.expect("Cannot unwrap User row item"))
-        .collect::<Vec<_>>();
+        .collect()
+}
+
+// Every collection of the user nested by parent, with its saved requests, in their set order
+pub async fn get_all_collections_from_db(
+    state: ConnectionState,
+    email: String,
+) -> Result<Vec<Collection>> {
+    state.collections(email).await
+}
+
+pub fn mask_variable(mut variable: Variable) -> Variable {
+    if variable.secret {
+        variable.value = SECRET_MASK.to_string();
+    }
 
-    Ok(parsed_rows)
+    variable
 }
 
 // Template utils
@@ -185,6 +386,56 @@ pub fn humanize_date(date: Option<String>) -> Result<String> {
     Ok(human_date)
 }
 
+// Collapsible timings, headers, trailers and status details under a response
+pub fn render_response_metadata(request: &Request) -> String {
+    let timings = [
+        ("took", request.total_ms),
+        ("connect", request.connect_ms),
+        ("first byte", request.first_byte_ms),
+    ]
+    .into_iter()
+    .filter_map(|(name, millis)| millis.map(|millis| format!("{name} {millis:.2} ms")))
+    .collect::<Vec<_>>();
+
+    let mut sections = Vec::new();
+    if let Some(message) = &request.status_message {
+        sections.push(format!(
+            "<p>$  status message: {}</p>",
+            escape_html(message)
+        ));
+    }
+    for (title, pairs) in [
+        ("status details", &request.status_details),
+        ("headers", &request.headers),
+        ("trailers", &request.trailers),
+    ] {
+        if pairs.is_empty() {
+            continue;
+        }
+        let pairs = pairs
+            .iter()
+            .map(|(name, value)| {
+                format!(
+                    "<p>&nbsp;&nbsp;{}: {}</p>",
+                    escape_html(name),
+                    escape_html(value)
+                )
+            })
+            .collect::<String>();
+        sections.push(format!("<p>$  {title}:</p>{pairs}"));
+    }
+
+    let summary = match timings.is_empty() {
+        true => "metadata".to_string(),
+        false => timings.join(", "),
+    };
+
+    format!(
+        r#"<details class="response-metadata"><summary>$  {summary}</summary>{}</details>"#,
+        sections.concat()
+    )
+}
+
 pub fn get_status_color(status: &Option<String>) -> String {
     let status_colors = std::collections::HashMap::from([
         // Success
@@ -234,10 +485,21 @@ pub fn get_status_color(status: &Option<String>) -> String {
         .to_string()
 }
 
-pub fn get_service_name(command: &String) -> String {
-    command
-        .split_whitespace()
-        .filter(|word| !word.starts_with('-'))
-        .collect::<Vec<_>>()
-        .join(" ")
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+pub fn get_service_name(command: &str) -> String {
+    match GrpcCommand::parse(command) {
+        Ok(command) => command.summary(),
+        Err(_) => command
+            .split_whitespace()
+            .filter(|word| !word.starts_with('-'))
+            .collect::<Vec<_>>()
+            .join(" "),
+    }
 }
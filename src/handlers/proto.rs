@@ -0,0 +1,82 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use axum::{
+    extract::{Json, Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use prost::Message;
+use prost_types::FileDescriptorSet;
+
+use crate::handlers::{
+    ApiResult, AppError, AuthUser, ConnectionState, PathParams, ProtoBody, get_all_protos_from_db,
+};
+
+pub async fn get_all_protos(state: ConnectionState, auth: AuthUser) -> ApiResult {
+    let protos = get_all_protos_from_db(state, auth.owner()).await?;
+
+    Ok((StatusCode::OK, Json(protos)).into_response())
+}
+
+// Uploading a file with the same name for the same target replaces it
+pub async fn create_proto(
+    State(state): ConnectionState,
+    auth: AuthUser,
+    Json(proto): Json<ProtoBody>,
+) -> ApiResult {
+    let email = auth.owner();
+    let target = proto.target.trim().to_string();
+    let name = proto.name.trim().trim_start_matches('/').to_string();
+
+    if target.is_empty() || name.is_empty() {
+        return Err(AppError::bad_request("Target and name are required"));
+    }
+
+    let (kind, content) = if name.ends_with(".proto") {
+        ("proto", proto.content.into_bytes())
+    } else {
+        let Ok(bytes) = STANDARD.decode(proto.content.trim()) else {
+            return Err(AppError::bad_request("Protoset content must be base64"));
+        };
+        if let Err(e) = FileDescriptorSet::decode(bytes.as_slice()) {
+            return Err(AppError::bad_request(format!(
+                "Invalid protoset {name}: {e}"
+            )));
+        }
+        ("protoset", bytes)
+    };
+
+    let date = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis()
+        .to_string();
+
+    // Files that don't compile with the rest of the target's are turned away
+    match state
+        .create_proto(email, target, name, kind.to_string(), content, date)
+        .await?
+    {
+        Ok(Some(proto)) => Ok((StatusCode::OK, Json(proto)).into_response()),
+        Ok(None) => Err(AppError::not_found("Proto file not found")),
+        Err(e) => Err(AppError::bad_request(e)),
+    }
+}
+
+pub async fn delete_proto(
+    State(state): ConnectionState,
+    auth: AuthUser,
+    Path(path): Path<PathParams>,
+) -> ApiResult {
+    let email = auth.owner();
+    let proto_id = path
+        .id
+        .ok_or_else(|| AppError::bad_request("Missing proto id"))?;
+
+    match state.delete_proto(email, proto_id.get()).await? {
+        Ok(true) => Ok((StatusCode::OK).into_response()),
+        Ok(false) => Err(AppError::not_found("Proto file not found")),
+        Err(e) => Err(AppError::bad_request(e)),
+    }
+}
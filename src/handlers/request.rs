@@ -1,167 +1,109 @@
 use axum::{
     extract::{Json, Path, State},
     http::StatusCode,
-    response::{IntoResponse, Response},
+    response::IntoResponse,
 };
-use miette::{Result, miette};
 
 use crate::{
+    grpc::{GrpcCommand, is_status_name},
     handlers::{
-        ConnectionState, PathParams, get_all_favorites_from_db, get_all_requests_from_db,
-        map_requests,
+        ApiResult, AppError, AuthUser, ConnectionState, PathParams, get_all_favorites_from_db,
+        get_all_requests_from_db, get_request_from_db, response_max_size,
     },
-    models::{request::Request, serialize_bool_for_db},
+    models::request::Request,
+    utils::mask_sensitive_headers,
 };
 
-pub async fn get_all_requests(state: ConnectionState, Path(path): Path<PathParams>) -> Response {
-    let res: Result<Response> = (async || {
-        let requests = get_all_requests_from_db(state, Path(path)).await;
+pub async fn get_all_requests(state: ConnectionState, auth: AuthUser) -> ApiResult {
+    let requests = get_all_requests_from_db(state, auth.owner()).await?;
 
-        match requests {
-            Ok(requests) => {
-                if requests.is_empty() {
-                    Ok((
-                        StatusCode::NOT_FOUND,
-                        "No requests found from this user email",
-                    )
-                        .into_response())
-                } else {
-                    Ok((StatusCode::OK, Json(requests)).into_response())
-                }
-            }
-            Err(e) => Ok((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Server error: {e}"),
-            )
-                .into_response()),
-        }
-    })()
-    .await;
+    if requests.is_empty() {
+        return Err(AppError::not_found(
+            "No requests found from this user email",
+        ));
+    }
+
+    Ok((StatusCode::OK, Json(requests)).into_response())
+}
 
-    match res {
-        Ok(res) => res,
-        Err(e) => panic!("{e}"),
+pub async fn get_all_favorite_requests(state: ConnectionState, auth: AuthUser) -> ApiResult {
+    let favorites = get_all_favorites_from_db(state, auth.owner()).await?;
+
+    if favorites.is_empty() {
+        return Err(AppError::not_found(
+            "No favorite requests found from this user email",
+        ));
     }
+
+    Ok((StatusCode::OK, Json(favorites)).into_response())
 }
 
-pub async fn get_all_favorite_requests(
+pub async fn get_request(
     state: ConnectionState,
+    auth: AuthUser,
     Path(path): Path<PathParams>,
-) -> Response {
-    let res: Result<Response> = (async || {
-        let favorites = get_all_favorites_from_db(state, Path(path)).await;
-
-        match favorites {
-            Ok(favorites) => {
-                if favorites.is_empty() {
-                    Ok((
-                        StatusCode::NOT_FOUND,
-                        "No favorite requests found from this user email",
-                    )
-                        .into_response())
-                } else {
-                    Ok((StatusCode::OK, Json(favorites)).into_response())
-                }
-            }
-            Err(e) => Ok((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Server error: {e}"),
-            )
-                .into_response()),
-        }
-    })()
-    .await;
+) -> ApiResult {
+    let request_id = path
+        .id
+        .ok_or_else(|| AppError::bad_request("Missing request id"))?;
 
-    match res {
-        Ok(res) => res,
-        Err(e) => panic!("{e}"),
+    match get_request_from_db(state, auth.owner(), request_id).await? {
+        Some(request) => Ok((StatusCode::OK, Json(request)).into_response()),
+        None => Err(AppError::not_found("Request not found")),
     }
 }
 
 pub async fn create_request(
     State(state): ConnectionState,
-    Path(path): Path<PathParams>,
+    auth: AuthUser,
     Json(request): Json<Request>,
-) -> Response {
-    let res: Result<Response> = (|| {
-        let email = path.email.unwrap_or("anon".to_string());
-        let db = state
-            .lock()
-            .map_err(|e| miette!("Global db can't block current thread {e}"))?;
+) -> ApiResult {
+    if let Some(status) = &request.status
+        && !is_status_name(status)
+    {
+        return Err(AppError::Unprocessable(format!(
+            "Unknown gRPC status \"{status}\""
+        )));
+    }
+    // The lists show the method next to the command, so it comes from the command itself and
+    // commands gURL can't parse are kept without one
+    let method = GrpcCommand::parse(&request.command)
+        .ok()
+        .map(|command| command.method());
 
-        match map_requests(
-            db.prepare(
-                r#"
-            INSERT INTO request (user_email, command, status, method, date, hidden) 
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6) RETURNING *
-        "#,
-            )
-            .map_err(|e| miette!("Invalid statement: {e}"))?,
-            &[
-                email,
-                request.command,
-                request.status.unwrap_or_default(),
-                request.method.unwrap_or_default(),
-                request.date,
-                serialize_bool_for_db(request.hidden).to_string(),
-            ],
-        ) {
-            Ok(mapped_request) => {
-                if let Some(parsed_request) = mapped_request.first()
-                    && parsed_request.id != Some(0)
-                {
-                    Ok((StatusCode::OK, Json(parsed_request)).into_response())
-                } else {
-                    Ok((StatusCode::NOT_FOUND).into_response())
-                }
-            }
-            Err(e) => Ok((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Server Error: {e}"),
-            )
-                .into_response()),
-        }
-    })();
+    // Same as requests sent through gURL, credentials typed into headers aren't kept
+    let request = Request {
+        method,
+        command: mask_sensitive_headers(&request.command)?,
+        resolved_command: request
+            .resolved_command
+            .as_deref()
+            .map(mask_sensitive_headers)
+            .transpose()?,
+        ..request
+    };
 
-    match res {
-        Ok(res) => res,
-        Err(e) => panic!("{e}"),
+    match state
+        .create_request(auth.owner(), request, response_max_size())
+        .await?
+    {
+        Some(created) => Ok((StatusCode::OK, Json(created)).into_response()),
+        None => Err(AppError::not_found("Request was not stored")),
     }
 }
 
-pub async fn hide_request(State(state): ConnectionState, Path(path): Path<PathParams>) -> Response {
-    let res: Result<Response> =
-        (|| {
-            let email = path.email.unwrap_or("anon".to_string());
-            let request_id = path.id.expect("Missing request id");
-            let db = state
-                .lock()
-                .map_err(|e| miette!("Global db can't block current thread {e}"))?;
-
-            match map_requests(db.prepare(
-            "UPDATE request SET hidden = true WHERE user_email = ?1 AND id = ?2 RETURNING *",
-        ).map_err(|e| miette!("Invalid statement: {e}"))?, &[email, request_id.to_string()]) {
-            Ok(mapped_request) => {
-                if let Some(parsed_request) = mapped_request.first() {
-                    if parsed_request.hidden {
-                        Ok((StatusCode::OK).into_response())
-                    } else {
-                        Ok((StatusCode::NOT_FOUND).into_response())
-                    }
-                } else {
-                        Ok((StatusCode::NOT_FOUND).into_response())
-                }
-            }
-            Err(e) => Ok((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Server Error: {e}"),
-            )
-                .into_response()),
-        }
-        })();
+pub async fn hide_request(
+    State(state): ConnectionState,
+    auth: AuthUser,
+    Path(path): Path<PathParams>,
+) -> ApiResult {
+    let email = auth.owner();
+    let request_id = path
+        .id
+        .ok_or_else(|| AppError::bad_request("Missing request id"))?;
 
-    match res {
-        Ok(res) => res,
-        Err(e) => panic!("{e}"),
+    match state.hide_request(email, request_id).await? {
+        true => Ok((StatusCode::OK).into_response()),
+        false => Err(AppError::not_found("Request not found")),
     }
 }
@@ -0,0 +1,217 @@
+use axum::{
+    extract::FromRequestParts,
+    http::{Method, header::AUTHORIZATION, request::Parts},
+    response::{IntoResponse, Response},
+};
+
+use crate::{
+    handlers::{AppError, HtmlError},
+    models::{token::Scope, user::User, workspace::Workspace},
+    repository::{Repository, workspace_owner},
+    utils::{
+        API_TOKEN_PREFIX, CSRF_HEADER, JwtSignature, SESSION_COOKIE, hash_token, now_secs,
+        parse_jwt, read_cookie, verify_csrf_token,
+    },
+};
+
+// Who's asking: the user of a verified bearer token or session cookie, or anon when there's
+// neither, and the workspace they're working in. Routes never take the user from the path or the
+// body
+#[derive(Debug, Clone)]
+pub struct AuthUser(pub Option<User>, pub Option<Workspace>);
+
+impl AuthUser {
+    // Whose history, collections, environments and protos a route works on
+    pub fn owner(&self) -> String {
+        match (&self.0, &self.1) {
+            (_, Some(workspace)) => workspace_owner(workspace.id),
+            (Some(user), None) => user.email.clone(),
+            (None, None) => "anon".to_string(),
+        }
+    }
+
+    // For what anon can't do, like changing an account
+    pub fn required(self) -> Result<User, AppError> {
+        self.0
+            .ok_or_else(|| AppError::Unauthorized("Login required".to_string()))
+    }
+}
+
+impl FromRequestParts<Repository> for AuthUser {
+    type Rejection = Response;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Repository,
+    ) -> Result<Self, Self::Rejection> {
+        let Some(user) = request_user(parts, state).await? else {
+            return Ok(AuthUser(None, None));
+        };
+
+        let workspace = state
+            .active_workspace(user.email.clone())
+            .await
+            .map_err(|e| rejection(parts, e.into()))?;
+
+        if let Some(workspace) = &workspace
+            && !workspace.role.can_edit()
+            && changes_data(&parts.method, parts.uri.path())
+        {
+            return Err(rejection(
+                parts,
+                AppError::Forbidden(format!("Viewers can't change {}", workspace.name)),
+            ));
+        }
+
+        Ok(AuthUser(Some(user), workspace))
+    }
+}
+
+fn rejection(parts: &Parts, error: AppError) -> Response {
+    match parts.uri.path().starts_with("/api/") {
+        true => error.into_response(),
+        false => HtmlError(error).into_response(),
+    }
+}
+
+// The user behind the bearer token or session cookie, None for anon
+async fn request_user(parts: &Parts, state: &Repository) -> Result<Option<User>, Response> {
+    let reject = |error: AppError| rejection(parts, error);
+    let signature = parts
+        .extensions
+        .get::<JwtSignature>()
+        .cloned()
+        .unwrap_or_default();
+
+    // API clients send a bearer token, the browser sends the session cookie
+    let (token, from_cookie) = match parts.headers.get(AUTHORIZATION) {
+        Some(header) => (
+            header
+                .to_str()
+                .ok()
+                .and_then(|header| header.strip_prefix("Bearer "))
+                .map(str::to_string)
+                .ok_or_else(|| {
+                    reject(AppError::Unauthorized(
+                        "Authorization isn't a bearer token".to_string(),
+                    ))
+                })?,
+            false,
+        ),
+        None => match read_cookie(&parts.headers, SESSION_COOKIE) {
+            Some(token) => (token, true),
+            None => return Ok(None),
+        },
+    };
+
+    // Personal tokens only reach the routes their scopes cover
+    if token.starts_with(API_TOKEN_PREFIX) {
+        let Some(scope) = route_scope(&parts.method, parts.uri.path()) else {
+            return Err(reject(AppError::Forbidden(
+                "Personal tokens can't be used here".to_string(),
+            )));
+        };
+        let (user, scopes) = authenticate_api_token(state, &token)
+            .await
+            .map_err(reject)?;
+
+        if !scopes.contains(&scope) {
+            return Err(reject(AppError::Forbidden(format!(
+                "Token is missing the {} scope",
+                scope.as_str()
+            ))));
+        }
+
+        return Ok(Some(user));
+    }
+
+    // A stale cookie is just a logged out browser, a bad bearer token is an error
+    let (user, session) = match authenticate(state, &signature, &token).await {
+        Ok(authenticated) => authenticated,
+        Err(AppError::Unauthorized(_)) if from_cookie => return Ok(None),
+        Err(error) => return Err(reject(error)),
+    };
+
+    // Cookies go along with requests other sites make, so whatever changes data also needs
+    // the token only the page can read
+    if from_cookie && !parts.method.is_safe() {
+        let key = signature.key().map_err(|e| reject(e.into()))?;
+        let valid = parts
+            .headers
+            .get(CSRF_HEADER)
+            .and_then(|header| header.to_str().ok())
+            .is_some_and(|csrf| verify_csrf_token(key, session, csrf));
+
+        if !valid {
+            return Err(reject(AppError::Forbidden(
+                "Missing or invalid CSRF token".to_string(),
+            )));
+        }
+    }
+
+    Ok(Some(user))
+}
+
+// A valid signature isn't enough, the session behind the token must not have been revoked.
+// Returns the user and their session id
+pub async fn authenticate(
+    state: &Repository,
+    signature: &JwtSignature,
+    token: &str,
+) -> Result<(User, i64), AppError> {
+    let (user, session) = parse_jwt(token, signature.key()?).map_err(AppError::unauthorized)?;
+
+    if !state.session_is_active(session, now_secs()?).await? {
+        return Err(AppError::Unauthorized(
+            "Session has been revoked".to_string(),
+        ));
+    }
+
+    Ok((user, session))
+}
+
+pub async fn authenticate_api_token(
+    state: &Repository,
+    token: &str,
+) -> Result<(User, Vec<Scope>), AppError> {
+    let Some((email, scopes)) = state.use_api_token(hash_token(token), now_secs()?).await? else {
+        return Err(AppError::Unauthorized(
+            "Token is unknown, revoked or expired".to_string(),
+        ));
+    };
+
+    match state.user(email).await? {
+        Some(user) => Ok((user, scopes)),
+        None => Err(AppError::Unauthorized("User not found".to_string())),
+    }
+}
+
+// The scope a personal token needs for a route, None for routes left to logged in browsers
+pub fn route_scope(method: &Method, path: &str) -> Option<Scope> {
+    let reading = *method == Method::GET;
+
+    match path {
+        path if path.starts_with("/api/collection") => Some(Scope::ManageCollections),
+        path if path.starts_with("/grpcurl/") => Some(Scope::Execute),
+        path if reading
+            && (path.starts_with("/api/request") || path.starts_with("/api/favorite")) =>
+        {
+            Some(Scope::ReadHistory)
+        }
+        _ => None,
+    }
+}
+
+// Routes that change a workspace's history, collections, environments or protos
+pub fn changes_data(method: &Method, path: &str) -> bool {
+    let data = [
+        "/api/request",
+        "/api/favorite",
+        "/api/proto",
+        "/api/environment",
+        "/api/collection",
+        "/grpcurl/request",
+    ];
+
+    !method.is_safe() && data.iter().any(|prefix| path.starts_with(prefix))
+}
@@ -1,14 +1,24 @@
 use askama::Template;
 use axum::{
-    extract::{Path, Request},
+    Extension,
+    extract::{Json, Path, Request, State},
     http::StatusCode,
     response::{Html, IntoResponse, Response},
 };
 use miette::{Result, miette};
 
 use crate::{
-    ConnectionState, PathParams, get_all_favorites_from_db, get_all_requests_from_db,
-    get_status_color, humanize_date, parse_jwt,
+    AppError, AuthUser, BrowseBody, ConnectionState, HtmlResult, PathParams, escape_html,
+    get_all_collections_from_db, get_all_environments_from_db, get_all_favorites_from_db,
+    get_all_protos_from_db, get_all_requests_from_db, get_command_protoset,
+    get_favorite_ids_from_db, get_registry_protoset_from_db, get_request_from_db, get_status_color,
+    grpc::{DescriptorCache, GrpcCommand, describe_message, describe_method},
+    humanize_date,
+    models::{collection::Collection, workspace::Role},
+    render_response_metadata,
+    repository::Repository,
+    resolve_command_variables,
+    utils::{VendorScript, now_secs, vendor_scripts},
 };
 
 use super::get_service_name;
@@ -18,49 +28,47 @@ use super::get_service_name;
 pub struct IndexTemplate {
     screen: String,
     index_active: String,
+    scripts: Vec<VendorScript>,
 }
 
-pub async fn render_page(request: Request) -> Response {
-    let res: Result<Response> = (|| {
-        let screen = match request.uri().to_string().as_str() {
-            "/" => "home".to_string(),
-            screen => screen.strip_prefix("/").unwrap_or(screen).to_string(),
-        };
-        let index_active = match screen.as_str() {
-            "home" => "navbar-active".to_string(),
-            _ => "".to_string(),
-        };
-
-        let template = IndexTemplate {
-            screen,
-            index_active,
-        };
-
-        let html = template
-            .render()
-            .map_err(|e| miette!("Server Error: {e}"))?;
-
-        Ok((StatusCode::OK, Html(html)).into_response())
-    })();
-
-    match res {
-        Ok(res) => res,
-        Err(e) => panic!("Server Error: {e}"),
-    }
+pub async fn render_page(request: Request) -> HtmlResult {
+    let screen = match request.uri().to_string().as_str() {
+        "/" => "home".to_string(),
+        screen => screen.strip_prefix("/").unwrap_or(screen).to_string(),
+    };
+    let index_active = match screen.as_str() {
+        "home" => "navbar-active".to_string(),
+        _ => "".to_string(),
+    };
+
+    let template = IndexTemplate {
+        screen,
+        index_active,
+        scripts: vendor_scripts(),
+    };
+
+    let html = template
+        .render()
+        .map_err(|e| miette!("Server Error: {e}"))?;
+
+    Ok((StatusCode::OK, Html(html)).into_response())
 }
 
-pub async fn render_navbar(Path(path): Path<PathParams>) -> Response {
-    let res: Result<Response> = (|| {
-        let token = path.token.unwrap_or("null".to_string());
-        let page = path.page.unwrap_or("home".to_string());
+pub async fn render_navbar(
+    State(state): ConnectionState,
+    auth: AuthUser,
+    Path(path): Path<PathParams>,
+) -> HtmlResult {
+    let page = path.page.unwrap_or("home".to_string());
 
-        let mut pages = std::collections::HashMap::new();
-        pages.insert("login", "");
-        pages.insert("signup", "");
-        pages.insert("profile", "");
-        pages.insert(&page, "navbar-active");
+    let mut pages = std::collections::HashMap::new();
+    pages.insert("login", "");
+    pages.insert("signup", "");
+    pages.insert("profile", "");
+    pages.insert(&page, "navbar-active");
 
-        if token == "null".to_string() {
+    match auth.0 {
+        None => {
             let html = format!(
                 r#"
                     <a id="{}" href="/login">login /</a>
@@ -71,110 +79,105 @@ pub async fn render_navbar(Path(path): Path<PathParams>) -> Response {
             );
 
             Ok((StatusCode::OK, Html(html)).into_response())
-        } else {
-            let user = parse_jwt(&token)?;
+        }
+        Some(user) => {
             let html = format!(
                 r#"
+                    {}
                     <a id="{}" href="/profile">{} /</a>
                     <a onclick="logout();">/ logout</a>
                 "#,
+                render_workspace_switcher(&state, &user.email).await?,
                 pages.get("profile").unwrap_or(&""),
                 user.username
             );
 
             Ok((StatusCode::OK, Html(html)).into_response())
         }
-    })();
-
-    match res {
-        Ok(res) => res,
-        Err(e) => panic!("Server Error: {e}"),
     }
 }
 
-pub async fn render_username(Path(path): Path<PathParams>) -> Response {
-    let res: Result<Response> = (|| {
-        let token = path.token.unwrap_or("null".to_string());
+// Picks what the data routes work on, the user's own data or one of their workspaces
+async fn render_workspace_switcher(state: &Repository, email: &str) -> Result<String> {
+    let workspaces = state.workspaces(email.to_string()).await?;
 
-        if token == "null".to_string() {
+    if workspaces.is_empty() {
+        return Ok(String::new());
+    }
+
+    let personal = match workspaces.iter().any(|workspace| workspace.active) {
+        true => "",
+        false => " selected",
+    };
+    let options = workspaces
+        .iter()
+        .map(|workspace| {
+            format!(
+                r#"<option value="{}"{}>{} ({})</option>"#,
+                workspace.id,
+                if workspace.active { " selected" } else { "" },
+                escape_html(&workspace.name),
+                workspace.role.as_str()
+            )
+        })
+        .collect::<String>();
+
+    Ok(format!(
+        r#"
+        <select id="workspace-switcher" onchange="switchWorkspace(this.value)">
+            <option value=""{personal}>personal</option>{options}
+        </select>
+        "#
+    ))
+}
+
+pub async fn render_username(auth: AuthUser) -> HtmlResult {
+    match auth.0 {
+        None => {
             let html = "<p>$  hello anon! Signup or login to save your favorite requests and organize your request history in your own profiles</p>";
             Ok((StatusCode::OK, Html(html)).into_response())
-        } else {
-            let user = parse_jwt(&token)?;
-            Ok((
-                StatusCode::OK,
-                format!("<p>$  hello {}!</p>", user.username),
-            )
-                .into_response())
         }
-    })();
-
-    match res {
-        Ok(res) => res,
-        Err(e) => panic!("Server Error: {e}"),
+        Some(user) => Ok((
+            StatusCode::OK,
+            format!("<p>$  hello {}!</p>", user.username),
+        )
+            .into_response()),
     }
 }
 
-pub async fn render_login(Path(path): Path<PathParams>) -> Response {
-    let res: Result<Response> = (|| {
-        let token = path.token.unwrap_or("null".to_string());
-
-        if token == "null".to_string() {
-            Ok((
-                StatusCode::OK,
-                Html("<p>$  incorrect credentials or user doesn't exist</p>"),
-            )
-                .into_response())
-        } else {
-            let user = parse_jwt(&token)?;
-            Ok((
-                StatusCode::OK,
-                Html(format!("<p>$  welcome back {}!</p>", user.username)),
-            )
-                .into_response())
-        }
-    })();
-
-    match res {
-        Ok(res) => res,
-        Err(e) => panic!("Server Error: {e}"),
+pub async fn render_login(auth: AuthUser) -> HtmlResult {
+    match auth.0 {
+        None => Ok((
+            StatusCode::OK,
+            Html("<p>$  incorrect credentials or user doesn't exist</p>"),
+        )
+            .into_response()),
+        Some(user) => Ok((
+            StatusCode::OK,
+            Html(format!("<p>$  welcome back {}!</p>", user.username)),
+        )
+            .into_response()),
     }
 }
 
-pub async fn render_signup(Path(path): Path<PathParams>) -> Response {
-    let res: Result<Response> = (|| {
-        let token = path.token.unwrap_or("null".to_string());
-
-        if token == "null".to_string() {
-            Ok((StatusCode::OK, Html("<p>$  invalid input</p>".to_string())).into_response())
-        } else {
-            let user = parse_jwt(&token)?;
-
-            Ok((
-                StatusCode::OK,
-                Html(format!(
-                    "<p>$  account created! username: {}, email: {}</p>",
-                    user.username, user.email
-                )),
-            )
-                .into_response())
-        }
-    })();
-
-    match res {
-        Ok(res) => res,
-        Err(e) => panic!("Server Error: {e}"),
+pub async fn render_signup(auth: AuthUser) -> HtmlResult {
+    match auth.0 {
+        None => Ok((StatusCode::OK, Html("<p>$  invalid input</p>".to_string())).into_response()),
+        Some(user) => Ok((
+            StatusCode::OK,
+            Html(format!(
+                "<p>$  account created! username: {}, email: {}</p>",
+                user.username, user.email
+            )),
+        )
+            .into_response()),
     }
 }
 
-pub async fn render_profile_info(Path(path): Path<PathParams>) -> Response {
-    let res: Result<Response> = (|| {
-        let token = path.token.unwrap_or("null".to_string());
-
-        if token == "null".to_string() {
-            Ok((StatusCode::OK, Html("<p>$  invalid token</p>")).into_response())
-        } else {
-            let user = parse_jwt(&token)?;
+pub async fn render_profile_info(auth: AuthUser) -> HtmlResult {
+    match auth.0 {
+        None => Ok((StatusCode::OK, Html("<p>$  invalid token</p>")).into_response()),
+        Some(user) => {
             let user_since = humanize_date(user.date)?;
 
             Ok((
@@ -186,37 +189,20 @@ pub async fn render_profile_info(Path(path): Path<PathParams>) -> Response {
             )
                 .into_response())
         }
-    })();
-
-    match res {
-        Ok(res) => res,
-        Err(e) => panic!("Server Error: {e}"),
     }
 }
 
-pub async fn render_profile_update(Path(path): Path<PathParams>) -> Response {
-    let res: Result<Response> = (|| {
-        let token = path.token.unwrap_or("null".to_string());
-
-        if token == "null".to_string() {
-            Ok((StatusCode::OK, Html("<p>$  invalid input</p>")).into_response())
-        } else {
-            let user = parse_jwt(&token)?;
-
-            Ok((
-                StatusCode::OK,
-                Html(format!(
-                    "<p>$  account updated! username: {}, email: {}</p>",
-                    user.username, user.email
-                )),
-            )
-                .into_response())
-        }
-    })();
-
-    match res {
-        Ok(res) => res,
-        Err(e) => panic!("Server Error: {e}"),
+pub async fn render_profile_update(auth: AuthUser) -> HtmlResult {
+    match auth.0 {
+        None => Ok((StatusCode::OK, Html("<p>$  invalid input</p>")).into_response()),
+        Some(user) => Ok((
+            StatusCode::OK,
+            Html(format!(
+                "<p>$  account updated! username: {}, email: {}</p>",
+                user.username, user.email
+            )),
+        )
+            .into_response()),
     }
 }
 
@@ -224,175 +210,684 @@ pub async fn render_profile_delete() -> Response {
     Html("<p>$  deleting account</p>".to_string()).into_response()
 }
 
-pub async fn render_home_shortcuts(Path(path): Path<PathParams>) -> Response {
-    let res: Result<Response> = (|| {
-        let token = path.token.unwrap_or("null".to_string());
+// Personal tokens with their scopes, and the form that creates them
+pub async fn render_api_token_list(State(state): ConnectionState, auth: AuthUser) -> HtmlResult {
+    let Some(user) = auth.0 else {
+        return Ok(Html("<p>$  login to manage tokens</p>").into_response());
+    };
+    let tokens = state.api_tokens(user.email).await?;
+    let now = now_secs()?;
+
+    let mut html_tokens = String::new();
+    for token in &tokens {
+        let scopes = token
+            .scopes
+            .iter()
+            .map(|scope| scope.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let status = match (token.revoked, token.expires) {
+            (true, _) => "revoked".to_string(),
+            (false, Some(expires)) if expires <= now => "expired".to_string(),
+            (false, Some(expires)) => {
+                format!("expires in {} days", (expires - now).div_ceil(86400))
+            }
+            (false, None) => "never expires".to_string(),
+        };
+        let last_used = match token.last_used {
+            Some(last_used) => format!(
+                "used {} ago",
+                humanize_date(Some((last_used * 1000).to_string()))?
+            ),
+            None => "never used".to_string(),
+        };
+        let revoke = match token.revoked {
+            true => String::new(),
+            false => format!(
+                r#"<input type="button" value="revoke" onclick="revokeApiToken({})" />"#,
+                token.id
+            ),
+        };
 
-        if token == "null".to_string() {
-            Ok((
-                StatusCode::OK,
-                Html(
+        html_tokens.push_str(&format!(
+            r#"
+            <div class="token-item">
+                <p>{} - {scopes}, {status}, {last_used}</p>
+                {revoke}
+            </div>
+            "#,
+            escape_html(&token.name),
+        ));
+    }
+
+    if tokens.is_empty() {
+        html_tokens.push_str("<p>$  no tokens</p>");
+    }
+
+    Ok(Html(format!(
+        r#"
+        <form id="token-form" onsubmit="createApiToken(event)">
+            $  new token <input name="name" type="text" placeholder="name" />
+            <label><input name="scopes" type="checkbox" value="read-history" /> read-history</label>
+            <label><input name="scopes" type="checkbox" value="execute" /> execute</label>
+            <label><input name="scopes" type="checkbox" value="manage-collections" /> manage-collections</label>
+            expires in <input name="expires_in_days" type="number" min="1" placeholder="never" /> days
+            <input type="submit" value="create" />
+        </form>
+        <div id="token-notice"></div>
+        {html_tokens}
+    "#
+    ))
+    .into_response())
+}
+
+// The user's workspaces with their members, owners also get the forms that manage them
+pub async fn render_workspace_list(State(state): ConnectionState, auth: AuthUser) -> HtmlResult {
+    let Some(user) = auth.0 else {
+        return Ok(Html("<p>$  login to manage workspaces</p>").into_response());
+    };
+    let workspaces = state.workspaces(user.email.clone()).await?;
+
+    let mut html_workspaces = String::new();
+    for workspace in &workspaces {
+        let owner = workspace.role == Role::Owner;
+        let members = state.members(workspace.id).await?;
+
+        let html_members = members
+            .iter()
+            .map(|member| {
+                let remove = match (owner, member.user_email == user.email) {
+                    (_, true) => "leave",
+                    (true, false) => "remove",
+                    (false, false) => "",
+                };
+                let button = match remove {
+                    "" => String::new(),
+                    remove => format!(
+                        r#"<input type="button" value="{remove}" onclick="removeMember({})" />"#,
+                        member.id
+                    ),
+                };
+
+                format!(
                     r#"
+                <div class="member-item">
+                    <p>{} ({}) - {}</p>
+                    {button}
+                </div>
+                "#,
+                    escape_html(&member.username),
+                    escape_html(&member.user_email),
+                    member.role.as_str(),
+                )
+            })
+            .collect::<String>();
+
+        let html_manage = match owner {
+            false => String::new(),
+            true => format!(
+                r#"
+                <form class="member-form" onsubmit="setMember(event, {id})">
+                    $  add or change <input name="email" type="text" placeholder="email" />
+                    as <select name="role">
+                        <option value="viewer">viewer</option>
+                        <option value="editor">editor</option>
+                        <option value="owner">owner</option>
+                    </select>
+                    <input type="submit" value="save" />
+                    <input type="button" value="delete workspace" onclick="deleteWorkspace({id})" />
+                </form>
+                "#,
+                id = workspace.id
+            ),
+        };
+
+        html_workspaces.push_str(&format!(
+            r#"
+            <div class="workspace-item">
+                <p>$  {} - {}, created {} ago</p>
+                {html_members}
+                {html_manage}
+            </div>
+            "#,
+            escape_html(&workspace.name),
+            workspace.role.as_str(),
+            humanize_date(Some(workspace.date.clone()))?,
+        ));
+    }
+
+    if workspaces.is_empty() {
+        html_workspaces.push_str("<p>$  no workspaces</p>");
+    }
+
+    Ok(Html(format!(
+        r#"
+        <form id="workspace-form" onsubmit="createWorkspace(event)">
+            $  new workspace <input name="name" type="text" placeholder="name" />
+            <input type="submit" value="create" />
+        </form>
+        <div id="workspace-notice"></div>
+        {html_workspaces}
+    "#
+    ))
+    .into_response())
+}
+
+pub async fn render_home_shortcuts(auth: AuthUser) -> HtmlResult {
+    match auth.0 {
+        None => Ok((
+            StatusCode::OK,
+            Html(
+                r#"
                     <div><kbd>ctrl</kbd> + <kbd>alt</kbd> + <kbd>l</kbd> - login page</div>
                     <div><kbd>ctrl</kbd> + <kbd>alt</kbd> + <kbd>s</kbd> - signup page</div>
                 "#,
-                ),
-            )
-                .into_response())
-        } else {
-            Ok((
-                StatusCode::OK,
-                Html(
-                    r#"
+            ),
+        )
+            .into_response()),
+        Some(_) => Ok((
+            StatusCode::OK,
+            Html(
+                r#"
                     <div><kbd>ctrl</kbd> + <kbd>alt</kbd> + <kbd>p</kbd> - profile page</div>
                     <div><kbd>ctrl</kbd> + <kbd>alt</kbd> + <kbd>l</kbd> - logout</div>
                 "#,
-                ),
-            )
-                .into_response())
-        }
-    })();
-
-    match res {
-        Ok(res) => res,
-        Err(e) => panic!("Server Error: {e}"),
+            ),
+        )
+            .into_response()),
     }
 }
 
-pub async fn render_new_request(Path(path): Path<PathParams>) -> Response {
-    let email = path.email.unwrap_or("anon".to_string());
-
-    let html = format!(
-        r##"
+pub async fn render_new_request() -> Response {
+    let html = r##"
         <form id="new-request"
-            hx-post="/grpcurl/request/{email}"
+            hx-post="/grpcurl/request"
             hx-target="#request-response"
             hx-swap="innerHTML"
             hx-ext="json-enc"
+            hx-on::config-request="assignCall(event)"
             hx-on::before-request="loading()"
-            hx-on::after-request="formatResponse()"
+            hx-on::after-request="formatResponse(); watchStream()"
         >
-            $  grpcurl <textarea name="command" type="text" placeholder="command" autofocus></textarea>
+            $  grpcurl <textarea name="command" type="text" placeholder="command" autofocus
+                oninput="requestCompletions()" onkeydown="acceptCompletion(event)"></textarea>
             <input type="submit" value="execute" />
+            <input type="button" value="skeleton" onclick="generateSkeleton()" />
         </form>
+        <div id="completions"></div>
+        <div id="environments"
+            hx-get="/handle/environment/list"
+            hx-trigger="load"
+            hx-swap="innerHTML"
+        ></div>
+        <form id="service-browser"
+            hx-post="/handle/request/browse"
+            hx-target="#browser-tree"
+            hx-swap="innerHTML"
+            hx-ext="json-enc"
+        >
+            $  browse <input name="target" type="text" placeholder="-plaintext localhost:50051" />
+            <label><input name="refresh" type="checkbox" value="true" /> refresh</label>
+            <input type="submit" value="browse" />
+            <div id="browser-tree"></div>
+        </form>
+        <details id="proto-registry"
+            hx-get="/handle/proto/list"
+            hx-trigger="toggle once"
+            hx-target="#proto-list"
+            hx-swap="innerHTML"
+        >
+            <summary>$  proto files</summary>
+            <form id="proto-upload" onsubmit="uploadProto(event)">
+                $  target <input name="target" type="text" placeholder="localhost:50051" />
+                import path <input name="name" type="text" placeholder="acme/v1/service.proto" />
+                <input name="file" type="file" accept=".proto,.protoset,.pb,.bin" />
+                <input type="submit" value="upload" />
+            </form>
+            <div id="proto-notice"></div>
+            <div id="proto-list"></div>
+        </details>
         <div id="request-response"></div>
-        "##
-    );
+        "##;
 
     Html(html).into_response()
 }
 
-pub async fn render_history_list(state: ConnectionState, Path(path): Path<PathParams>) -> Response {
-    let res: Result<Response> = (async || {
-        let requests = get_all_requests_from_db(state, Path(path)).await?;
-        let mut html_history_list = String::new();
-
-        if requests.is_empty() {
-            return Ok((
-                StatusCode::OK,
-                Html(r#"<br /><p style="margin-left:15px;">$  no history</p"#),
-            )
-                .into_response());
+// Services, methods and message types a target exposes through reflection (or its uploaded
+// proto files), picking a method fills the command form with it
+pub async fn render_service_browser(
+    state: ConnectionState,
+    Extension(cache): Extension<DescriptorCache>,
+    auth: AuthUser,
+    Json(body): Json<BrowseBody>,
+) -> Response {
+    // Methods fill the form with the target as typed, so its placeholders stay in the command
+    let command = match resolve_command_variables(
+        State(state.0.clone()),
+        auth.owner(),
+        &format!("{} list", body.target),
+    )
+    .await
+    .and_then(|resolved| GrpcCommand::parse(&resolved.command))
+    {
+        Ok(command) => command,
+        Err(e) => {
+            return Html(format!("<p>$  error: {}</p>", escape_html(&e.to_string())))
+                .into_response();
         }
-
-        for (i, request) in requests.iter().enumerate() {
-            let date = humanize_date(Some(request.date.clone()))?;
-            let status_color = get_status_color(&request.status);
-            let service_name = get_service_name(&request.command);
-
-            html_history_list.push_str(&format!(
+    };
+    let registry = match get_command_protoset(state, auth.owner(), &command).await {
+        Ok(registry) => registry,
+        Err(e) => {
+            return Html(format!("<p>$  error: {}</p>", escape_html(&e.to_string())))
+                .into_response();
+        }
+    };
+    let pool = match cache
+        .get_or_load(&command, registry.as_deref(), body.refresh.is_some())
+        .await
+    {
+        Ok(pool) => pool,
+        Err(e) => {
+            return Html(format!("<p>$  error: {}</p>", escape_html(&e.to_string())))
+                .into_response();
+        }
+    };
+
+    let target = body.target.trim();
+    let mut html_tree = String::new();
+
+    for service in pool.services() {
+        let mut html_methods = String::new();
+
+        for method in service.methods() {
+            let messages = [method.input(), method.output()]
+                .iter()
+                .map(|message| {
+                    format!(
+                        "<details><summary>{}</summary><pre>{}</pre></details>",
+                        message.full_name(),
+                        escape_html(&describe_message(message, 0))
+                    )
+                })
+                .collect::<String>();
+
+            html_methods.push_str(&format!(
                 r#"
-                    <div class="history-item" tabindex="{}" id="{}">
-                        <div class="history-item-left-container">
-                            <p style="color: {};font-size:18px;">{}</p>
-                            <p>{}</p>
-                        </div>
-                        <div class="history-item-right-container">
-                            <p><bdi>{}</bdi></p>
-                            <p>{}</p>
-                        </div>
-                        <div class="added-favorite">added to favorites</div>
-                        <div class="removed-favorite">removed from favorites</div>
-                        <div class="not-loggedin">log in to save favorites</div>
-                        <div class="deleted-item">deleted item</div>
-                        <input type="hidden" name="command" value="{}" />
+                    <div class="browser-method" tabindex="0" data-command="{}">
+                        <p>{}</p>
+                        {messages}
                     </div>
-                    "#,
-                i + 1,
-                request.id.unwrap_or_default(),
-                status_color,
-                request.status.clone().unwrap_or_default(),
-                request.method.clone().unwrap_or_default(),
-                service_name,
-                date,
-                request.command,
+                "#,
+                escape_html(&format!(
+                    "{target} {}/{}",
+                    service.full_name(),
+                    method.name()
+                )),
+                escape_html(&describe_method(&method)),
             ));
         }
 
-        Ok(Html(html_history_list).into_response())
-    })()
-    .await;
+        html_tree.push_str(&format!(
+            r#"<details class="browser-service"><summary>{}</summary>{html_methods}</details>"#,
+            service.full_name()
+        ));
+    }
 
-    match res {
-        Ok(res) => res,
-        Err(e) => panic!("Server Error: {e}"),
+    if html_tree.is_empty() {
+        return Html("<p>$  no services found</p>").into_response();
     }
+
+    Html(html_tree).into_response()
 }
 
-pub async fn render_favorites_list(
-    state: ConnectionState,
-    Path(path): Path<PathParams>,
-) -> Response {
-    let res: Result<Response> = (async || {
-        let favorites = get_all_favorites_from_db(state, Path(path)).await?;
-        let mut html_favorites_list = String::new();
+// Uploaded files grouped by target, with whether each target's files compile together
+pub async fn render_proto_list(state: ConnectionState, auth: AuthUser) -> HtmlResult {
+    let protos = get_all_protos_from_db(state.clone(), auth.owner()).await?;
 
-        if favorites.is_empty() {
-            return Ok(Html(
-                r#"<br /><p style="margin-left:15px;">$  no favorites</p>"#.to_string(),
-            )
-            .into_response());
-        }
+    if protos.is_empty() {
+        return Ok(Html("<p>$  no proto files</p>").into_response());
+    }
+
+    let mut targets = protos.iter().map(|proto| &proto.target).collect::<Vec<_>>();
+    targets.dedup();
 
-        for (i, request) in favorites.iter().enumerate() {
-            let date = humanize_date(Some(request.date.clone()))?;
-            let status_color = get_status_color(&request.status);
-            let service_name = get_service_name(&request.command);
+    let mut html_proto_list = String::new();
+    for target in targets {
+        let compiled =
+            match get_registry_protoset_from_db(state.clone(), auth.owner(), target).await {
+                Ok(_) => "compiles".to_string(),
+                Err(e) => format!("error: {}", escape_html(&e.to_string())),
+            };
 
-            html_favorites_list.push_str(&format!(
+        html_proto_list.push_str(&format!("<p>$  {} ({compiled})</p>", escape_html(target)));
+
+        for proto in protos.iter().filter(|proto| &proto.target == target) {
+            html_proto_list.push_str(&format!(
                 r#"
-                    <div class="favorites-item" tabindex="{}" id="{}">
-                        <div class="favorites-item-left-container">
-                            <p style="color: {};font-size:18px;">{}</p>
-                            <p>{}</p>
-                        </div>
-                        <div class="favorites-item-right-container">
-                            <p><bdi>{}</bdi></p>
-                            <p>{}</p>
-                        </div>
-                        <div class="added-favorite">added to favorites</div>
-                        <div class="removed-favorite">removed from favorites</div>
-                        <div class="deleted-item">deleted item</div>
-                        <input type="hidden" name="command" value="{}" />
-                    </div>
-                    "#,
-                i + 1,
-                request.id.unwrap_or_default(),
-                status_color,
-                request.status.clone().unwrap_or_default(),
-                request.method.clone().unwrap_or_default(),
-                service_name,
-                date,
-                request.command,
+            <div class="proto-item">
+                <p>{} - {}, {} bytes, {} ago</p>
+                <input type="button" value="delete" onclick="deleteProto({})" />
+            </div>
+            "#,
+                escape_html(&proto.name),
+                proto.kind,
+                proto.size.unwrap_or_default(),
+                humanize_date(Some(proto.date.clone()))?,
+                proto.id.unwrap_or_default(),
             ));
         }
+    }
 
-        Ok(Html(html_favorites_list).into_response())
-    })()
-    .await;
+    Ok(Html(html_proto_list).into_response())
+}
 
-    match res {
-        Ok(res) => res,
-        Err(e) => panic!("Server Error: {e}"),
+// The environment switcher, with every environment's variables under it
+pub async fn render_environment_list(state: ConnectionState, auth: AuthUser) -> HtmlResult {
+    let environments = get_all_environments_from_db(state, auth.owner()).await?;
+    let active = environments
+        .iter()
+        .find(|environment| environment.active)
+        .map(|environment| environment.name.clone())
+        .unwrap_or_default();
+
+    let mut html_options = format!(
+        r#"<option value=""{}>none</option>"#,
+        if active.is_empty() { " selected" } else { "" }
+    );
+    let mut html_environments = String::new();
+
+    for environment in &environments {
+        let name = escape_html(&environment.name);
+        let selected = if environment.active { " selected" } else { "" };
+        html_options.push_str(&format!(
+            r#"<option value="{name}"{selected}>{name}</option>"#
+        ));
+
+        let variables = environment
+            .variables
+            .iter()
+            .map(|variable| {
+                format!(
+                    r#"
+                <div class="variable-item">
+                    <p>{{{{{}}}}} = {}{}</p>
+                    <input type="button" value="delete" onclick="deleteVariable({})" />
+                </div>
+                "#,
+                    escape_html(&variable.key),
+                    escape_html(&variable.value),
+                    if variable.secret { " (secret)" } else { "" },
+                    variable.id.unwrap_or_default(),
+                )
+            })
+            .collect::<String>();
+
+        html_environments.push_str(&format!(
+            r#"
+            <div class="environment-item">
+                <p>$  {name}{}, updated {} ago</p>
+                <input type="button" value="delete" onclick="deleteEnvironment({})" />
+            </div>
+            {variables}
+            "#,
+            if environment.active { " (active)" } else { "" },
+            humanize_date(Some(environment.date.clone()))?,
+            environment.id.unwrap_or_default(),
+        ));
+    }
+
+    Ok(Html(format!(
+        r#"
+        $  env <select id="environment-switcher" onchange="switchEnvironment(this.value)">{html_options}</select>
+        <details id="environment-variables">
+            <summary>$  variables</summary>
+            <form id="variable-form" onsubmit="setVariable(event)">
+                $  environment <input name="environment" type="text" placeholder="staging" value="{}" />
+                key <input name="key" type="text" placeholder="host" />
+                value <input name="value" type="text" placeholder="localhost:50051" />
+                <label><input name="secret" type="checkbox" /> secret</label>
+                <input type="submit" value="set" />
+            </form>
+            <div id="environment-notice"></div>
+            {html_environments}
+        </details>
+    "#,
+        escape_html(&active)
+    ))
+    .into_response())
+}
+
+// The collections modal, nested folders are <details> so they can be folded from the keyboard
+pub async fn render_collection_list(state: ConnectionState, auth: AuthUser) -> HtmlResult {
+    let collections = get_all_collections_from_db(state, auth.owner()).await?;
+
+    let mut html_options = String::new();
+    collection_options(&collections, 0, &mut html_options);
+
+    let html_tree = match collections.is_empty() {
+        true => r#"<p style="margin-left:15px;">$  no collections</p>"#.to_string(),
+        false => collections.iter().map(render_collection_node).collect(),
+    };
+
+    Ok(Html(format!(
+        r#"
+        <div id="collection-forms">
+            <form id="collection-form" onsubmit="createCollection(event)">
+                $  new <input name="name" type="text" placeholder="collection or folder" />
+                in <select name="parent_id"><option value="">top level</option>{html_options}</select>
+                <input type="submit" value="add" />
+            </form>
+            <form id="saved-request-form" onsubmit="saveRequest(event)">
+                $  save command as <input name="title" type="text" placeholder="title" />
+                <input name="description" type="text" placeholder="description" />
+                in <select name="collection_id">{html_options}</select>
+                <input type="submit" value="save" />
+            </form>
+            <div id="collection-notice"></div>
+        </div>
+        <div id="collection-tree" class="collection-children" data-id="">{html_tree}</div>
+    "#
+    ))
+    .into_response())
+}
+
+fn collection_options(collections: &[Collection], depth: usize, html: &mut String) {
+    for collection in collections {
+        html.push_str(&format!(
+            r#"<option value="{}">{}{}</option>"#,
+            collection.id.unwrap_or_default(),
+            "&nbsp;&nbsp;".repeat(depth),
+            escape_html(&collection.name)
+        ));
+        collection_options(&collection.folders, depth + 1, html);
+    }
+}
+
+fn render_collection_node(collection: &Collection) -> String {
+    let id = collection.id.unwrap_or_default();
+    let folders = collection
+        .folders
+        .iter()
+        .map(render_collection_node)
+        .collect::<String>();
+    let requests = collection
+        .requests
+        .iter()
+        .map(|request| {
+            format!(
+                r#"
+                <div class="collection-item" tabindex="0" id="saved-{}" data-id="{}" draggable="true">
+                    <p>{}</p>
+                    <p>{}</p>
+                    <p><bdi>{}</bdi></p>
+                    <input type="hidden" name="command" value="{}" />
+                </div>
+                "#,
+                request.id.unwrap_or_default(),
+                request.id.unwrap_or_default(),
+                escape_html(&request.title),
+                escape_html(&request.description.clone().unwrap_or_default()),
+                escape_html(&get_service_name(&request.command)),
+                escape_html(&request.command),
+            )
+        })
+        .collect::<String>();
+
+    format!(
+        r#"
+        <details class="collection-folder" data-id="{id}" draggable="true" open>
+            <summary class="collection-summary" tabindex="0">
+                {}
+                <input type="button" value="rename" onclick="renameCollection({id})" />
+                <input type="button" value="delete" onclick="deleteCollection({id})" />
+            </summary>
+            <div class="collection-children" data-id="{id}">{folders}{requests}</div>
+        </details>
+        "#,
+        escape_html(&collection.name),
+    )
+}
+
+pub async fn render_history_list(state: ConnectionState, auth: AuthUser) -> HtmlResult {
+    let favorite_ids = get_favorite_ids_from_db(state.clone(), auth.owner()).await?;
+    let requests = get_all_requests_from_db(state, auth.owner()).await?;
+    let mut html_history_list = String::new();
+
+    if requests.is_empty() {
+        return Ok((
+            StatusCode::OK,
+            Html(r#"<br /><p style="margin-left:15px;">$  no history</p"#),
+        )
+            .into_response());
     }
+
+    for (i, request) in requests.iter().enumerate() {
+        let date = humanize_date(Some(request.date.clone()))?;
+        let status_color = get_status_color(&request.status);
+        let service_name = escape_html(&get_service_name(&request.command));
+
+        html_history_list.push_str(&format!(
+            r#"
+                <div class="history-item" tabindex="{}" id="{}" data-favorite="{}">
+                    <div class="history-item-left-container">
+                        <p style="color: {};font-size:18px;">{}</p>
+                        <p>{}</p>
+                    </div>
+                    <div class="history-item-right-container">
+                        <p><bdi>{}</bdi></p>
+                        <p>{}</p>
+                    </div>
+                    <div class="added-favorite">added to favorites</div>
+                    <div class="removed-favorite">removed from favorites</div>
+                    <div class="not-loggedin">log in to save favorites</div>
+                    <div class="deleted-item">deleted item</div>
+                    <input type="hidden" name="command" value="{}" />
+                </div>
+                "#,
+            i + 1,
+            request.id.unwrap_or_default(),
+            request.id.is_some_and(|id| favorite_ids.contains(&id)),
+            status_color,
+            escape_html(&request.status.clone().unwrap_or_default()),
+            escape_html(&request.method.clone().unwrap_or_default()),
+            service_name,
+            date,
+            escape_html(&request.command),
+        ));
+    }
+
+    Ok(Html(html_history_list).into_response())
+}
+
+pub async fn render_favorites_list(state: ConnectionState, auth: AuthUser) -> HtmlResult {
+    let favorites = get_all_favorites_from_db(state, auth.owner()).await?;
+    let mut html_favorites_list = String::new();
+
+    if favorites.is_empty() {
+        return Ok(
+            Html(r#"<br /><p style="margin-left:15px;">$  no favorites</p>"#.to_string())
+                .into_response(),
+        );
+    }
+
+    for (i, request) in favorites.iter().enumerate() {
+        let date = humanize_date(Some(request.date.clone()))?;
+        let status_color = get_status_color(&request.status);
+        let service_name = escape_html(&get_service_name(&request.command));
+
+        html_favorites_list.push_str(&format!(
+            r#"
+                <div class="favorites-item" tabindex="{}" id="{}" data-favorite="true">
+                    <div class="favorites-item-left-container">
+                        <p style="color: {};font-size:18px;">{}</p>
+                        <p>{}</p>
+                    </div>
+                    <div class="favorites-item-right-container">
+                        <p><bdi>{}</bdi></p>
+                        <p>{}</p>
+                    </div>
+                    <div class="added-favorite">added to favorites</div>
+                    <div class="removed-favorite">removed from favorites</div>
+                    <div class="deleted-item">deleted item</div>
+                    <input type="hidden" name="command" value="{}" />
+                </div>
+                "#,
+            i + 1,
+            request.id.unwrap_or_default(),
+            status_color,
+            escape_html(&request.status.clone().unwrap_or_default()),
+            escape_html(&request.method.clone().unwrap_or_default()),
+            service_name,
+            date,
+            escape_html(&request.command),
+        ));
+    }
+
+    Ok(Html(html_favorites_list).into_response())
+}
+
+pub async fn render_stored_response(
+    state: ConnectionState,
+    auth: AuthUser,
+    Path(path): Path<PathParams>,
+) -> HtmlResult {
+    let request_id = path
+        .id
+        .ok_or_else(|| AppError::bad_request("Missing request id"))?;
+    let Some(request) = get_request_from_db(state, auth.owner(), request_id).await? else {
+        return Ok(Html("<p>$  request not found</p>").into_response());
+    };
+
+    let date = humanize_date(Some(request.date.clone()))?;
+    let status = escape_html(&request.status.clone().unwrap_or_default());
+    let metadata = render_response_metadata(&request);
+
+    let body = match &request.response {
+        Some(response) => format!(
+            r#"<textarea id="response-textarea" readonly>{}&#013;</textarea>"#,
+            escape_html(response)
+        ),
+        None => "<p>$  no response body was stored for this request</p>".to_string(),
+    };
+    let resolved = match &request.resolved_command {
+        Some(resolved) => format!("<p>$  sent as: {}</p>", escape_html(resolved)),
+        None => String::new(),
+    };
+    let truncated = match (request.response_truncated, request.response_size) {
+        (true, Some(size)) => format!("<p>$  response was cut short, it was {size} bytes</p>"),
+        _ => String::new(),
+    };
+
+    Ok(Html(format!(
+        r#"
+        $  status: {status}, stored {date} ago
+        <br /><br />
+        {resolved}
+        {metadata}
+        {body}
+        {truncated}
+    "#
+    ))
+    .into_response())
 }
@@ -0,0 +1,87 @@
+use axum::{
+    extract::{Json, Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+
+use crate::{
+    handlers::{ApiResult, AppError, AuthUser, ConnectionState, PathParams},
+    models::token::{CreatedApiToken, NewApiToken},
+    utils::{generate_api_token, hash_token, now_secs},
+};
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+// Ten years, tokens that should outlive that can be left without an expiry
+const MAX_EXPIRES_IN_DAYS: u64 = 3650;
+
+pub async fn get_all_api_tokens(State(state): ConnectionState, auth: AuthUser) -> ApiResult {
+    let tokens = state.api_tokens(auth.required()?.email).await?;
+
+    Ok((StatusCode::OK, Json(tokens)).into_response())
+}
+
+// The only response the token is ever in, scripts and CI send it as a bearer token
+pub async fn create_api_token(
+    State(state): ConnectionState,
+    auth: AuthUser,
+    Json(new_token): Json<NewApiToken>,
+) -> ApiResult {
+    let email = auth.required()?.email;
+    let name = new_token.name.trim().to_string();
+
+    if name.is_empty() {
+        return Err(AppError::bad_request("Token name is required"));
+    }
+    if new_token.scopes.is_empty() {
+        return Err(AppError::bad_request("Pick at least one scope"));
+    }
+
+    let now = now_secs()?;
+    let expires = match new_token.expires_in_days {
+        Some(days @ 1..=MAX_EXPIRES_IN_DAYS) => Some(
+            days.checked_mul(SECONDS_PER_DAY)
+                .and_then(|seconds| now.checked_add(seconds))
+                .ok_or_else(|| AppError::bad_request("Token expiry is out of range"))?,
+        ),
+        Some(_) => {
+            return Err(AppError::bad_request(format!(
+                "Tokens must last between 1 and {MAX_EXPIRES_IN_DAYS} days"
+            )));
+        }
+        None => None,
+    };
+    let token = generate_api_token()?;
+
+    let mut scopes = new_token.scopes;
+    scopes.sort();
+    scopes.dedup();
+
+    let api_token = state
+        .create_api_token(
+            email,
+            name,
+            hash_token(&token),
+            scopes,
+            expires,
+            (now * 1000).to_string(),
+        )
+        .await?;
+
+    Ok((StatusCode::OK, Json(CreatedApiToken { token, api_token })).into_response())
+}
+
+pub async fn revoke_api_token(
+    State(state): ConnectionState,
+    auth: AuthUser,
+    Path(path): Path<PathParams>,
+) -> ApiResult {
+    let email = auth.required()?.email;
+    let token_id = path
+        .id
+        .ok_or_else(|| AppError::bad_request("Missing token id"))?;
+
+    match state.revoke_api_token(email, token_id.get().into()).await? {
+        true => Ok((StatusCode::OK).into_response()),
+        false => Err(AppError::not_found("Token not found")),
+    }
+}
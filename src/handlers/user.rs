@@ -1,189 +1,201 @@
 use axum::{
+    Extension,
     extract::{Json, State},
-    http::StatusCode,
-    response::{IntoResponse, Response},
+    http::{HeaderMap, StatusCode, header::SET_COOKIE},
+    response::{AppendHeaders, IntoResponse},
 };
-use miette::{Result, miette};
+use miette::Result;
 
-use crate::utils::{create_jwt, hash_password, verify_password};
+use crate::utils::{
+    JwtSignature, REFRESH_COOKIE, REFRESH_TOKEN_TTL, cleared_session_cookies, create_jwt,
+    csrf_token, generate_refresh_token, hash_password, hash_token, now_secs, password_needs_rehash,
+    read_cookie, session_cookies, verify_password,
+};
 use crate::{
-    handlers::{ConnectionState, map_user},
-    models::{serialize_bool_for_db, serialize_favorites_for_db, user::User},
+    handlers::{ApiResult, AppError, AuthUser, ConnectionState},
+    models::user::{Profile, User},
+    repository::{Repository, WORKSPACE_OWNER_PREFIX},
 };
 
-pub async fn get_user(State(state): ConnectionState, Json(user): Json<User>) -> Response {
-    let res: Result<Response> = (|| {
-        let (email, password) = (user.email, user.password);
-        let db = state
-            .lock()
-            .map_err(|e| miette!("Global db can't block current thread {e}"))?;
-
-        match map_user(
-            db.prepare(r#"SELECT * FROM "user" WHERE email = ?1 AND deleted = false"#)
-                .map_err(|e| miette!("Invalid statement: {e}"))?,
-            &[&email],
-        ) {
-            Ok(mapped_user) => {
-                if let Some(parsed_user) = mapped_user.first() {
-                    if parsed_user.deleted {
-                        return Ok((StatusCode::NOT_FOUND).into_response());
-                    }
-
-                    match verify_password(&password, &parsed_user.password) {
-                        Ok(password_valid) => {
-                            if !password_valid {
-                                return Ok((StatusCode::UNAUTHORIZED).into_response());
-                            }
-
-                            Ok((StatusCode::OK, Json(create_jwt(parsed_user.to_owned())?))
-                                .into_response())
-                        }
-                        Err(e) => Ok((
-                            StatusCode::INTERNAL_SERVER_ERROR,
-                            format!("Server Error: {e}"),
-                        )
-                            .into_response()),
-                    }
-                } else {
-                    Ok((StatusCode::NOT_FOUND,).into_response())
-                }
-            }
-            Err(e) => Ok((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Server Error: {e}"),
-            )
-                .into_response()),
-        }
-    })();
+pub async fn get_user(
+    State(state): ConnectionState,
+    Extension(signature): Extension<JwtSignature>,
+    Json(user): Json<User>,
+) -> ApiResult {
+    let (email, password) = (user.email, user.password);
 
-    match res {
-        Ok(res) => res,
-        Err(e) => panic!("{e}"),
-    }
-}
+    let Some(mut parsed_user) = state.user(email.clone()).await? else {
+        return Err(AppError::not_found("User not found"));
+    };
 
-pub async fn create_user(State(state): ConnectionState, Json(user): Json<User>) -> Response {
-    let res: Result<Response> = (|| {
-        let db = state
-            .lock()
-            .map_err(|e| miette!("Global db can't block current thread {e}"))?;
-
-        let hashed_password = hash_password(&user.password)?;
-
-        match map_user(
-            db.prepare(
-                r#"
-                INSERT INTO "user" (username, email, password, favorites, date, deleted) 
-                VALUES (?1, ?2, ?3, ?4, ?5, ?6) RETURNING *
-            "#,
-            )
-            .map_err(|e| miette!("Invalid statement: {e}"))?,
-            &[
-                &user.username,
-                &user.email,
-                &hashed_password,
-                &serialize_favorites_for_db(&user.favorites),
-                &user.date.clone().unwrap_or_default(),
-                &serialize_bool_for_db(user.deleted).to_string(),
-            ],
-        ) {
-            Ok(_) => Ok((StatusCode::OK, Json(create_jwt(user)?)).into_response()),
-            Err(e) => Ok((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Server Error: {e}"),
-            )
-                .into_response()),
-        }
-    })();
+    if !verify_password(&password, &parsed_user.password)? {
+        return Err(AppError::Unauthorized("Incorrect password".to_string()));
+    }
 
-    match res {
-        Ok(res) => res,
-        Err(e) => panic!("{e}"),
+    // The only time the plain password is around to upgrade a legacy or outdated hash
+    if password_needs_rehash(&parsed_user.password)? {
+        parsed_user.password = hash_password(&password)?;
+        state
+            .update_password(email, parsed_user.password.clone())
+            .await?;
     }
-}
 
-pub async fn update_user(State(state): ConnectionState, Json(mut user): Json<User>) -> Response {
-    let res: Result<Response> = (|| {
-        let db = state
-            .lock()
-            .map_err(|e| miette!("Global db can't block current thread {e}"))?;
-
-        user.password = hash_password(&user.password)?;
-
-        match map_user(
-            db.prepare(r#"UPDATE "user" SET username = ?1, password = ?2 WHERE email = ?3"#)
-                .map_err(|e| miette!("Invalid statement: {e}"))?,
-            &[&user.username, &user.password, &user.email],
-        ) {
-            Ok(_) => Ok((StatusCode::OK, Json(create_jwt(user)?)).into_response()),
-            Err(e) => Ok((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Server Error: {e}"),
-            )
-                .into_response()),
-        }
-    })();
+    let cookies = start_session(&state, &signature, &parsed_user).await?;
 
-    match res {
-        Ok(res) => res,
-        Err(e) => panic!("{e}"),
+    Ok((
+        StatusCode::OK,
+        AppendHeaders(cookies.map(|cookie| (SET_COOKIE, cookie))),
+        Json(Profile::from(parsed_user)),
+    )
+        .into_response())
+}
+
+pub async fn create_user(
+    State(state): ConnectionState,
+    Extension(signature): Extension<JwtSignature>,
+    Json(user): Json<User>,
+) -> ApiResult {
+    if user.email.starts_with(WORKSPACE_OWNER_PREFIX) {
+        return Err(AppError::bad_request("That email can't be used"));
     }
+
+    let hashed_user = User {
+        password: hash_password(&user.password)?,
+        ..user
+    };
+
+    state.create_user(hashed_user.clone()).await?;
+
+    let cookies = start_session(&state, &signature, &hashed_user).await?;
+
+    Ok((
+        StatusCode::OK,
+        AppendHeaders(cookies.map(|cookie| (SET_COOKIE, cookie))),
+        Json(Profile::from(hashed_user)),
+    )
+        .into_response())
 }
 
-pub async fn delete_user(State(state): ConnectionState, Json(user): Json<User>) -> Response {
-    let res: Result<Response> = (|| {
-        let db = state
-            .lock()
-            .map_err(|e| miette!("Global db can't block current thread {e}"))?;
-
-        match map_user(
-            db.prepare(r#"UPDATE "user" SET deleted = true WHERE email = ?1 RETURNING *"#)
-                .map_err(|e| miette!("Invalid statement: {e}"))?,
-            &[&user.email],
-        ) {
-            Ok(_) => Ok((StatusCode::OK, Json(true)).into_response()),
-            Err(e) => Ok((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Server Error: {e}"),
-            )
-                .into_response()),
-        }
-    })();
+// Every other session is logged out, this one starts fresh
+pub async fn update_user(
+    State(state): ConnectionState,
+    Extension(signature): Extension<JwtSignature>,
+    auth: AuthUser,
+    Json(mut user): Json<User>,
+) -> ApiResult {
+    user.email = auth.required()?.email;
+    user.password = hash_password(&user.password)?;
+
+    state.update_user(user.clone()).await?;
+
+    let cookies = start_session(&state, &signature, &user).await?;
+
+    Ok((
+        StatusCode::OK,
+        AppendHeaders(cookies.map(|cookie| (SET_COOKIE, cookie))),
+        Json(Profile::from(user)),
+    )
+        .into_response())
+}
 
-    match res {
-        Ok(res) => res,
-        Err(e) => panic!("{e}"),
-    }
+pub async fn delete_user(State(state): ConnectionState, auth: AuthUser) -> ApiResult {
+    state.delete_user(auth.required()?.email).await?;
+
+    Ok((
+        StatusCode::OK,
+        AppendHeaders(cleared_session_cookies().map(|cookie| (SET_COOKIE, cookie))),
+        Json(true),
+    )
+        .into_response())
 }
 
-pub async fn update_favorites(State(state): ConnectionState, Json(user): Json<User>) -> Response {
-    let res: Result<Response> = (|| {
-        let db = state
-            .lock()
-            .map_err(|e| miette!("Global db can't block current thread {e}"))?;
-
-        match map_user(
-            db.prepare(r#"UPDATE "user" SET favorites = ?1 WHERE email = ?2 AND deleted = false RETURNING *"#)
-                .map_err(|e| miette!("Invalid statement: {e}"))?,
-            &[&serialize_favorites_for_db(&user.favorites), &user.email],
-        ) {
-            Ok(mapped_user) => {
-                if let Some(parsed_user) = mapped_user.first() {
-                    Ok((StatusCode::OK, Json(create_jwt(parsed_user.clone())?)).into_response())
-                } else {
-                    Ok((StatusCode::BAD_REQUEST).into_response())
-                }
-            }
-            Err(e) => Ok((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Server Error: {e}"),
-            )
-                .into_response()),
+// Trades the refresh cookie for a new session and refresh cookie, the old one can't be used again
+pub async fn refresh_session(
+    State(state): ConnectionState,
+    Extension(signature): Extension<JwtSignature>,
+    headers: HeaderMap,
+) -> ApiResult {
+    let key = signature.key()?;
+    let refresh_token = generate_refresh_token()?;
+    let now = now_secs()?;
+
+    let rotated = match read_cookie(&headers, REFRESH_COOKIE) {
+        Some(old_token) => {
+            state
+                .rotate_session(
+                    hash_token(&old_token),
+                    hash_token(&refresh_token),
+                    now + REFRESH_TOKEN_TTL,
+                    now,
+                )
+                .await?
         }
-    })();
+        None => None,
+    };
+
+    let user = match rotated {
+        Some((session, email)) => state.user(email).await?.map(|user| (user, session)),
+        None => None,
+    };
+
+    let Some((user, session)) = user else {
+        // Whatever the browser still holds is no good, so it can stop sending it
+        return Ok((
+            AppendHeaders(cleared_session_cookies().map(|cookie| (SET_COOKIE, cookie))),
+            AppError::Unauthorized("Session expired, login again".to_string()),
+        )
+            .into_response());
+    };
+
+    let cookies = session_cookies(
+        &create_jwt(&user, session, key)?,
+        &refresh_token,
+        &csrf_token(key, session),
+    );
+
+    Ok((
+        StatusCode::OK,
+        AppendHeaders(cookies.map(|cookie| (SET_COOKIE, cookie))),
+        Json(Profile::from(user)),
+    )
+        .into_response())
+}
 
-    match res {
-        Ok(res) => res,
-        Err(e) => panic!("{e}"),
+pub async fn logout_user(State(state): ConnectionState, headers: HeaderMap) -> ApiResult {
+    if let Some(refresh_token) = read_cookie(&headers, REFRESH_COOKIE) {
+        state.revoke_session(hash_token(&refresh_token)).await?;
     }
+
+    Ok((
+        StatusCode::OK,
+        AppendHeaders(cleared_session_cookies().map(|cookie| (SET_COOKIE, cookie))),
+        Json(true),
+    )
+        .into_response())
+}
+
+// Opens a session and returns the cookies that carry it
+async fn start_session(
+    state: &Repository,
+    signature: &JwtSignature,
+    user: &User,
+) -> Result<[String; 3]> {
+    let key = signature.key()?;
+    let refresh_token = generate_refresh_token()?;
+    let now = now_secs()?;
+
+    let session = state
+        .create_session(
+            user.email.clone(),
+            hash_token(&refresh_token),
+            now + REFRESH_TOKEN_TTL,
+            (now * 1000).to_string(),
+        )
+        .await?;
+
+    Ok(session_cookies(
+        &create_jwt(user, session, key)?,
+        &refresh_token,
+        &csrf_token(key, session),
+    ))
 }
@@ -0,0 +1,213 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use axum::{
+    extract::{Json, Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+
+use crate::{
+    handlers::{ApiResult, AppError, AuthUser, ConnectionState, PathParams},
+    models::workspace::{
+        ActiveWorkspaceBody, AttachBody, MemberBody, NewWorkspace, Role, Workspace,
+    },
+    repository::Repository,
+};
+
+pub async fn get_all_workspaces(State(state): ConnectionState, auth: AuthUser) -> ApiResult {
+    let workspaces = state.workspaces(auth.required()?.email).await?;
+
+    Ok((StatusCode::OK, Json(workspaces)).into_response())
+}
+
+pub async fn create_workspace(
+    State(state): ConnectionState,
+    auth: AuthUser,
+    Json(workspace): Json<NewWorkspace>,
+) -> ApiResult {
+    let email = auth.required()?.email;
+    let name = workspace.name.trim().to_string();
+
+    if name.is_empty() {
+        return Err(AppError::bad_request("Workspace name is required"));
+    }
+
+    let workspace = state.create_workspace(email, name, now_millis()).await?;
+
+    Ok((StatusCode::OK, Json(workspace)).into_response())
+}
+
+// What the navbar switcher calls, data routes then work in that workspace
+pub async fn switch_workspace(
+    State(state): ConnectionState,
+    auth: AuthUser,
+    Json(body): Json<ActiveWorkspaceBody>,
+) -> ApiResult {
+    match state
+        .switch_workspace(auth.required()?.email, body.id)
+        .await?
+    {
+        true => Ok((StatusCode::OK, Json(true)).into_response()),
+        false => Err(AppError::not_found("Workspace not found")),
+    }
+}
+
+pub async fn get_workspace_members(
+    State(state): ConnectionState,
+    auth: AuthUser,
+    Path(path): Path<PathParams>,
+) -> ApiResult {
+    let workspace = member_of(&state, auth.required()?.email, &path).await?;
+    let members = state.members(workspace.id).await?;
+
+    Ok((StatusCode::OK, Json(members)).into_response())
+}
+
+// Adds a user by email, or changes the role of one already in
+pub async fn set_workspace_member(
+    State(state): ConnectionState,
+    auth: AuthUser,
+    Path(path): Path<PathParams>,
+    Json(member): Json<MemberBody>,
+) -> ApiResult {
+    let workspace = owner_of(&state, auth.required()?.email, &path).await?;
+    let email = member.email.trim().to_string();
+
+    let Some(user) = state.user(email.clone()).await? else {
+        return Err(AppError::not_found("User not found"));
+    };
+
+    if member.role != Role::Owner
+        && let Some(current) = state.workspace(user.email.clone(), workspace.id).await?
+        && current.role == Role::Owner
+    {
+        keep_an_owner(&state, workspace.id).await?;
+    }
+
+    let member = state
+        .set_member(workspace.id, user.email, member.role, now_millis())
+        .await?;
+
+    Ok((StatusCode::OK, Json(member)).into_response())
+}
+
+// Owners remove anyone, everyone else can only leave
+pub async fn remove_workspace_member(
+    State(state): ConnectionState,
+    auth: AuthUser,
+    Path(path): Path<PathParams>,
+) -> ApiResult {
+    let email = auth.required()?.email;
+    let member_id = path
+        .id
+        .ok_or_else(|| AppError::bad_request("Missing member id"))?;
+
+    let Some(member) = state.member(member_id.get().into()).await? else {
+        return Err(AppError::not_found("Member not found"));
+    };
+    let Some(workspace) = state.workspace(email.clone(), member.workspace_id).await? else {
+        return Err(AppError::not_found("Member not found"));
+    };
+
+    if workspace.role != Role::Owner && member.user_email != email {
+        return Err(AppError::Forbidden(
+            "Only owners remove other members".to_string(),
+        ));
+    }
+    if member.role == Role::Owner {
+        keep_an_owner(&state, workspace.id).await?;
+    }
+
+    state.remove_member(member.id).await?;
+
+    Ok((StatusCode::OK).into_response())
+}
+
+pub async fn delete_workspace(
+    State(state): ConnectionState,
+    auth: AuthUser,
+    Path(path): Path<PathParams>,
+) -> ApiResult {
+    let workspace = owner_of(&state, auth.required()?.email, &path).await?;
+
+    state.delete_workspace(workspace.id).await?;
+
+    Ok((StatusCode::OK).into_response())
+}
+
+// Moves one of the user's own items into a workspace they can edit
+pub async fn attach_to_workspace(
+    State(state): ConnectionState,
+    auth: AuthUser,
+    Path(path): Path<PathParams>,
+    Json(body): Json<AttachBody>,
+) -> ApiResult {
+    let email = auth.required()?.email;
+    let workspace = member_of(&state, email.clone(), &path).await?;
+
+    if !workspace.role.can_edit() {
+        return Err(AppError::Forbidden(format!(
+            "Viewers can't change {}",
+            workspace.name
+        )));
+    }
+
+    match state
+        .attach_to_workspace(email, workspace.id, body.kind, body.id)
+        .await
+    {
+        Ok(true) => Ok((StatusCode::OK).into_response()),
+        Ok(false) => Err(AppError::not_found("Nothing of yours to attach")),
+        // Names are unique within a workspace like they are for a user
+        Err(e) => Err(AppError::bad_request(e.to_string())),
+    }
+}
+
+// The workspace in the path as the user sees it, 404 for workspaces they aren't in
+async fn member_of(
+    state: &Repository,
+    email: String,
+    path: &PathParams,
+) -> Result<Workspace, AppError> {
+    let workspace_id = path
+        .id
+        .ok_or_else(|| AppError::bad_request("Missing workspace id"))?;
+
+    state
+        .workspace(email, workspace_id.get().into())
+        .await?
+        .ok_or_else(|| AppError::not_found("Workspace not found"))
+}
+
+async fn owner_of(
+    state: &Repository,
+    email: String,
+    path: &PathParams,
+) -> Result<Workspace, AppError> {
+    let workspace = member_of(state, email, path).await?;
+
+    match workspace.role {
+        Role::Owner => Ok(workspace),
+        _ => Err(AppError::Forbidden(
+            "Only owners manage a workspace".to_string(),
+        )),
+    }
+}
+
+// Called before an owner is demoted or removed
+async fn keep_an_owner(state: &Repository, workspace_id: i64) -> Result<(), AppError> {
+    match state.owner_count(workspace_id).await? {
+        0 | 1 => Err(AppError::bad_request(
+            "A workspace needs at least one owner",
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn now_millis() -> String {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis()
+        .to_string()
+}
@@ -1,8 +1,11 @@
+pub mod grpc;
 pub mod handlers;
 pub mod models;
+pub mod repository;
 pub mod router;
 pub mod utils;
 
 pub use handlers::*;
+pub use repository::*;
 pub use router::*;
 pub use utils::*;
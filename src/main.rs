@@ -1,19 +1,40 @@
-use std::sync::{Arc, Mutex};
+// The binary reports where it's running on stdout
+#![allow(clippy::print_stdout)]
 
-use include_dir::{Dir, include_dir};
 use miette::{Result, miette};
 use tokio::net::TcpListener;
 
-use gURL::{db::db, env::env, init_router};
-
-// For release binary
-static _TEMPLATES: Dir<'static> = include_dir!("$CARGO_MANIFEST_DIR/templates");
+use gURL::{
+    db::{db, db_path},
+    env::env,
+    init_router, schema_version,
+};
 
+// The runtime tokio::main builds is expected to start
+#[allow(clippy::unwrap_in_result)]
 #[tokio::main]
 async fn main() -> Result<()> {
-    let (db, port) = (db(true, false)?, env()?.port);
-    let global_db = Arc::new(Mutex::new(db));
-    let router = init_router(global_db);
+    println!("sqlite database file located at {}", db_path()?);
+
+    // `gURL migrate` upgrades the database without starting the server, flags are left for
+    // whatever starts the binary (the Docker image passes some along)
+    match std::env::args().nth(1).as_deref() {
+        Some("migrate") => {
+            let connection = db(true, false)?
+                .get()
+                .map_err(|e| miette!("sqlite connection could not be opened: {e}"))?;
+            let version = schema_version(&connection)?;
+            println!("sqlite schema at version {version}");
+            return Ok(());
+        }
+        Some(command) if !command.starts_with('-') => {
+            return Err(miette!("Unknown command {command}, try gURL migrate"));
+        }
+        _ => {}
+    }
+
+    let (pool, port) = (db(true, false)?, env()?.port);
+    let router = init_router(pool);
 
     let listener = TcpListener::bind(format!("0.0.0.0:{port}"))
         .await
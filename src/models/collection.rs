@@ -0,0 +1,45 @@
+use serde::{Deserialize, Serialize};
+use serde_with::skip_serializing_none;
+use validator::Validate;
+
+// Top level collections have no parent, folders are collections nested in another one
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct Collection {
+    pub id: Option<i32>,
+    #[validate(email)]
+    pub user_email: Option<String>,
+    pub parent_id: Option<i32>,
+    #[validate(length(min = 1))]
+    pub name: String,
+    pub position: i32,
+    #[validate(length(min = 1))]
+    pub date: String,
+    #[serde(default)]
+    pub folders: Vec<Collection>,
+    #[serde(default)]
+    pub requests: Vec<SavedRequest>,
+}
+
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct SavedRequest {
+    pub id: Option<i32>,
+    pub collection_id: i32,
+    #[validate(length(min = 1))]
+    pub title: String,
+    pub description: Option<String>,
+    #[validate(length(min = 1))]
+    pub command: String,
+    pub position: i32,
+    #[validate(length(min = 1))]
+    pub date: String,
+}
+
+// What a reorder moves, collections and folders or the saved requests of a collection
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OrderKind {
+    Collection,
+    Request,
+}
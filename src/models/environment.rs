@@ -0,0 +1,32 @@
+use serde::{Deserialize, Serialize};
+use serde_with::skip_serializing_none;
+use validator::Validate;
+
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct Environment {
+    pub id: Option<i32>,
+    #[validate(email)]
+    pub user_email: Option<String>,
+    #[validate(length(min = 1))]
+    pub name: String,
+    // Commands are resolved against the active environment, at most one per user
+    pub active: bool,
+    #[validate(length(min = 1))]
+    pub date: String,
+    #[serde(default)]
+    pub variables: Vec<Variable>,
+}
+
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct Variable {
+    pub id: Option<i32>,
+    pub environment_id: Option<i32>,
+    #[validate(length(min = 1))]
+    pub key: String,
+    // Encrypted in the db when secret, and only ever sent back masked
+    pub value: String,
+    #[serde(default)]
+    pub secret: bool,
+}
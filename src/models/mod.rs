@@ -1,26 +1,58 @@
+use std::io::{Read, Write};
+
+use flate2::{Compression, read::DeflateDecoder, write::DeflateEncoder};
+use miette::{Result, miette};
+
+pub mod collection;
+pub mod environment;
+pub mod proto;
 pub mod request;
+pub mod token;
 pub mod user;
+pub mod workspace;
+
+pub fn serialize_pairs_for_db(pairs: &[(String, String)]) -> String {
+    serde_json::to_string(pairs).unwrap_or_default()
+}
+
+pub fn deserialize_pairs_from_db(pairs: Option<String>) -> Vec<(String, String)> {
+    pairs
+        .and_then(|pairs| serde_json::from_str(&pairs).ok())
+        .unwrap_or_default()
+}
+
+// Bound as text like every other column, an empty string is stored as NULL
+pub fn serialize_millis_for_db(millis: Option<f64>) -> String {
+    millis.map(|millis| millis.to_string()).unwrap_or_default()
+}
 
-pub fn serialize_favorites_for_db(favorites: &Option<Vec<i32>>) -> String {
-    match favorites {
-        Some(favorites) => {
-            let csv = favorites
-                .iter()
-                .map(|n| n.to_string())
-                .collect::<Vec<_>>()
-                .join(",");
-            csv
-        }
-        None => String::new(),
+// Deflates the response body after cutting it down to max_size bytes, also returns whether
+// it had to be cut
+pub fn compress_response_for_db(response: &str, max_size: usize) -> Result<(Vec<u8>, bool)> {
+    let mut end = response.len().min(max_size);
+    while !response.is_char_boundary(end) {
+        end -= 1;
     }
+
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+    encoder
+        .write_all(&response.as_bytes()[..end])
+        .map_err(|e| miette!("Could not compress response: {e}"))?;
+    let compressed = encoder
+        .finish()
+        .map_err(|e| miette!("Could not compress response: {e}"))?;
+
+    Ok((compressed, end < response.len()))
 }
 
-pub fn deserialize_favorites_from_db(favorites_str: Option<String>) -> Option<Vec<i32>> {
-    favorites_str.filter(|s| !s.is_empty()).map(|s| {
-        s.split(',')
-            .filter_map(|n| n.trim().parse::<i32>().ok())
-            .collect::<Vec<i32>>()
-    })
+pub fn decompress_response_from_db(response: &[u8]) -> Result<String> {
+    let mut decompressed = String::new();
+
+    DeflateDecoder::new(response)
+        .read_to_string(&mut decompressed)
+        .map_err(|e| miette!("Could not decompress stored response: {e}"))?;
+
+    Ok(decompressed)
 }
 
 pub fn serialize_bool_for_db(value: bool) -> u8 {
@@ -31,9 +63,5 @@ pub fn serialize_bool_for_db(value: bool) -> u8 {
 }
 
 pub fn deserialize_bool_from_db(value: u8) -> bool {
-    match value {
-        1 => true,
-        0 => false,
-        _ => false,
-    }
+    value == 1
 }
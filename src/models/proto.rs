@@ -0,0 +1,21 @@
+use serde::{Deserialize, Serialize};
+use serde_with::skip_serializing_none;
+use validator::Validate;
+
+// An uploaded .proto file or protoset, the content stays in the db and is only read when a
+// command against its target runs
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct ProtoFile {
+    pub id: Option<i32>,
+    #[validate(email)]
+    pub user_email: Option<String>,
+    #[validate(length(min = 1))]
+    pub target: String,
+    #[validate(length(min = 1))]
+    pub name: String,
+    pub kind: String,
+    pub size: Option<i64>,
+    #[validate(length(min = 1))]
+    pub date: String,
+}
@@ -15,4 +15,21 @@ pub struct Request {
     #[validate(length(min = 1))]
     pub date: String,
     pub hidden: bool,
+    pub status_message: Option<String>,
+    #[serde(default)]
+    pub status_details: Vec<(String, String)>,
+    #[serde(default)]
+    pub headers: Vec<(String, String)>,
+    #[serde(default)]
+    pub trailers: Vec<(String, String)>,
+    pub total_ms: Option<f64>,
+    pub connect_ms: Option<f64>,
+    pub first_byte_ms: Option<f64>,
+    // Only filled in when a single request is fetched with its response
+    pub response: Option<String>,
+    pub response_size: Option<i64>,
+    #[serde(default)]
+    pub response_truncated: bool,
+    // The command as sent, when the saved one has {{variables}} in it
+    pub resolved_command: Option<String>,
 }
@@ -0,0 +1,51 @@
+use serde::{Deserialize, Serialize};
+use serde_with::skip_serializing_none;
+use validator::Validate;
+
+// What a personal token may be used for, anything else it's sent to is refused
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "kebab-case")]
+pub enum Scope {
+    ReadHistory,
+    Execute,
+    ManageCollections,
+}
+
+impl Scope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::ReadHistory => "read-history",
+            Scope::Execute => "execute",
+            Scope::ManageCollections => "manage-collections",
+        }
+    }
+}
+
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ApiToken {
+    pub id: i64,
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    // Seconds since the epoch, never when unset
+    pub expires: Option<u64>,
+    pub last_used: Option<u64>,
+    pub revoked: bool,
+    pub date: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct NewApiToken {
+    #[validate(length(min = 1))]
+    pub name: String,
+    #[validate(length(min = 1))]
+    pub scopes: Vec<Scope>,
+    pub expires_in_days: Option<u64>,
+}
+
+// The token itself is only ever shown here, the db keeps its hash
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CreatedApiToken {
+    pub token: String,
+    pub api_token: ApiToken,
+}
@@ -11,8 +11,26 @@ pub struct User {
     pub email: String,
     #[validate(length(min = 1))]
     pub password: String,
-    pub favorites: Option<Vec<i32>>,
     #[validate(length(min = 1))]
     pub date: Option<String>,
     pub deleted: bool,
 }
+
+// What login, signup and refresh answer with, the tokens themselves only travel in cookies
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Profile {
+    pub username: String,
+    pub email: String,
+    pub date: Option<String>,
+}
+
+impl From<User> for Profile {
+    fn from(user: User) -> Self {
+        Profile {
+            username: user.username,
+            email: user.email,
+            date: user.date,
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+// Viewers read, editors also change the workspace's data, owners also manage its members
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Owner,
+    Editor,
+    Viewer,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Owner => "owner",
+            Role::Editor => "editor",
+            Role::Viewer => "viewer",
+        }
+    }
+
+    pub fn parse(role: &str) -> Option<Role> {
+        match role {
+            "owner" => Some(Role::Owner),
+            "editor" => Some(Role::Editor),
+            "viewer" => Some(Role::Viewer),
+            _ => None,
+        }
+    }
+
+    pub fn can_edit(&self) -> bool {
+        *self != Role::Viewer
+    }
+}
+
+// A workspace as one of its members sees it
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Workspace {
+    pub id: i64,
+    pub name: String,
+    pub role: Role,
+    // What the member is working in, at most one per user and their own data when none is
+    pub active: bool,
+    pub date: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Member {
+    pub id: i64,
+    pub workspace_id: i64,
+    pub user_email: String,
+    pub username: String,
+    pub role: Role,
+    pub date: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct NewWorkspace {
+    #[validate(length(min = 1))]
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct MemberBody {
+    #[validate(email)]
+    pub email: String,
+    pub role: Role,
+}
+
+// No id switches back to the user's own data
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ActiveWorkspaceBody {
+    pub id: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AttachKind {
+    Request,
+    Collection,
+    Environment,
+    Proto,
+}
+
+// Moves one of the user's own items into a workspace, collections bring their folders and
+// saved requests along
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AttachBody {
+    pub kind: AttachKind,
+    pub id: i64,
+}
@@ -0,0 +1,412 @@
+use std::collections::HashSet;
+
+use miette::{Result, miette};
+use rusqlite::{
+    Connection, OptionalExtension, Statement, TransactionBehavior, params, params_from_iter,
+};
+
+use crate::models::collection::{Collection, OrderKind, SavedRequest};
+
+// Every collection of the user nested by parent, with its saved requests, in their set order
+pub fn find_collections(db: &mut Connection, email: String) -> Result<Vec<Collection>> {
+    let collections = map_collections(
+        db.prepare("SELECT * FROM collection WHERE user_email = ?1 ORDER BY position, id")
+            .map_err(|e| miette!("Invalid statement: {e}"))?,
+        std::slice::from_ref(&email),
+    )?;
+    let requests = map_saved_requests(
+        db.prepare(
+            r#"
+            SELECT saved_request.* FROM saved_request
+            JOIN collection ON collection.id = saved_request.collection_id
+            WHERE collection.user_email = ?1
+            ORDER BY saved_request.position, saved_request.id
+        "#,
+        )
+        .map_err(|e| miette!("Invalid statement: {e}"))?,
+        &[email],
+    )?;
+
+    Ok(nest_collections(None, &collections, &requests))
+}
+
+// Some(parent) when the user owns the collection, the parent itself is None at the top level
+pub fn find_collection_parent(
+    db: &Connection,
+    email: String,
+    id: i32,
+) -> Result<Option<Option<i32>>> {
+    db.query_row(
+        "SELECT parent_id FROM collection WHERE user_email = ?1 AND id = ?2",
+        params![email, id],
+        |row| row.get::<_, Option<i32>>(0),
+    )
+    .optional()
+    .map_err(|e| miette!("Could not read collection: {e}"))
+}
+
+// The collection holding the user's saved request, None if they have no such request
+pub fn find_saved_request_collection(
+    db: &mut Connection,
+    email: String,
+    id: i32,
+) -> Result<Option<i32>> {
+    db.query_row(
+        r#"
+        SELECT saved_request.collection_id FROM saved_request
+        JOIN collection ON collection.id = saved_request.collection_id
+        WHERE collection.user_email = ?1 AND saved_request.id = ?2
+    "#,
+        params![email, id],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(|e| miette!("Could not read saved request: {e}"))
+}
+
+// Whether id is the collection itself or one of the folders under it
+pub fn collection_tree_contains(db: &Connection, collection_id: i32, id: i32) -> Result<bool> {
+    db.query_row(
+        r#"
+        WITH RECURSIVE tree(id) AS (
+            SELECT id FROM collection WHERE id = ?1
+            UNION ALL
+            SELECT collection.id FROM collection JOIN tree ON collection.parent_id = tree.id
+        )
+        SELECT EXISTS (SELECT 1 FROM tree WHERE id = ?2)
+    "#,
+        params![collection_id, id],
+        |row| row.get::<_, bool>(0),
+    )
+    .map_err(|e| miette!("Could not read collection tree: {e}"))
+}
+
+// New collections and folders go after their siblings
+pub fn insert_collection(
+    db: &mut Connection,
+    email: String,
+    parent_id: Option<i32>,
+    name: String,
+    date: String,
+) -> Result<Option<Collection>> {
+    let position = next_collection_position(db, &email, parent_id)?;
+
+    Ok(map_collections(
+        db.prepare(
+            r#"
+            INSERT INTO collection (user_email, parent_id, name, position, date)
+            VALUES (?1, CAST(NULLIF(?2, '') AS INTEGER), ?3, ?4, ?5) RETURNING *
+        "#,
+        )
+        .map_err(|e| miette!("Invalid statement: {e}"))?,
+        &[
+            email,
+            optional_id(parent_id),
+            name,
+            position.to_string(),
+            date,
+        ],
+    )?
+    .pop())
+}
+
+// Renames a collection, or moves it (to the end of its new parent) when the parent changes
+pub fn save_collection(
+    db: &mut Connection,
+    email: String,
+    id: i32,
+    name: String,
+    parent_id: Option<i32>,
+) -> Result<Option<Collection>> {
+    let Some(current_parent) = find_collection_parent(db, email.clone(), id)? else {
+        return Ok(None);
+    };
+    let position = match current_parent == parent_id {
+        true => None,
+        false => Some(next_collection_position(db, &email, parent_id)?),
+    };
+
+    Ok(map_collections(
+        db.prepare(
+            r#"
+            UPDATE collection SET
+                name = ?3,
+                parent_id = CAST(NULLIF(?4, '') AS INTEGER),
+                position = COALESCE(CAST(NULLIF(?5, '') AS INTEGER), position)
+            WHERE user_email = ?1 AND id = ?2 RETURNING *
+        "#,
+        )
+        .map_err(|e| miette!("Invalid statement: {e}"))?,
+        &[
+            email,
+            id.to_string(),
+            name,
+            optional_id(parent_id),
+            optional_id(position),
+        ],
+    )?
+    .pop())
+}
+
+// Folders and saved requests under the collection go with it. Returns whether the user had a
+// collection with that id
+pub fn remove_collection(db: &mut Connection, email: String, id: i32) -> Result<bool> {
+    db.execute(
+        "DELETE FROM collection WHERE user_email = ?1 AND id = ?2",
+        params![email, id],
+    )
+    .map(|deleted| deleted > 0)
+    .map_err(|e| miette!("Could not delete collection: {e}"))
+}
+
+pub fn insert_saved_request(
+    db: &mut Connection,
+    collection_id: i32,
+    title: String,
+    description: Option<String>,
+    command: String,
+    date: String,
+) -> Result<Option<SavedRequest>> {
+    let position = next_saved_request_position(db, collection_id)?;
+
+    Ok(map_saved_requests(
+        db.prepare(
+            r#"
+            INSERT INTO saved_request (collection_id, title, description, command, position, date)
+            VALUES (?1, ?2, NULLIF(?3, ''), ?4, ?5, ?6) RETURNING *
+        "#,
+        )
+        .map_err(|e| miette!("Invalid statement: {e}"))?,
+        &[
+            collection_id.to_string(),
+            title,
+            description.unwrap_or_default(),
+            command,
+            position.to_string(),
+            date,
+        ],
+    )?
+    .pop())
+}
+
+// A saved request moved to another collection goes after the requests already there
+pub fn save_saved_request(
+    db: &mut Connection,
+    email: String,
+    id: i32,
+    collection_id: i32,
+    title: String,
+    description: Option<String>,
+    command: String,
+) -> Result<Option<SavedRequest>> {
+    let Some(current_collection) = find_saved_request_collection(db, email, id)? else {
+        return Ok(None);
+    };
+    let position = match current_collection == collection_id {
+        true => None,
+        false => Some(next_saved_request_position(db, collection_id)?),
+    };
+
+    Ok(map_saved_requests(
+        db.prepare(
+            r#"
+            UPDATE saved_request SET
+                collection_id = ?2,
+                title = ?3,
+                description = NULLIF(?4, ''),
+                command = ?5,
+                position = COALESCE(CAST(NULLIF(?6, '') AS INTEGER), position)
+            WHERE id = ?1 RETURNING *
+        "#,
+        )
+        .map_err(|e| miette!("Invalid statement: {e}"))?,
+        &[
+            id.to_string(),
+            collection_id.to_string(),
+            title,
+            description.unwrap_or_default(),
+            command,
+            optional_id(position),
+        ],
+    )?
+    .pop())
+}
+
+// Returns whether the user had a saved request with that id
+pub fn remove_saved_request(db: &mut Connection, email: String, id: i32) -> Result<bool> {
+    db.execute(
+        r#"
+        DELETE FROM saved_request WHERE id = ?2 AND collection_id IN (
+            SELECT id FROM collection WHERE user_email = ?1
+        )
+    "#,
+        params![email, id],
+    )
+    .map(|deleted| deleted > 0)
+    .map_err(|e| miette!("Could not delete saved request: {e}"))
+}
+
+// Puts the user's items under the parent in the given order, moving in the ones that were
+// elsewhere. Everything is checked in the same transaction as the writes: the parent and every id
+// belong to the user, the ids hold each item already under the parent once and no collection goes
+// inside itself. Nothing changes and false comes back when one of them fails
+pub fn set_collection_order(
+    db: &mut Connection,
+    email: String,
+    kind: OrderKind,
+    parent_id: Option<i32>,
+    ids: Vec<i32>,
+) -> Result<bool> {
+    let (children, update) = match kind {
+        OrderKind::Collection => (
+            "SELECT id FROM collection WHERE user_email = ?1 AND parent_id IS ?2",
+            r#"
+            UPDATE collection SET parent_id = ?1, position = ?2
+            WHERE id = ?3 AND user_email = ?4
+        "#,
+        ),
+        OrderKind::Request => (
+            r#"
+            SELECT saved_request.id FROM saved_request
+            JOIN collection ON collection.id = saved_request.collection_id
+            WHERE collection.user_email = ?1 AND saved_request.collection_id IS ?2
+        "#,
+            r#"
+            UPDATE saved_request SET collection_id = ?1, position = ?2
+            WHERE id = ?3 AND collection_id IN (SELECT id FROM collection WHERE user_email = ?4)
+        "#,
+        ),
+    };
+
+    let transaction = db
+        .transaction_with_behavior(TransactionBehavior::Immediate)
+        .map_err(|e| miette!("Could not start transaction: {e}"))?;
+
+    if let Some(parent_id) = parent_id
+        && find_collection_parent(&transaction, email.clone(), parent_id)?.is_none()
+    {
+        return Ok(false);
+    }
+
+    let current = transaction
+        .prepare(children)
+        .map_err(|e| miette!("Invalid statement: {e}"))?
+        .query_map(params![email, parent_id], |row| row.get::<_, i32>(0))
+        .map_err(|e| miette!("Could not read collection order: {e}"))?
+        .collect::<Result<HashSet<_>, _>>()
+        .map_err(|e| miette!("Could not read collection order: {e}"))?;
+    let listed = ids.iter().copied().collect::<HashSet<_>>();
+    if listed.len() != ids.len() || !current.is_subset(&listed) {
+        return Ok(false);
+    }
+
+    for (position, id) in ids.iter().enumerate() {
+        if kind == OrderKind::Collection
+            && let Some(parent_id) = parent_id
+            && collection_tree_contains(&transaction, *id, parent_id)?
+        {
+            return Ok(false);
+        }
+
+        let updated = transaction
+            .execute(update, params![parent_id, position as i64, id, email])
+            .map_err(|e| miette!("Could not reorder collection: {e}"))?;
+        if updated != 1 {
+            return Ok(false);
+        }
+    }
+    transaction
+        .commit()
+        .map_err(|e| miette!("Could not reorder collection: {e}"))?;
+
+    Ok(true)
+}
+
+fn next_collection_position(db: &Connection, email: &str, parent_id: Option<i32>) -> Result<i32> {
+    db.query_row(
+        r#"
+        SELECT COALESCE(MAX(position) + 1, 0) FROM collection
+        WHERE user_email = ?1 AND parent_id IS ?2
+    "#,
+        params![email, parent_id],
+        |row| row.get(0),
+    )
+    .map_err(|e| miette!("Could not read collection positions: {e}"))
+}
+
+fn next_saved_request_position(db: &Connection, collection_id: i32) -> Result<i32> {
+    db.query_row(
+        "SELECT COALESCE(MAX(position) + 1, 0) FROM saved_request WHERE collection_id = ?1",
+        params![collection_id],
+        |row| row.get(0),
+    )
+    .map_err(|e| miette!("Could not read saved request positions: {e}"))
+}
+
+fn optional_id(id: Option<i32>) -> String {
+    id.map(|id| id.to_string()).unwrap_or_default()
+}
+
+fn nest_collections(
+    parent_id: Option<i32>,
+    collections: &[Collection],
+    requests: &[SavedRequest],
+) -> Vec<Collection> {
+    collections
+        .iter()
+        .filter(|collection| collection.parent_id == parent_id)
+        .map(|collection| Collection {
+            folders: nest_collections(collection.id, collections, requests),
+            requests: requests
+                .iter()
+                .filter(|request| Some(request.collection_id) == collection.id)
+                .cloned()
+                .collect(),
+            ..collection.clone()
+        })
+        .collect()
+}
+
+pub fn map_collections(mut statement: Statement<'_>, args: &[String]) -> Result<Vec<Collection>> {
+    let parsed_rows = statement
+        .query_map(params_from_iter(args), |row| {
+            Ok(Collection {
+                id: row.get(0)?,
+                user_email: row.get::<_, Option<String>>(1)?,
+                parent_id: row.get(2)?,
+                name: row.get(3)?,
+                position: row.get(4)?,
+                date: row.get(5)?,
+                folders: Vec::new(),
+                requests: Vec::new(),
+            })
+        })
+        .map_err(|e| miette!("Error mapping rows to Collection: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| miette!("Could not read Collection row: {e}"))?;
+
+    Ok(parsed_rows)
+}
+
+pub fn map_saved_requests(
+    mut statement: Statement<'_>,
+    args: &[String],
+) -> Result<Vec<SavedRequest>> {
+    let parsed_rows = statement
+        .query_map(params_from_iter(args), |row| {
+            Ok(SavedRequest {
+                id: row.get(0)?,
+                collection_id: row.get(1)?,
+                title: row.get(2)?,
+                description: row.get(3)?,
+                command: row.get(4)?,
+                position: row.get(5)?,
+                date: row.get(6)?,
+            })
+        })
+        .map_err(|e| miette!("Error mapping rows to SavedRequest: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| miette!("Could not read SavedRequest row: {e}"))?;
+
+    Ok(parsed_rows)
+}
@@ -0,0 +1,197 @@
+use miette::{Result, miette};
+use rusqlite::{Connection, OptionalExtension, Statement, params, params_from_iter};
+
+use crate::models::{
+    deserialize_bool_from_db,
+    environment::{Environment, Variable},
+    serialize_bool_for_db,
+};
+
+// Every environment of the user with their variables, values as stored
+pub fn find_environments(db: &mut Connection, email: String) -> Result<Vec<Environment>> {
+    let mut environments = map_environments(
+        db.prepare("SELECT * FROM environment WHERE user_email = ?1 ORDER BY name")
+            .map_err(|e| miette!("Invalid statement: {e}"))?,
+        std::slice::from_ref(&email),
+    )?;
+    let variables = map_variables(
+        db.prepare(
+            r#"
+            SELECT environment_variable.* FROM environment_variable
+            JOIN environment ON environment.id = environment_variable.environment_id
+            WHERE environment.user_email = ?1 ORDER BY environment_variable.key
+        "#,
+        )
+        .map_err(|e| miette!("Invalid statement: {e}"))?,
+        &[email],
+    )?;
+
+    for environment in environments.iter_mut() {
+        environment.variables = variables
+            .iter()
+            .filter(|variable| variable.environment_id == environment.id)
+            .cloned()
+            .collect();
+    }
+
+    Ok(environments)
+}
+
+// Variables of the active environment as stored, empty when none is active
+pub fn find_active_variables(db: &mut Connection, email: String) -> Result<Vec<Variable>> {
+    map_variables(
+        db.prepare(
+            r#"
+            SELECT environment_variable.* FROM environment_variable
+            JOIN environment ON environment.id = environment_variable.environment_id
+            WHERE environment.user_email = ?1 AND environment.active = 1
+        "#,
+        )
+        .map_err(|e| miette!("Invalid statement: {e}"))?,
+        &[email],
+    )
+}
+
+pub fn find_environment(db: &mut Connection, email: String, name: String) -> Result<Option<i32>> {
+    db.query_row(
+        "SELECT id FROM environment WHERE user_email = ?1 AND name = ?2",
+        params![email, name],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(|e| miette!("Could not read environment: {e}"))
+}
+
+// The named environment, created inactive if it's new
+pub fn ensure_environment(
+    db: &mut Connection,
+    email: String,
+    name: String,
+    date: String,
+) -> Result<Option<Environment>> {
+    db.execute(
+        r#"
+        INSERT INTO environment (user_email, name, active, date) VALUES (?1, ?2, 0, ?3)
+        ON CONFLICT (user_email, name) DO NOTHING
+    "#,
+        params![email, name, date],
+    )
+    .map_err(|e| miette!("Could not create environment: {e}"))?;
+
+    Ok(map_environments(
+        db.prepare("SELECT * FROM environment WHERE user_email = ?1 AND name = ?2")
+            .map_err(|e| miette!("Invalid statement: {e}"))?,
+        &[email, name],
+    )?
+    .pop())
+}
+
+// Setting a key that already exists in the environment replaces its value, the environment is
+// created when it doesn't exist yet
+pub fn upsert_variable(
+    db: &mut Connection,
+    email: String,
+    environment: String,
+    key: String,
+    value: String,
+    secret: bool,
+    date: String,
+) -> Result<Option<Variable>> {
+    let Some(environment_id) =
+        ensure_environment(db, email, environment, date)?.and_then(|environment| environment.id)
+    else {
+        return Ok(None);
+    };
+
+    Ok(map_variables(
+        db.prepare(
+            r#"
+            INSERT INTO environment_variable (environment_id, key, value, secret)
+            VALUES (?1, ?2, ?3, ?4)
+            ON CONFLICT (environment_id, key)
+            DO UPDATE SET value = excluded.value, secret = excluded.secret
+            RETURNING *
+        "#,
+        )
+        .map_err(|e| miette!("Invalid statement: {e}"))?,
+        &[
+            environment_id.to_string(),
+            key,
+            value,
+            serialize_bool_for_db(secret).to_string(),
+        ],
+    )?
+    .pop())
+}
+
+// Returns whether the user had a variable with that id
+pub fn remove_variable(db: &mut Connection, email: String, id: i32) -> Result<bool> {
+    db.execute(
+        r#"
+        DELETE FROM environment_variable WHERE id = ?2 AND environment_id IN (
+            SELECT id FROM environment WHERE user_email = ?1
+        )
+    "#,
+        params![email, id],
+    )
+    .map(|deleted| deleted > 0)
+    .map_err(|e| miette!("Could not delete variable: {e}"))
+}
+
+// Its variables go with it. Returns whether the user had an environment with that id
+pub fn remove_environment(db: &mut Connection, email: String, id: i32) -> Result<bool> {
+    db.execute(
+        "DELETE FROM environment WHERE user_email = ?1 AND id = ?2",
+        params![email, id],
+    )
+    .map(|deleted| deleted > 0)
+    .map_err(|e| miette!("Could not delete environment: {e}"))
+}
+
+// Makes the named environment the only active one, an empty name turns variables off
+pub fn set_active_environment(db: &mut Connection, email: String, name: String) -> Result<()> {
+    db.execute(
+        "UPDATE environment SET active = (name = ?2) WHERE user_email = ?1",
+        params![email, name],
+    )
+    .map_err(|e| miette!("Could not switch environment: {e}"))?;
+
+    Ok(())
+}
+
+pub fn map_environments(mut statement: Statement<'_>, args: &[String]) -> Result<Vec<Environment>> {
+    let parsed_rows = statement
+        .query_map(params_from_iter(args), |row| {
+            Ok(Environment {
+                id: row.get(0)?,
+                user_email: row.get::<_, Option<String>>(1)?,
+                name: row.get(2)?,
+                active: deserialize_bool_from_db(row.get(3)?),
+                date: row.get(4)?,
+                variables: Vec::new(),
+            })
+        })
+        .map_err(|e| miette!("Error mapping rows to Environment: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| miette!("Could not read Environment row: {e}"))?;
+
+    Ok(parsed_rows)
+}
+
+pub fn map_variables(mut statement: Statement<'_>, args: &[String]) -> Result<Vec<Variable>> {
+    let parsed_rows = statement
+        .query_map(params_from_iter(args), |row| {
+            Ok(Variable {
+                id: row.get(0)?,
+                environment_id: row.get(1)?,
+                key: row.get(2)?,
+                value: row.get(3)?,
+                secret: deserialize_bool_from_db(row.get::<_, Option<u8>>(4)?.unwrap_or_default()),
+            })
+        })
+        .map_err(|e| miette!("Error mapping rows to Variable: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| miette!("Could not read Variable row: {e}"))?;
+
+    Ok(parsed_rows)
+}
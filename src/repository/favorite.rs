@@ -0,0 +1,59 @@
+use miette::{Result, miette};
+use rusqlite::{Connection, OptionalExtension, params};
+
+// Ids of the requests the user marked as favorite
+pub fn find_favorite_ids(db: &mut Connection, email: String) -> Result<Vec<i32>> {
+    db.prepare("SELECT request_id FROM favorite WHERE user_email = ?1")
+        .map_err(|e| miette!("Invalid statement: {e}"))?
+        .query_map([email], |row| row.get::<_, i32>(0))
+        .map_err(|e| miette!("Could not query favorites: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| miette!("Could not read favorites: {e}"))
+}
+
+// Adding is a single statement, so two tabs favoriting at once can't drop each other's
+// favorites. Returns whether the request is a favorite now, false if the user has no such
+// request
+pub fn insert_favorite(
+    db: &mut Connection,
+    email: String,
+    request_id: i32,
+    date: String,
+) -> Result<bool> {
+    let added = db
+        .execute(
+            r#"
+            INSERT INTO favorite (user_email, request_id, position, date)
+            SELECT ?1, id, (
+                SELECT COALESCE(MAX(position) + 1, 0) FROM favorite WHERE user_email = ?1
+            ), ?3
+            FROM request WHERE id = ?2 AND user_email = ?1 AND hidden = false
+            ON CONFLICT (user_email, request_id) DO NOTHING
+        "#,
+            params![email, request_id, date],
+        )
+        .map_err(|e| miette!("Could not add favorite: {e}"))?;
+
+    if added > 0 {
+        return Ok(true);
+    }
+
+    db.query_row(
+        "SELECT id FROM favorite WHERE user_email = ?1 AND request_id = ?2",
+        params![email, request_id],
+        |row| row.get::<_, i32>(0),
+    )
+    .optional()
+    .map(|favorite| favorite.is_some())
+    .map_err(|e| miette!("Could not query favorite: {e}"))
+}
+
+// Returns whether the request was a favorite
+pub fn delete_favorite(db: &mut Connection, email: String, request_id: i32) -> Result<bool> {
+    db.execute(
+        "DELETE FROM favorite WHERE user_email = ?1 AND request_id = ?2",
+        params![email, request_id],
+    )
+    .map(|removed| removed > 0)
+    .map_err(|e| miette!("Could not remove favorite: {e}"))
+}
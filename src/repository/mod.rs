@@ -0,0 +1,456 @@
+use std::num::NonZeroI32;
+
+use miette::{Report, Result, miette};
+use rusqlite::Connection;
+
+use crate::{
+    models::{
+        collection::{Collection, OrderKind, SavedRequest},
+        environment::{Environment, Variable},
+        proto::ProtoFile,
+        request::Request,
+        token::{ApiToken, Scope},
+        user::User,
+        workspace::{AttachKind, Member, Role, Workspace},
+    },
+    utils::DbPool,
+};
+
+mod collection;
+mod environment;
+mod favorite;
+mod proto;
+mod request;
+mod session;
+mod token;
+mod user;
+mod workspace;
+
+pub use collection::*;
+pub use environment::*;
+pub use favorite::*;
+pub use proto::*;
+pub use request::*;
+pub use session::*;
+pub use token::*;
+pub use user::*;
+pub use workspace::*;
+
+// How handlers reach the database: the work runs on a pooled connection in tokio's blocking
+// threads, so a slow query never stalls the async workers and no connection is held across an
+// await
+#[derive(Debug, Clone)]
+pub struct Repository {
+    pool: DbPool,
+}
+
+impl Repository {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+
+    // Handlers can fail the work with their own error type, anything that converts from a
+    // miette report
+    pub async fn run<T, E, F>(&self, work: F) -> Result<T, E>
+    where
+        F: FnOnce(&mut Connection) -> Result<T, E> + Send + 'static,
+        T: Send + 'static,
+        E: From<Report> + Send + 'static,
+    {
+        let pool = self.pool.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let mut connection = pool
+                .get()
+                .map_err(|e| E::from(miette!("Could not get a db connection: {e}")))?;
+
+            work(&mut connection)
+        })
+        .await
+        .map_err(|e| E::from(miette!("Db task didn't finish: {e}")))?
+    }
+
+    pub async fn requests(&self, email: String) -> Result<Vec<Request>> {
+        self.run(move |db| find_requests(db, email)).await
+    }
+
+    pub async fn favorite_requests(&self, email: String) -> Result<Vec<Request>> {
+        self.run(move |db| find_favorite_requests(db, email)).await
+    }
+
+    pub async fn request(&self, email: String, id: NonZeroI32) -> Result<Option<Request>> {
+        self.run(move |db| find_request(db, email, id)).await
+    }
+
+    pub async fn create_request(
+        &self,
+        email: String,
+        request: Request,
+        max_size: usize,
+    ) -> Result<Option<Request>> {
+        self.run(move |db| insert_request(db, email, request, max_size))
+            .await
+    }
+
+    pub async fn hide_request(&self, email: String, id: NonZeroI32) -> Result<bool> {
+        self.run(move |db| set_request_hidden(db, email, id)).await
+    }
+
+    pub async fn recent_commands(&self, email: String, limit: i64) -> Result<Vec<String>> {
+        self.run(move |db| find_recent_commands(db, email, limit))
+            .await
+    }
+
+    pub async fn favorite_ids(&self, email: String) -> Result<Vec<i32>> {
+        self.run(move |db| find_favorite_ids(db, email)).await
+    }
+
+    pub async fn add_favorite(&self, email: String, request_id: i32, date: String) -> Result<bool> {
+        self.run(move |db| insert_favorite(db, email, request_id, date))
+            .await
+    }
+
+    pub async fn remove_favorite(&self, email: String, request_id: i32) -> Result<bool> {
+        self.run(move |db| delete_favorite(db, email, request_id))
+            .await
+    }
+
+    pub async fn collections(&self, email: String) -> Result<Vec<Collection>> {
+        self.run(move |db| find_collections(db, email)).await
+    }
+
+    pub async fn collection_parent(&self, email: String, id: i32) -> Result<Option<Option<i32>>> {
+        self.run(move |db| find_collection_parent(db, email, id))
+            .await
+    }
+
+    pub async fn saved_request_collection(&self, email: String, id: i32) -> Result<Option<i32>> {
+        self.run(move |db| find_saved_request_collection(db, email, id))
+            .await
+    }
+
+    pub async fn collection_contains(&self, collection_id: i32, id: i32) -> Result<bool> {
+        self.run(move |db| collection_tree_contains(db, collection_id, id))
+            .await
+    }
+
+    pub async fn create_collection(
+        &self,
+        email: String,
+        parent_id: Option<i32>,
+        name: String,
+        date: String,
+    ) -> Result<Option<Collection>> {
+        self.run(move |db| insert_collection(db, email, parent_id, name, date))
+            .await
+    }
+
+    pub async fn update_collection(
+        &self,
+        email: String,
+        id: i32,
+        name: String,
+        parent_id: Option<i32>,
+    ) -> Result<Option<Collection>> {
+        self.run(move |db| save_collection(db, email, id, name, parent_id))
+            .await
+    }
+
+    pub async fn delete_collection(&self, email: String, id: i32) -> Result<bool> {
+        self.run(move |db| remove_collection(db, email, id)).await
+    }
+
+    pub async fn create_saved_request(
+        &self,
+        collection_id: i32,
+        title: String,
+        description: Option<String>,
+        command: String,
+        date: String,
+    ) -> Result<Option<SavedRequest>> {
+        self.run(move |db| {
+            insert_saved_request(db, collection_id, title, description, command, date)
+        })
+        .await
+    }
+
+    pub async fn update_saved_request(
+        &self,
+        email: String,
+        id: i32,
+        collection_id: i32,
+        title: String,
+        description: Option<String>,
+        command: String,
+    ) -> Result<Option<SavedRequest>> {
+        self.run(move |db| {
+            save_saved_request(db, email, id, collection_id, title, description, command)
+        })
+        .await
+    }
+
+    pub async fn delete_saved_request(&self, email: String, id: i32) -> Result<bool> {
+        self.run(move |db| remove_saved_request(db, email, id))
+            .await
+    }
+
+    pub async fn reorder_collection(
+        &self,
+        email: String,
+        kind: OrderKind,
+        parent_id: Option<i32>,
+        ids: Vec<i32>,
+    ) -> Result<bool> {
+        self.run(move |db| set_collection_order(db, email, kind, parent_id, ids))
+            .await
+    }
+
+    pub async fn environments(&self, email: String) -> Result<Vec<Environment>> {
+        self.run(move |db| find_environments(db, email)).await
+    }
+
+    pub async fn active_variables(&self, email: String) -> Result<Vec<Variable>> {
+        self.run(move |db| find_active_variables(db, email)).await
+    }
+
+    pub async fn environment(&self, email: String, name: String) -> Result<Option<i32>> {
+        self.run(move |db| find_environment(db, email, name)).await
+    }
+
+    pub async fn create_environment(
+        &self,
+        email: String,
+        name: String,
+        date: String,
+    ) -> Result<Option<Environment>> {
+        self.run(move |db| ensure_environment(db, email, name, date))
+            .await
+    }
+
+    pub async fn set_variable(
+        &self,
+        email: String,
+        environment: String,
+        key: String,
+        value: String,
+        secret: bool,
+        date: String,
+    ) -> Result<Option<Variable>> {
+        self.run(move |db| upsert_variable(db, email, environment, key, value, secret, date))
+            .await
+    }
+
+    pub async fn delete_variable(&self, email: String, id: i32) -> Result<bool> {
+        self.run(move |db| remove_variable(db, email, id)).await
+    }
+
+    pub async fn delete_environment(&self, email: String, id: i32) -> Result<bool> {
+        self.run(move |db| remove_environment(db, email, id)).await
+    }
+
+    pub async fn activate_environment(&self, email: String, name: String) -> Result<()> {
+        self.run(move |db| set_active_environment(db, email, name))
+            .await
+    }
+
+    pub async fn protos(&self, email: String) -> Result<Vec<ProtoFile>> {
+        self.run(move |db| find_protos(db, email)).await
+    }
+
+    pub async fn proto_build(&self, email: String, target: String) -> Result<Option<Vec<u8>>> {
+        self.run(move |db| find_proto_build(db, email, target))
+            .await
+    }
+
+    pub async fn create_proto(
+        &self,
+        email: String,
+        target: String,
+        name: String,
+        kind: String,
+        content: Vec<u8>,
+        date: String,
+    ) -> Result<Result<Option<ProtoFile>, String>> {
+        self.run(move |db| upsert_proto(db, email, target, name, kind, content, date))
+            .await
+    }
+
+    pub async fn delete_proto(&self, email: String, id: i32) -> Result<Result<bool, String>> {
+        self.run(move |db| remove_proto(db, email, id)).await
+    }
+
+    pub async fn user(&self, email: String) -> Result<Option<User>> {
+        self.run(move |db| find_user(db, email)).await
+    }
+
+    pub async fn create_user(&self, user: User) -> Result<()> {
+        self.run(move |db| insert_user(db, user)).await
+    }
+
+    // A password change or a deleted account logs every session out and revokes every API token,
+    // all in one transaction so a failure leaves the old credentials as they were
+    pub async fn update_user(&self, user: User) -> Result<()> {
+        self.run(move |db| {
+            let transaction = db
+                .transaction()
+                .map_err(|e| miette!("Could not start transaction: {e}"))?;
+            let email = user.email.clone();
+            save_user(&transaction, user)?;
+            revoke_user_api_tokens(&transaction, email.clone())?;
+            revoke_user_sessions(&transaction, email)?;
+            transaction
+                .commit()
+                .map_err(|e| miette!("Could not update user: {e}"))
+        })
+        .await
+    }
+
+    pub async fn update_password(&self, email: String, password: String) -> Result<()> {
+        self.run(move |db| save_password(db, email, password)).await
+    }
+
+    pub async fn delete_user(&self, email: String) -> Result<()> {
+        self.run(move |db| {
+            let transaction = db
+                .transaction()
+                .map_err(|e| miette!("Could not start transaction: {e}"))?;
+            mark_user_deleted(&transaction, email.clone())?;
+            leave_workspaces(&transaction, email.clone())?;
+            revoke_user_api_tokens(&transaction, email.clone())?;
+            revoke_user_sessions(&transaction, email)?;
+            transaction
+                .commit()
+                .map_err(|e| miette!("Could not delete user: {e}"))
+        })
+        .await
+    }
+
+    pub async fn create_session(
+        &self,
+        email: String,
+        token_hash: String,
+        expires: u64,
+        date: String,
+    ) -> Result<i64> {
+        self.run(move |db| insert_session(db, email, token_hash, expires, date))
+            .await
+    }
+
+    pub async fn rotate_session(
+        &self,
+        token_hash: String,
+        new_token_hash: String,
+        expires: u64,
+        now: u64,
+    ) -> Result<Option<(i64, String)>> {
+        self.run(move |db| rotate_session(db, token_hash, new_token_hash, expires, now))
+            .await
+    }
+
+    pub async fn session_is_active(&self, id: i64, now: u64) -> Result<bool> {
+        self.run(move |db| session_is_active(db, id, now)).await
+    }
+
+    pub async fn revoke_session(&self, token_hash: String) -> Result<()> {
+        self.run(move |db| revoke_session(db, token_hash)).await
+    }
+
+    pub async fn create_api_token(
+        &self,
+        email: String,
+        name: String,
+        token_hash: String,
+        scopes: Vec<Scope>,
+        expires: Option<u64>,
+        date: String,
+    ) -> Result<ApiToken> {
+        self.run(move |db| insert_api_token(db, email, name, token_hash, scopes, expires, date))
+            .await
+    }
+
+    pub async fn api_tokens(&self, email: String) -> Result<Vec<ApiToken>> {
+        self.run(move |db| find_api_tokens(db, email)).await
+    }
+
+    pub async fn use_api_token(
+        &self,
+        token_hash: String,
+        now: u64,
+    ) -> Result<Option<(String, Vec<Scope>)>> {
+        self.run(move |db| use_api_token(db, token_hash, now)).await
+    }
+
+    pub async fn revoke_api_token(&self, email: String, id: i64) -> Result<bool> {
+        self.run(move |db| mark_api_token_revoked(db, email, id))
+            .await
+    }
+
+    pub async fn create_workspace(
+        &self,
+        email: String,
+        name: String,
+        date: String,
+    ) -> Result<Workspace> {
+        self.run(move |db| insert_workspace(db, email, name, date))
+            .await
+    }
+
+    pub async fn workspaces(&self, email: String) -> Result<Vec<Workspace>> {
+        self.run(move |db| find_workspaces(db, email)).await
+    }
+
+    pub async fn workspace(&self, email: String, id: i64) -> Result<Option<Workspace>> {
+        self.run(move |db| find_workspace(db, email, id)).await
+    }
+
+    pub async fn active_workspace(&self, email: String) -> Result<Option<Workspace>> {
+        self.run(move |db| find_active_workspace(db, email)).await
+    }
+
+    pub async fn switch_workspace(&self, email: String, id: Option<i64>) -> Result<bool> {
+        self.run(move |db| set_active_workspace(db, email, id))
+            .await
+    }
+
+    pub async fn members(&self, workspace_id: i64) -> Result<Vec<Member>> {
+        self.run(move |db| find_members(db, workspace_id)).await
+    }
+
+    pub async fn member(&self, member_id: i64) -> Result<Option<Member>> {
+        self.run(move |db| find_member(db, member_id)).await
+    }
+
+    pub async fn set_member(
+        &self,
+        workspace_id: i64,
+        email: String,
+        role: Role,
+        date: String,
+    ) -> Result<Member> {
+        self.run(move |db| upsert_member(db, workspace_id, email, role, date))
+            .await
+    }
+
+    pub async fn remove_member(&self, member_id: i64) -> Result<()> {
+        self.run(move |db| delete_member(db, member_id)).await
+    }
+
+    pub async fn owner_count(&self, workspace_id: i64) -> Result<i64> {
+        self.run(move |db| count_owners(db, workspace_id)).await
+    }
+
+    pub async fn delete_workspace(&self, id: i64) -> Result<()> {
+        self.run(move |db| remove_workspace(db, id)).await
+    }
+
+    pub async fn attach_to_workspace(
+        &self,
+        email: String,
+        workspace_id: i64,
+        kind: AttachKind,
+        id: i64,
+    ) -> Result<bool> {
+        self.run(move |db| move_into_workspace(db, email, workspace_id, kind, id))
+            .await
+    }
+}
@@ -0,0 +1,216 @@
+use miette::{Result, miette};
+use rusqlite::{Connection, OptionalExtension, Statement, params, params_from_iter};
+
+use crate::{
+    grpc::{ProtoSource, build_protoset},
+    models::proto::ProtoFile,
+};
+
+const PROTO_COLUMNS: &str = "id, user_email, target, name, kind, length(content), date";
+
+pub fn find_protos(db: &mut Connection, email: String) -> Result<Vec<ProtoFile>> {
+    map_protos(
+        db.prepare(&format!(
+            "SELECT {PROTO_COLUMNS} FROM proto WHERE user_email = ?1 ORDER BY target, name"
+        ))
+        .map_err(|e| miette!("Invalid statement: {e}"))?,
+        &[email],
+    )
+}
+
+// Everything the user uploaded for a target, with the content
+pub fn find_proto_sources(
+    db: &Connection,
+    email: String,
+    target: String,
+) -> Result<Vec<ProtoSource>> {
+    db.prepare("SELECT name, kind, content FROM proto WHERE user_email = ?1 AND target = ?2")
+        .map_err(|e| miette!("Invalid statement: {e}"))?
+        .query_map([email, target], |row| {
+            let name = row.get::<_, String>(0)?;
+            let content = row.get::<_, Vec<u8>>(2)?;
+
+            Ok(match row.get::<_, String>(1)?.as_str() {
+                "proto" => ProtoSource::Proto {
+                    name,
+                    source: String::from_utf8_lossy(&content).to_string(),
+                },
+                _ => ProtoSource::Protoset {
+                    name,
+                    bytes: content,
+                },
+            })
+        })
+        .map_err(|e| miette!("Error mapping proto files: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| miette!("Could not read proto files: {e}"))
+}
+
+// The target's files compiled together. Targets uploaded to before builds were stored get theirs
+// on first use
+pub fn find_proto_build(
+    db: &mut Connection,
+    email: String,
+    target: String,
+) -> Result<Option<Vec<u8>>> {
+    if let Some(protoset) = find_stored_build(db, &email, &target)? {
+        return Ok(Some(protoset));
+    }
+
+    let sources = find_proto_sources(db, email.clone(), target.clone())?;
+    if sources.is_empty() {
+        return Ok(None);
+    }
+
+    store_build(db, &email, &target, &sources)?.map_err(|e| miette!("{e}"))
+}
+
+// Uploading a file with the same name for the same target replaces it. The target's files are
+// compiled together before anything is kept, the inner error is why they don't compile
+pub fn upsert_proto(
+    db: &mut Connection,
+    email: String,
+    target: String,
+    name: String,
+    kind: String,
+    content: Vec<u8>,
+    date: String,
+) -> Result<Result<Option<ProtoFile>, String>> {
+    let transaction = db
+        .transaction()
+        .map_err(|e| miette!("Could not start transaction: {e}"))?;
+
+    transaction
+        .execute(
+            r#"
+            INSERT INTO proto (user_email, target, name, kind, content, date)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+            ON CONFLICT (user_email, target, name)
+            DO UPDATE SET kind = excluded.kind, content = excluded.content, date = excluded.date
+        "#,
+            params![email, target, name, kind, content, date],
+        )
+        .map_err(|e| miette!("Could not store proto file: {e}"))?;
+
+    let sources = find_proto_sources(&transaction, email.clone(), target.clone())?;
+    if let Err(e) = store_build(&transaction, &email, &target, &sources)? {
+        return Ok(Err(e));
+    }
+
+    let proto = map_protos(
+        transaction
+            .prepare(&format!(
+                "SELECT {PROTO_COLUMNS} FROM proto WHERE user_email = ?1 AND target = ?2 AND name = ?3"
+            ))
+            .map_err(|e| miette!("Invalid statement: {e}"))?,
+        &[email, target, name],
+    )?
+    .pop();
+    transaction
+        .commit()
+        .map_err(|e| miette!("Could not store proto file: {e}"))?;
+
+    Ok(Ok(proto))
+}
+
+// Returns whether the user had a proto file with that id. A file the target's other files still
+// import stays, the inner error is why they wouldn't compile without it. Targets that didn't
+// compile to begin with can always lose files
+pub fn remove_proto(db: &mut Connection, email: String, id: i32) -> Result<Result<bool, String>> {
+    let transaction = db
+        .transaction()
+        .map_err(|e| miette!("Could not start transaction: {e}"))?;
+
+    let Some(target) = transaction
+        .query_row(
+            "DELETE FROM proto WHERE user_email = ?1 AND id = ?2 RETURNING target",
+            params![email, id],
+            |row| row.get::<_, String>(0),
+        )
+        .optional()
+        .map_err(|e| miette!("Could not delete proto file: {e}"))?
+    else {
+        return Ok(Ok(false));
+    };
+
+    let compiled = find_stored_build(&transaction, &email, &target)?.is_some();
+    let sources = find_proto_sources(&transaction, email.clone(), target.clone())?;
+    if let Err(e) = store_build(&transaction, &email, &target, &sources)?
+        && compiled
+    {
+        return Ok(Err(e));
+    }
+    transaction
+        .commit()
+        .map_err(|e| miette!("Could not delete proto file: {e}"))?;
+
+    Ok(Ok(true))
+}
+
+// Builds are made again on next use, for when files change owner
+pub fn clear_proto_build(db: &Connection, email: &str, target: &str) -> Result<()> {
+    db.execute(
+        "DELETE FROM proto_build WHERE user_email = ?1 AND target = ?2",
+        params![email, target],
+    )
+    .map_err(|e| miette!("Could not delete proto build: {e}"))?;
+
+    Ok(())
+}
+
+fn find_stored_build(db: &Connection, email: &str, target: &str) -> Result<Option<Vec<u8>>> {
+    db.query_row(
+        "SELECT protoset FROM proto_build WHERE user_email = ?1 AND target = ?2",
+        params![email, target],
+        |row| row.get::<_, Vec<u8>>(0),
+    )
+    .optional()
+    .map_err(|e| miette!("Could not read proto build: {e}"))
+}
+
+// Compiles the target's files and keeps the result in place of the last one, a target without
+// files (or whose files don't compile) keeps none
+fn store_build(
+    db: &Connection,
+    email: &str,
+    target: &str,
+    sources: &[ProtoSource],
+) -> Result<Result<Option<Vec<u8>>, String>> {
+    clear_proto_build(db, email, target)?;
+
+    if sources.is_empty() {
+        return Ok(Ok(None));
+    }
+    let protoset = match build_protoset(sources) {
+        Ok(protoset) => protoset,
+        Err(e) => return Ok(Err(e.to_string())),
+    };
+
+    db.execute(
+        "INSERT INTO proto_build (user_email, target, protoset) VALUES (?1, ?2, ?3)",
+        params![email, target, protoset],
+    )
+    .map_err(|e| miette!("Could not store proto build: {e}"))?;
+
+    Ok(Ok(Some(protoset)))
+}
+
+pub fn map_protos(mut statement: Statement<'_>, args: &[String]) -> Result<Vec<ProtoFile>> {
+    let parsed_rows = statement
+        .query_map(params_from_iter(args), |row| {
+            Ok(ProtoFile {
+                id: row.get(0)?,
+                user_email: row.get::<_, Option<String>>(1)?,
+                target: row.get(2)?,
+                name: row.get(3)?,
+                kind: row.get(4)?,
+                size: row.get(5)?,
+                date: row.get(6)?,
+            })
+        })
+        .map_err(|e| miette!("Error mapping rows to ProtoFile: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| miette!("Could not read ProtoFile row: {e}"))?;
+
+    Ok(parsed_rows)
+}
@@ -0,0 +1,209 @@
+use std::num::NonZeroI32;
+
+use miette::{Result, miette};
+use rusqlite::{Connection, Statement, params, params_from_iter};
+
+use crate::models::{
+    compress_response_for_db, decompress_response_from_db, deserialize_bool_from_db,
+    deserialize_pairs_from_db, request::Request, serialize_bool_for_db, serialize_millis_for_db,
+    serialize_pairs_for_db,
+};
+
+pub fn find_requests(db: &mut Connection, email: String) -> Result<Vec<Request>> {
+    map_requests(
+        db.prepare(
+            "SELECT * FROM request WHERE user_email = ?1 AND hidden = false ORDER BY id DESC",
+        )
+        .map_err(|e| miette!("Invalid statement: {e}"))?,
+        &[email],
+    )
+}
+
+pub fn find_favorite_requests(db: &mut Connection, email: String) -> Result<Vec<Request>> {
+    map_requests(
+        db.prepare(
+            r#"
+            SELECT request.* FROM favorite
+            JOIN request ON request.id = favorite.request_id
+            WHERE favorite.user_email = ?1 AND request.hidden = false
+            ORDER BY favorite.position, favorite.id
+        "#,
+        )
+        .map_err(|e| miette!("Invalid statement: {e}"))?,
+        &[email],
+    )
+}
+
+// The latest commands in the history, most recent first
+pub fn find_recent_commands(db: &mut Connection, email: String, limit: i64) -> Result<Vec<String>> {
+    db.prepare("SELECT command FROM request WHERE user_email = ?1 ORDER BY id DESC LIMIT ?2")
+        .map_err(|e| miette!("Invalid statement: {e}"))?
+        .query_map(params![email, limit], |row| row.get::<_, String>(0))
+        .map_err(|e| miette!("Error mapping commands: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| miette!("Could not read commands: {e}"))
+}
+
+// A single history row with its stored response body
+pub fn find_request(db: &mut Connection, email: String, id: NonZeroI32) -> Result<Option<Request>> {
+    let request_id = id.to_string();
+
+    let Some(mut request) = map_requests(
+        db.prepare("SELECT * FROM request WHERE user_email = ?1 AND id = ?2")
+            .map_err(|e| miette!("Invalid statement: {e}"))?,
+        &[email.clone(), request_id.clone()],
+    )?
+    .pop() else {
+        return Ok(None);
+    };
+
+    let response = db
+        .query_row(
+            "SELECT response FROM request WHERE user_email = ?1 AND id = ?2",
+            [email, request_id],
+            |row| row.get::<_, Option<Vec<u8>>>(0),
+        )
+        .map_err(|e| miette!("Could not query stored response: {e}"))?;
+    request.response = response
+        .map(|response| decompress_response_from_db(&response))
+        .transpose()?;
+
+    Ok(Some(request))
+}
+
+// Stores a call in the history with its response body cut to max_size, None if nothing was
+// inserted
+pub fn insert_request(
+    db: &mut Connection,
+    email: String,
+    request: Request,
+    max_size: usize,
+) -> Result<Option<Request>> {
+    let transaction = db
+        .transaction()
+        .map_err(|e| miette!("Could not start storing the request: {e}"))?;
+
+    let Some(mut created) = map_requests(
+        transaction
+            .prepare(
+                r#"
+            INSERT INTO request (
+                user_email, command, status, method, date, hidden, status_message,
+                status_details, headers, trailers, total_ms, connect_ms, first_byte_ms,
+                resolved_command
+            )
+            VALUES (
+                ?1, ?2, ?3, ?4, ?5, ?6, NULLIF(?7, ''), ?8, ?9, ?10,
+                CAST(NULLIF(?11, '') AS REAL), CAST(NULLIF(?12, '') AS REAL),
+                CAST(NULLIF(?13, '') AS REAL), NULLIF(?14, '')
+            ) RETURNING *
+        "#,
+            )
+            .map_err(|e| miette!("Invalid statement: {e}"))?,
+        &[
+            email,
+            request.command,
+            request.status.unwrap_or_default(),
+            request.method.unwrap_or_default(),
+            request.date,
+            serialize_bool_for_db(request.hidden).to_string(),
+            request.status_message.unwrap_or_default(),
+            serialize_pairs_for_db(&request.status_details),
+            serialize_pairs_for_db(&request.headers),
+            serialize_pairs_for_db(&request.trailers),
+            serialize_millis_for_db(request.total_ms),
+            serialize_millis_for_db(request.connect_ms),
+            serialize_millis_for_db(request.first_byte_ms),
+            request.resolved_command.unwrap_or_default(),
+        ],
+    )?
+    .pop()
+    .filter(|created| created.id != Some(0)) else {
+        return Ok(None);
+    };
+
+    if let Some(response) = &request.response {
+        let (compressed, truncated) = compress_response_for_db(response, max_size)?;
+
+        transaction
+            .execute(
+                "UPDATE request SET response = ?1, response_size = ?2, response_truncated = ?3 WHERE id = ?4",
+                params![
+                    compressed,
+                    response.len() as i64,
+                    serialize_bool_for_db(truncated),
+                    created.id
+                ],
+            )
+            .map_err(|e| miette!("Could not store response: {e}"))?;
+
+        created.response_size = Some(response.len() as i64);
+        created.response_truncated = truncated;
+    }
+
+    transaction
+        .commit()
+        .map_err(|e| miette!("Could not store the request: {e}"))?;
+
+    Ok(Some(created))
+}
+
+// Takes the request out of the history and the favorites, false if the user has no such
+// request
+pub fn set_request_hidden(db: &mut Connection, email: String, id: NonZeroI32) -> Result<bool> {
+    let transaction = db
+        .transaction()
+        .map_err(|e| miette!("Could not start hiding the request: {e}"))?;
+
+    let hidden = transaction
+        .execute(
+            "UPDATE request SET hidden = true WHERE user_email = ?1 AND id = ?2",
+            params![email, id.get()],
+        )
+        .map_err(|e| miette!("Could not hide request: {e}"))?;
+    transaction
+        .execute(
+            "DELETE FROM favorite WHERE user_email = ?1 AND request_id = ?2",
+            params![email, id.get()],
+        )
+        .map_err(|e| miette!("Could not remove favorite: {e}"))?;
+
+    transaction
+        .commit()
+        .map_err(|e| miette!("Could not hide the request: {e}"))?;
+
+    Ok(hidden > 0)
+}
+
+pub fn map_requests(mut statement: Statement<'_>, args: &[String]) -> Result<Vec<Request>> {
+    let parsed_rows = statement
+        .query_map(params_from_iter(args), |row| {
+            Ok(Request {
+                id: row.get(0)?,
+                user_email: row.get::<_, Option<String>>(1)?,
+                command: row.get(2)?,
+                status: row.get(3)?,
+                method: row.get(4)?,
+                date: row.get(5)?,
+                hidden: deserialize_bool_from_db(row.get(6)?),
+                status_message: row.get(7)?,
+                status_details: deserialize_pairs_from_db(row.get(8)?),
+                headers: deserialize_pairs_from_db(row.get(9)?),
+                trailers: deserialize_pairs_from_db(row.get(10)?),
+                total_ms: row.get(11)?,
+                connect_ms: row.get(12)?,
+                first_byte_ms: row.get(13)?,
+                response: None,
+                response_size: row.get(15)?,
+                response_truncated: deserialize_bool_from_db(
+                    row.get::<_, Option<u8>>(16)?.unwrap_or_default(),
+                ),
+                resolved_command: row.get(17)?,
+            })
+        })
+        .map_err(|e| miette!("Error mapping rows to Request: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| miette!("Could not read Request row: {e}"))?;
+
+    Ok(parsed_rows)
+}
@@ -0,0 +1,73 @@
+use miette::{Result, miette};
+use rusqlite::{Connection, OptionalExtension, params};
+
+// Returns the new session's id
+pub fn insert_session(
+    db: &mut Connection,
+    email: String,
+    token_hash: String,
+    expires: u64,
+    date: String,
+) -> Result<i64> {
+    db.query_row(
+        r#"
+        INSERT INTO session (user_email, token_hash, expires, revoked, date)
+        VALUES (?1, ?2, ?3, false, ?4) RETURNING id
+    "#,
+        params![email, token_hash, expires, date],
+        |row| row.get(0),
+    )
+    .map_err(|e| miette!("Could not create session: {e}"))
+}
+
+// Swaps the refresh token of a live session for a new one, the old token stops working. Returns
+// the session id and its user
+pub fn rotate_session(
+    db: &mut Connection,
+    token_hash: String,
+    new_token_hash: String,
+    expires: u64,
+    now: u64,
+) -> Result<Option<(i64, String)>> {
+    db.query_row(
+        r#"
+        UPDATE session SET token_hash = ?2, expires = ?3
+        WHERE token_hash = ?1 AND revoked = false AND expires > ?4
+        RETURNING id, user_email
+    "#,
+        params![token_hash, new_token_hash, expires, now],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+    .optional()
+    .map_err(|e| miette!("Could not rotate session: {e}"))
+}
+
+pub fn session_is_active(db: &mut Connection, id: i64, now: u64) -> Result<bool> {
+    db.query_row(
+        "SELECT COUNT(*) FROM session WHERE id = ?1 AND revoked = false AND expires > ?2",
+        params![id, now],
+        |row| row.get::<_, i64>(0),
+    )
+    .map(|count| count > 0)
+    .map_err(|e| miette!("Could not query session: {e}"))
+}
+
+pub fn revoke_session(db: &mut Connection, token_hash: String) -> Result<()> {
+    db.execute(
+        "UPDATE session SET revoked = true WHERE token_hash = ?1",
+        params![token_hash],
+    )
+    .map_err(|e| miette!("Could not revoke session: {e}"))?;
+
+    Ok(())
+}
+
+pub fn revoke_user_sessions(db: &Connection, email: String) -> Result<()> {
+    db.execute(
+        "UPDATE session SET revoked = true WHERE user_email = ?1",
+        params![email],
+    )
+    .map_err(|e| miette!("Could not revoke sessions: {e}"))?;
+
+    Ok(())
+}
@@ -0,0 +1,95 @@
+use miette::{Result, miette};
+use rusqlite::{Connection, OptionalExtension, Row, params};
+
+use crate::models::token::{ApiToken, Scope};
+
+const API_TOKEN_COLUMNS: &str = "id, name, scopes, expires, last_used, revoked, date";
+
+pub fn insert_api_token(
+    db: &mut Connection,
+    email: String,
+    name: String,
+    token_hash: String,
+    scopes: Vec<Scope>,
+    expires: Option<u64>,
+    date: String,
+) -> Result<ApiToken> {
+    let scopes = serde_json::to_string(&scopes).map_err(|e| miette!("Invalid scopes: {e}"))?;
+
+    db.query_row(
+        &format!(
+            r#"
+            INSERT INTO api_token (user_email, name, token_hash, scopes, expires, revoked, date)
+            VALUES (?1, ?2, ?3, ?4, ?5, false, ?6) RETURNING {API_TOKEN_COLUMNS}
+        "#
+        ),
+        params![email, name, token_hash, scopes, expires, date],
+        map_api_token,
+    )
+    .map_err(|e| miette!("Could not create token: {e}"))
+}
+
+pub fn find_api_tokens(db: &mut Connection, email: String) -> Result<Vec<ApiToken>> {
+    db.prepare(&format!(
+        "SELECT {API_TOKEN_COLUMNS} FROM api_token WHERE user_email = ?1 ORDER BY id DESC"
+    ))
+    .map_err(|e| miette!("Invalid statement: {e}"))?
+    .query_map(params![email], map_api_token)
+    .map_err(|e| miette!("Could not query tokens: {e}"))?
+    .collect::<Result<Vec<_>, _>>()
+    .map_err(|e| miette!("Could not read token: {e}"))
+}
+
+// Looks up a live token by its hash and marks it used. Returns its user and scopes
+pub fn use_api_token(
+    db: &mut Connection,
+    token_hash: String,
+    now: u64,
+) -> Result<Option<(String, Vec<Scope>)>> {
+    let found = db
+        .query_row(
+            r#"
+            UPDATE api_token SET last_used = ?2
+            WHERE token_hash = ?1 AND revoked = false AND (expires IS NULL OR expires > ?2)
+            RETURNING user_email, scopes
+        "#,
+            params![token_hash, now],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
+        )
+        .optional()
+        .map_err(|e| miette!("Could not query token: {e}"))?;
+
+    Ok(found.map(|(email, scopes)| (email, serde_json::from_str(&scopes).unwrap_or_default())))
+}
+
+// Returns whether the user had a token with that id
+pub fn mark_api_token_revoked(db: &mut Connection, email: String, id: i64) -> Result<bool> {
+    db.execute(
+        "UPDATE api_token SET revoked = true WHERE id = ?1 AND user_email = ?2",
+        params![id, email],
+    )
+    .map(|updated| updated > 0)
+    .map_err(|e| miette!("Could not revoke token: {e}"))
+}
+
+pub fn revoke_user_api_tokens(db: &Connection, email: String) -> Result<()> {
+    db.execute(
+        "UPDATE api_token SET revoked = true WHERE user_email = ?1",
+        params![email],
+    )
+    .map_err(|e| miette!("Could not revoke tokens: {e}"))?;
+
+    Ok(())
+}
+
+fn map_api_token(row: &Row<'_>) -> rusqlite::Result<ApiToken> {
+    Ok(ApiToken {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        scopes: serde_json::from_str(&row.get::<_, String>(2)?).unwrap_or_default(),
+        expires: row.get(3)?,
+        last_used: row.get(4)?,
+        revoked: row.get(5)?,
+        date: row.get(6)?,
+    })
+}
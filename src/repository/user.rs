@@ -0,0 +1,85 @@
+use miette::{Result, miette};
+use rusqlite::{Connection, Statement, params_from_iter};
+
+use crate::models::{deserialize_bool_from_db, serialize_bool_for_db, user::User};
+
+// Deleted users aren't found
+pub fn find_user(db: &mut Connection, email: String) -> Result<Option<User>> {
+    Ok(map_user(
+        db.prepare(r#"SELECT * FROM "user" WHERE email = ?1 AND deleted = false"#)
+            .map_err(|e| miette!("Invalid statement: {e}"))?,
+        &[&email],
+    )?
+    .pop())
+}
+
+// The password is stored as given, hash it first
+pub fn insert_user(db: &mut Connection, user: User) -> Result<()> {
+    map_user(
+        db.prepare(
+            r#"
+            INSERT INTO "user" (username, email, password, date, deleted)
+            VALUES (?1, ?2, ?3, ?4, ?5) RETURNING *
+        "#,
+        )
+        .map_err(|e| miette!("Invalid statement: {e}"))?,
+        &[
+            &user.username,
+            &user.email,
+            &user.password,
+            &user.date.unwrap_or_default(),
+            &serialize_bool_for_db(user.deleted).to_string(),
+        ],
+    )?;
+
+    Ok(())
+}
+
+pub fn save_user(db: &Connection, user: User) -> Result<()> {
+    map_user(
+        db.prepare(r#"UPDATE "user" SET username = ?1, password = ?2 WHERE email = ?3"#)
+            .map_err(|e| miette!("Invalid statement: {e}"))?,
+        &[&user.username, &user.password, &user.email],
+    )?;
+
+    Ok(())
+}
+
+// Swaps the hash without touching the sessions, the password itself didn't change
+pub fn save_password(db: &mut Connection, email: String, password: String) -> Result<()> {
+    map_user(
+        db.prepare(r#"UPDATE "user" SET password = ?1 WHERE email = ?2"#)
+            .map_err(|e| miette!("Invalid statement: {e}"))?,
+        &[&password, &email],
+    )?;
+
+    Ok(())
+}
+
+pub fn mark_user_deleted(db: &Connection, email: String) -> Result<()> {
+    map_user(
+        db.prepare(r#"UPDATE "user" SET deleted = true WHERE email = ?1 RETURNING *"#)
+            .map_err(|e| miette!("Invalid statement: {e}"))?,
+        &[&email],
+    )?;
+
+    Ok(())
+}
+
+pub fn map_user(mut statement: Statement<'_>, args: &[&String]) -> Result<Vec<User>> {
+    let parsed_rows = statement
+        .query_map(params_from_iter(args), |row| {
+            Ok(User {
+                username: row.get(0)?,
+                email: row.get(1)?,
+                password: row.get(2)?,
+                date: row.get::<_, Option<String>>(3)?,
+                deleted: deserialize_bool_from_db(row.get(4)?),
+            })
+        })
+        .map_err(|e| miette!("Error mapping rows to User: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| miette!("Could not read User row: {e}"))?;
+
+    Ok(parsed_rows)
+}
@@ -0,0 +1,387 @@
+use miette::{Result, miette};
+use rusqlite::{Connection, OptionalExtension, Row, params};
+
+use crate::{
+    models::workspace::{AttachKind, Member, Role, Workspace},
+    repository::clear_proto_build,
+};
+
+const WORKSPACE_COLUMNS: &str = r#"
+    workspace.id, workspace.name, workspace_member.role, workspace_member.active, workspace.date
+"#;
+const MEMBER_COLUMNS: &str = r#"
+    workspace_member.id, workspace_member.workspace_id, workspace_member.user_email,
+    "user".username, workspace_member.role, workspace_member.date
+"#;
+
+// A workspace owns its history, collections, environments and protos the way a user does, under
+// a "user" row of its own with the email `workspace:{id}`. Every table already scopes its rows by
+// `user_email` and references "user", so the workspace's data goes through the same queries,
+// foreign keys and handlers as a user's without a second owner column on each table.
+// The row is inserted as deleted with an empty password, so it can't log in or be found as a
+// user. Signup refuses emails with the prefix, and the row is removed along with the workspace
+pub const WORKSPACE_OWNER_PREFIX: &str = "workspace:";
+
+pub fn workspace_owner(id: i64) -> String {
+    format!("{WORKSPACE_OWNER_PREFIX}{id}")
+}
+
+// The creator becomes its first owner
+pub fn insert_workspace(
+    db: &mut Connection,
+    email: String,
+    name: String,
+    date: String,
+) -> Result<Workspace> {
+    let transaction = db
+        .transaction()
+        .map_err(|e| miette!("Could not start creating workspace: {e}"))?;
+
+    let id = transaction
+        .query_row(
+            "INSERT INTO workspace (name, date) VALUES (?1, ?2) RETURNING id",
+            params![name, date],
+            |row| row.get::<_, i64>(0),
+        )
+        .map_err(|e| miette!("Could not create workspace: {e}"))?;
+    transaction
+        .execute(
+            r#"
+            INSERT INTO "user" (username, email, password, date, deleted)
+            VALUES (?1, ?2, '', ?3, true)
+        "#,
+            params![name, workspace_owner(id), date],
+        )
+        .map_err(|e| miette!("Could not create workspace owner: {e}"))?;
+    transaction
+        .execute(
+            r#"
+            INSERT INTO workspace_member (workspace_id, user_email, role, active, date)
+            VALUES (?1, ?2, ?3, false, ?4)
+        "#,
+            params![id, email, Role::Owner.as_str(), date],
+        )
+        .map_err(|e| miette!("Could not add workspace owner: {e}"))?;
+
+    transaction
+        .commit()
+        .map_err(|e| miette!("Could not finish creating workspace: {e}"))?;
+
+    Ok(Workspace {
+        id,
+        name,
+        role: Role::Owner,
+        active: false,
+        date,
+    })
+}
+
+pub fn find_workspaces(db: &mut Connection, email: String) -> Result<Vec<Workspace>> {
+    db.prepare(&format!(
+        r#"
+        SELECT {WORKSPACE_COLUMNS} FROM workspace_member
+        JOIN workspace ON workspace.id = workspace_member.workspace_id
+        WHERE workspace_member.user_email = ?1 ORDER BY workspace.name, workspace.id
+    "#
+    ))
+    .map_err(|e| miette!("Invalid statement: {e}"))?
+    .query_map(params![email], map_workspace)
+    .map_err(|e| miette!("Could not query workspaces: {e}"))?
+    .collect::<Result<Vec<_>, _>>()
+    .map_err(|e| miette!("Could not read workspace: {e}"))
+}
+
+// The user's membership of the workspace, None when they aren't in it
+pub fn find_workspace(db: &mut Connection, email: String, id: i64) -> Result<Option<Workspace>> {
+    db.query_row(
+        &format!(
+            r#"
+            SELECT {WORKSPACE_COLUMNS} FROM workspace_member
+            JOIN workspace ON workspace.id = workspace_member.workspace_id
+            WHERE workspace_member.user_email = ?1 AND workspace.id = ?2
+        "#
+        ),
+        params![email, id],
+        map_workspace,
+    )
+    .optional()
+    .map_err(|e| miette!("Could not query workspace: {e}"))
+}
+
+pub fn find_active_workspace(db: &mut Connection, email: String) -> Result<Option<Workspace>> {
+    db.query_row(
+        &format!(
+            r#"
+            SELECT {WORKSPACE_COLUMNS} FROM workspace_member
+            JOIN workspace ON workspace.id = workspace_member.workspace_id
+            WHERE workspace_member.user_email = ?1 AND workspace_member.active = true
+        "#
+        ),
+        params![email],
+        map_workspace,
+    )
+    .optional()
+    .map_err(|e| miette!("Could not query active workspace: {e}"))
+}
+
+// Makes the workspace the only active one, no id goes back to the user's own data. Returns
+// whether the user is a member of it
+pub fn set_active_workspace(db: &mut Connection, email: String, id: Option<i64>) -> Result<bool> {
+    if let Some(id) = id
+        && find_workspace(db, email.clone(), id)?.is_none()
+    {
+        return Ok(false);
+    }
+
+    db.execute(
+        "UPDATE workspace_member SET active = (workspace_id IS ?2) WHERE user_email = ?1",
+        params![email, id],
+    )
+    .map_err(|e| miette!("Could not switch workspace: {e}"))?;
+
+    Ok(true)
+}
+
+pub fn find_members(db: &mut Connection, workspace_id: i64) -> Result<Vec<Member>> {
+    db.prepare(&format!(
+        r#"
+        SELECT {MEMBER_COLUMNS} FROM workspace_member
+        JOIN "user" ON "user".email = workspace_member.user_email
+        WHERE workspace_member.workspace_id = ?1 ORDER BY workspace_member.id
+    "#
+    ))
+    .map_err(|e| miette!("Invalid statement: {e}"))?
+    .query_map(params![workspace_id], map_member)
+    .map_err(|e| miette!("Could not query members: {e}"))?
+    .collect::<Result<Vec<_>, _>>()
+    .map_err(|e| miette!("Could not read member: {e}"))
+}
+
+pub fn find_member(db: &mut Connection, member_id: i64) -> Result<Option<Member>> {
+    db.query_row(
+        &format!(
+            r#"
+            SELECT {MEMBER_COLUMNS} FROM workspace_member
+            JOIN "user" ON "user".email = workspace_member.user_email
+            WHERE workspace_member.id = ?1
+        "#
+        ),
+        params![member_id],
+        map_member,
+    )
+    .optional()
+    .map_err(|e| miette!("Could not query member: {e}"))
+}
+
+// Adds the user to the workspace, or changes their role when they're already in it
+pub fn upsert_member(
+    db: &mut Connection,
+    workspace_id: i64,
+    email: String,
+    role: Role,
+    date: String,
+) -> Result<Member> {
+    let member_id = db
+        .query_row(
+            r#"
+            INSERT INTO workspace_member (workspace_id, user_email, role, active, date)
+            VALUES (?1, ?2, ?3, false, ?4)
+            ON CONFLICT (workspace_id, user_email) DO UPDATE SET role = excluded.role
+            RETURNING id
+        "#,
+            params![workspace_id, email, role.as_str(), date],
+            |row| row.get::<_, i64>(0),
+        )
+        .map_err(|e| miette!("Could not add member: {e}"))?;
+
+    find_member(db, member_id)?.ok_or_else(|| miette!("Member {member_id} went missing"))
+}
+
+pub fn delete_member(db: &mut Connection, member_id: i64) -> Result<()> {
+    db.execute(
+        "DELETE FROM workspace_member WHERE id = ?1",
+        params![member_id],
+    )
+    .map_err(|e| miette!("Could not remove member: {e}"))?;
+
+    Ok(())
+}
+
+pub fn count_owners(db: &mut Connection, workspace_id: i64) -> Result<i64> {
+    db.query_row(
+        "SELECT COUNT(*) FROM workspace_member WHERE workspace_id = ?1 AND role = ?2",
+        params![workspace_id, Role::Owner.as_str()],
+        |row| row.get(0),
+    )
+    .map_err(|e| miette!("Could not count owners: {e}"))
+}
+
+// Everything the workspace owns goes with it, favorites of its requests included
+pub fn remove_workspace(db: &mut Connection, id: i64) -> Result<()> {
+    let transaction = db
+        .transaction()
+        .map_err(|e| miette!("Could not start deleting workspace: {e}"))?;
+
+    clear_workspace(&transaction, id)?;
+
+    transaction
+        .commit()
+        .map_err(|e| miette!("Could not finish deleting workspace: {e}"))
+}
+
+// Takes a deleted user out of every workspace. A workspace nobody is left in is deleted, one left
+// without an owner gets its longest standing member as the new owner
+pub fn leave_workspaces(db: &Connection, email: String) -> Result<()> {
+    let workspace_ids = db
+        .prepare("SELECT workspace_id FROM workspace_member WHERE user_email = ?1")
+        .map_err(|e| miette!("Invalid statement: {e}"))?
+        .query_map(params![email], |row| row.get::<_, i64>(0))
+        .map_err(|e| miette!("Could not query workspaces: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| miette!("Could not read workspace: {e}"))?;
+
+    db.execute(
+        "DELETE FROM workspace_member WHERE user_email = ?1",
+        params![email],
+    )
+    .map_err(|e| miette!("Could not leave workspaces: {e}"))?;
+
+    for id in workspace_ids {
+        let (members, owners) = db
+            .query_row(
+                "SELECT COUNT(*), COUNT(*) FILTER (WHERE role = ?2) FROM workspace_member WHERE workspace_id = ?1",
+                params![id, Role::Owner.as_str()],
+                |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?)),
+            )
+            .map_err(|e| miette!("Could not count members: {e}"))?;
+
+        if members == 0 {
+            clear_workspace(db, id)?;
+        } else if owners == 0 {
+            db.execute(
+                r#"
+                UPDATE workspace_member SET role = ?2 WHERE id = (
+                    SELECT MIN(id) FROM workspace_member WHERE workspace_id = ?1
+                )
+            "#,
+                params![id, Role::Owner.as_str()],
+            )
+            .map_err(|e| miette!("Could not hand over workspace: {e}"))?;
+        }
+    }
+
+    Ok(())
+}
+
+fn clear_workspace(db: &Connection, id: i64) -> Result<()> {
+    let owner = workspace_owner(id);
+
+    for statement in [
+        "DELETE FROM favorite WHERE user_email = ?1",
+        "DELETE FROM request WHERE user_email = ?1",
+        "DELETE FROM proto WHERE user_email = ?1",
+        "DELETE FROM proto_build WHERE user_email = ?1",
+        "DELETE FROM environment WHERE user_email = ?1",
+        "DELETE FROM collection WHERE user_email = ?1",
+    ] {
+        db.execute(statement, params![owner])
+            .map_err(|e| miette!("Could not delete workspace data: {e}"))?;
+    }
+    db.execute("DELETE FROM workspace WHERE id = ?1", params![id])
+        .map_err(|e| miette!("Could not delete workspace: {e}"))?;
+    db.execute(r#"DELETE FROM "user" WHERE email = ?1"#, params![owner])
+        .map_err(|e| miette!("Could not delete workspace owner: {e}"))?;
+
+    Ok(())
+}
+
+// Hands one of the user's own items to the workspace. Returns whether the user had it
+pub fn move_into_workspace(
+    db: &mut Connection,
+    email: String,
+    workspace_id: i64,
+    kind: AttachKind,
+    id: i64,
+) -> Result<bool> {
+    let owner = workspace_owner(workspace_id);
+    let statement = match kind {
+        AttachKind::Request => {
+            "UPDATE request SET user_email = ?2 WHERE id = ?3 AND user_email = ?1 AND hidden = false"
+        }
+        AttachKind::Proto => "UPDATE proto SET user_email = ?2 WHERE id = ?3 AND user_email = ?1",
+        AttachKind::Environment => {
+            "UPDATE environment SET user_email = ?2, active = false WHERE id = ?3 AND user_email = ?1"
+        }
+        // The folder lands at the workspace's top level with everything under it
+        AttachKind::Collection => {
+            r#"
+            WITH RECURSIVE tree (id) AS (
+                SELECT id FROM collection WHERE id = ?3 AND user_email = ?1
+                UNION ALL
+                SELECT collection.id FROM collection JOIN tree ON collection.parent_id = tree.id
+            )
+            UPDATE collection SET
+                user_email = ?2,
+                parent_id = CASE WHEN id = ?3 THEN NULL ELSE parent_id END,
+                position = CASE WHEN id = ?3 THEN (
+                    SELECT COALESCE(MAX(position) + 1, 0) FROM collection
+                    WHERE user_email = ?2 AND parent_id IS NULL
+                ) ELSE position END
+            WHERE id IN tree
+        "#
+        }
+    };
+
+    let transaction = db
+        .transaction()
+        .map_err(|e| miette!("Could not start attaching to workspace: {e}"))?;
+
+    // A proto file changes what both sides compile for its target
+    let target = match kind {
+        AttachKind::Proto => transaction
+            .query_row(
+                "SELECT target FROM proto WHERE id = ?2 AND user_email = ?1",
+                params![email, id],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()
+            .map_err(|e| miette!("Could not read proto file: {e}"))?,
+        _ => None,
+    };
+
+    let attached = transaction
+        .execute(statement, params![email, owner, id])
+        .map(|updated| updated > 0)
+        .map_err(|e| miette!("Could not attach to workspace: {e}"))?;
+    if let Some(target) = target {
+        clear_proto_build(&transaction, &email, &target)?;
+        clear_proto_build(&transaction, &owner, &target)?;
+    }
+
+    transaction
+        .commit()
+        .map_err(|e| miette!("Could not finish attaching to workspace: {e}"))?;
+
+    Ok(attached)
+}
+
+fn map_workspace(row: &Row<'_>) -> rusqlite::Result<Workspace> {
+    Ok(Workspace {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        role: Role::parse(&row.get::<_, String>(2)?).unwrap_or(Role::Viewer),
+        active: row.get(3)?,
+        date: row.get(4)?,
+    })
+}
+
+fn map_member(row: &Row<'_>) -> rusqlite::Result<Member> {
+    Ok(Member {
+        id: row.get(0)?,
+        workspace_id: row.get(1)?,
+        user_email: row.get(2)?,
+        username: row.get(3)?,
+        role: Role::parse(&row.get::<_, String>(4)?).unwrap_or(Role::Viewer),
+        date: row.get(5)?,
+    })
+}
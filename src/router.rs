@@ -1,54 +1,132 @@
-use std::sync::{Arc, Mutex};
+use std::path::PathBuf;
 
 use axum::{
-    Router,
-    routing::{delete, get, patch, post},
+    Extension, Router,
+    routing::{delete, get, patch, post, put},
 };
-use tower_http::services::ServeDir;
 
-use crate::handlers::*;
+use crate::{
+    grpc::{DescriptorCache, InFlightCalls, StreamSessions},
+    handlers::*,
+    repository::Repository,
+    utils::{AssetOverride, DbPool, JwtSignature, env},
+};
+
+pub fn init_router(pool: DbPool) -> Router {
+    init_router_with(pool, JwtSignature::from_env())
+}
+
+// Tests sign tokens with their own key, the server uses JWT_SIGNATURE
+pub fn init_router_with(pool: DbPool, signature: JwtSignature) -> Router {
+    let assets_dir = env()
+        .ok()
+        .map(|env| env.assets_dir)
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from);
 
-pub fn init_router(global_db: Arc<Mutex<rusqlite::Connection>>) -> Router {
     Router::new()
         // User routes
         .route("/api/user/auth", post(get_user))
+        .route("/api/user/refresh", post(refresh_session))
+        .route("/api/user/logout", post(logout_user))
         .route(
             "/api/user",
             post(create_user).put(update_user).delete(delete_user),
         )
-        .route("/api/user/favorites", patch(update_favorites))
+        .route("/api/token", get(get_all_api_tokens).post(create_api_token))
+        .route("/api/token/delete/{id}", delete(revoke_api_token))
+        // Workspace routes
+        .route(
+            "/api/workspace",
+            get(get_all_workspaces).post(create_workspace),
+        )
+        .route("/api/workspace/active", put(switch_workspace))
+        .route("/api/workspace/delete/{id}", delete(delete_workspace))
+        .route(
+            "/api/workspace/{id}/member",
+            get(get_workspace_members).put(set_workspace_member),
+        )
+        .route(
+            "/api/workspace/member/delete/{id}",
+            delete(remove_workspace_member),
+        )
+        .route("/api/workspace/{id}/attach", put(attach_to_workspace))
         // Request routes
+        .route("/api/request", get(get_all_requests).post(create_request))
+        .route("/api/request/favorites", get(get_all_favorite_requests))
+        .route("/api/request/{id}", get(get_request))
+        .route("/api/request/delete/{id}", delete(hide_request))
+        // Favorite routes
+        .route(
+            "/api/favorite/{id}",
+            put(add_favorite).delete(remove_favorite),
+        )
+        // Proto registry routes
+        .route("/api/proto", get(get_all_protos).post(create_proto))
+        .route("/api/proto/delete/{id}", delete(delete_proto))
+        // Environment routes
         .route(
-            "/api/request/{email}",
-            get(get_all_requests).post(create_request),
+            "/api/environment",
+            get(get_all_environments).post(create_environment),
         )
+        .route("/api/environment/active", patch(activate_environment))
+        .route("/api/environment/variable", put(set_variable))
+        .route("/api/environment/variable/{id}", delete(delete_variable))
+        .route("/api/environment/delete/{id}", delete(delete_environment))
+        // Collection routes
         .route(
-            "/api/request/favorites/{email}",
-            get(get_all_favorite_requests),
+            "/api/collection",
+            get(get_all_collections).post(create_collection),
+        )
+        .route("/api/collection/{id}", put(update_collection))
+        .route("/api/collection/delete/{id}", delete(delete_collection))
+        .route("/api/collection/order", patch(reorder_collection))
+        .route("/api/collection/request", post(create_saved_request))
+        .route("/api/collection/request/{id}", put(update_saved_request))
+        .route(
+            "/api/collection/request/delete/{id}",
+            delete(delete_saved_request),
         )
-        .route("/api/request/delete/{email}/{id}", delete(hide_request))
         // Template routes
         .route("/", get(render_page))
         .route("/login", get(render_page))
         .route("/signup", get(render_page))
         .route("/profile", get(render_page))
-        .route("/handle/navbar/{page}/{token}", get(render_navbar))
-        .route("/handle/username/{token}", get(render_username))
-        .route("/handle/shortcut/{token}", get(render_home_shortcuts))
-        .route("/handle/request/new/{email}", get(render_new_request))
-        .route("/handle/request/history/{email}", get(render_history_list))
-        .route(
-            "/handle/request/favorites/{email}",
-            get(render_favorites_list),
-        )
-        .route("/grpcurl/request/{email}", post(execute_grpcurl_request))
-        .route("/handle/login/{token}", get(render_login))
-        .route("/handle/signup/{token}", get(render_signup))
-        .route("/handle/profile/info/{token}", get(render_profile_info))
-        .route("/handle/profile/update/{token}", get(render_profile_update))
+        .route("/handle/navbar/{page}", get(render_navbar))
+        .route("/handle/username", get(render_username))
+        .route("/handle/shortcut", get(render_home_shortcuts))
+        .route("/handle/request/new", get(render_new_request))
+        .route("/handle/request/history", get(render_history_list))
+        .route("/handle/request/favorites", get(render_favorites_list))
+        .route("/handle/request/browse", post(render_service_browser))
+        .route("/handle/proto/list", get(render_proto_list))
+        .route("/handle/collection/list", get(render_collection_list))
+        .route("/handle/environment/list", get(render_environment_list))
+        .route("/handle/request/response/{id}", get(render_stored_response))
+        .route("/grpcurl/request", post(execute_grpcurl_request))
+        .route("/grpcurl/complete", post(complete_grpcurl_command))
+        .route("/grpcurl/skeleton", post(generate_request_skeleton))
+        .route("/grpcurl/stream/{id}", get(stream_grpcurl_events))
+        .route(
+            "/grpcurl/stream/{id}/message",
+            post(send_grpcurl_stream_message),
+        )
+        .route("/grpcurl/stream/{id}/close", post(close_grpcurl_stream))
+        .route("/grpcurl/cancel/{id}", post(cancel_grpcurl_request))
+        .route("/handle/login", get(render_login))
+        .route("/handle/signup", get(render_signup))
+        .route("/handle/profile/info", get(render_profile_info))
+        .route("/handle/profile/update", get(render_profile_update))
         .route("/handle/profile/delete", get(render_profile_delete))
-        .nest_service("/public", ServeDir::new("public"))
+        .route("/handle/profile/tokens", get(render_api_token_list))
+        .route("/handle/profile/workspaces", get(render_workspace_list))
+        .route("/public/{*path}", get(serve_public_asset))
         // Healtcheck route
         .route("/api/healthcheck", get("gURL is healthy"))
-        .with_state(global_db)
+        .layer(Extension(StreamSessions::default()))
+        .layer(Extension(InFlightCalls::default()))
+        .layer(Extension(DescriptorCache::default()))
+        .layer(Extension(AssetOverride(assets_dir)))
+        .layer(Extension(signature))
+        .with_state(Repository::new(pool))
 }
@@ -0,0 +1,88 @@
+use std::{
+    borrow::Cow,
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use include_dir::{Dir, include_dir};
+use ring::digest::{SHA384, digest};
+
+static PUBLIC: Dir<'static> = include_dir!("$CARGO_MANIFEST_DIR/public");
+
+// Third party scripts pinned to a version: the file vendor.sh puts in public/vendor/ and the CDN
+// used when it isn't there
+const VENDOR_SCRIPTS: &[(&str, &str, Option<&str>)] = &[
+    (
+        "vendor/htmx-1.9.11.min.js",
+        "https://unpkg.com/htmx.org@1.9.11",
+        Some("sha384-0gxUXCCR8yv9FM2b+U3FDbsKthCI66oH5IA9fHppQq9DDMHuMauqq1ZHBpJxQ0J0"),
+    ),
+    (
+        "vendor/json-enc-1.9.11.js",
+        "https://unpkg.com/htmx.org@1.9.11/dist/ext/json-enc.js",
+        None,
+    ),
+    (
+        "vendor/beautify-html-1.15.1.min.js",
+        "https://cdnjs.cloudflare.com/ajax/libs/js-beautify/1.15.1/beautify-html.min.js",
+        None,
+    ),
+];
+
+// Vendored files never change under the same name, so browsers can keep them for good
+pub const VENDOR_CACHE_CONTROL: &str = "public, max-age=31536000, immutable";
+
+#[derive(Debug, Clone)]
+pub struct VendorScript {
+    pub src: String,
+    pub integrity: Option<String>,
+}
+
+// Directory read before the embedded files, set ASSETS_DIR="public" while working on the
+// frontend to see changes without rebuilding
+#[derive(Debug, Clone, Default)]
+pub struct AssetOverride(pub Option<PathBuf>);
+
+pub fn public_asset(override_dir: Option<&Path>, path: &str) -> Option<Cow<'static, [u8]>> {
+    let path = Path::new(path);
+
+    // Plain relative paths only, so nothing outside the directory can be read
+    if !path
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+    {
+        return None;
+    }
+
+    if let Some(dir) = override_dir
+        && let Ok(bytes) = fs::read(dir.join(path))
+    {
+        return Some(Cow::Owned(bytes));
+    }
+
+    PUBLIC
+        .get_file(path)
+        .map(|file| Cow::Borrowed(file.contents()))
+}
+
+pub fn vendor_scripts() -> Vec<VendorScript> {
+    VENDOR_SCRIPTS
+        .iter()
+        .map(|(path, cdn, cdn_integrity)| match PUBLIC.get_file(path) {
+            Some(file) => VendorScript {
+                src: format!("/public/{path}"),
+                integrity: Some(asset_integrity(file.contents())),
+            },
+            None => VendorScript {
+                src: cdn.to_string(),
+                integrity: cdn_integrity.map(str::to_string),
+            },
+        })
+        .collect()
+}
+
+// Subresource integrity value for the script tag
+pub fn asset_integrity(contents: &[u8]) -> String {
+    format!("sha384-{}", STANDARD.encode(digest(&SHA384, contents)))
+}
@@ -1,11 +1,20 @@
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use argon2::{
-    Argon2,
-    password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Algorithm as Argon2Algorithm, Argon2, Params, Version,
+    password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString, rand_core::OsRng},
 };
-use jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation, decode, encode};
+use base64::{
+    Engine,
+    engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
+};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation, decode, encode};
 use miette::{Result, miette};
+use ring::{
+    digest::{SHA256, digest},
+    hmac,
+    rand::{SecureRandom, SystemRandom},
+};
 use serde::{Deserialize, Serialize};
 
 use crate::{models::user::User, utils::env::env};
@@ -13,68 +22,200 @@ use crate::{models::user::User, utils::env::env};
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: String,
+    pub sid: i64, // session id
     pub exp: u64, // expiration time
     pub iat: u64, // issued at
 }
 
+// Hashes from before per-user salts all share this one, they're replaced on the next login
+const LEGACY_SALT: &str = "dec7901d02ee422ba6bd0333e4fef137";
+
+// Argon2id with the costs from the .env, Argon2's recommended ones when they're unset
+pub fn argon2() -> Result<Argon2<'static>> {
+    match env() {
+        Ok(env) => argon2_with_cost(
+            &env.argon2_memory_kib,
+            &env.argon2_iterations,
+            &env.argon2_parallelism,
+        ),
+        Err(_) => argon2_with_cost("", "", ""),
+    }
+}
+
+pub fn argon2_with_cost(
+    memory_kib: &str,
+    iterations: &str,
+    parallelism: &str,
+) -> Result<Argon2<'static>> {
+    let cost = |name: &str, value: &str, default: u32| match value.is_empty() {
+        true => Ok(default),
+        false => value
+            .parse::<u32>()
+            .map_err(|e| miette!("{name} must be a number: {e}")),
+    };
+
+    let params = Params::new(
+        cost("ARGON2_MEMORY_KIB", memory_kib, Params::DEFAULT_M_COST)?,
+        cost("ARGON2_ITERATIONS", iterations, Params::DEFAULT_T_COST)?,
+        cost("ARGON2_PARALLELISM", parallelism, Params::DEFAULT_P_COST)?,
+        None,
+    )
+    .map_err(|e| miette!("Invalid Argon2 parameters: {e}"))?;
+
+    Ok(Argon2::new(
+        Argon2Algorithm::Argon2id,
+        Version::V0x13,
+        params,
+    ))
+}
+
 pub fn hash_password(password: &str) -> Result<String> {
-    // In real applications you should never use a constant salt!!
-    // Checkout https://cheatsheetseries.owasp.org/cheatsheets/Password_Storage_Cheat_Sheet.html#salting for details
-    let salt = SaltString::from_b64("dec7901d02ee422ba6bd0333e4fef137")
-        .map_err(|e| miette!("Failed to create salt: {e}"))?;
-    Ok(Argon2::default()
+    hash_password_with(&argon2()?, password)
+}
+
+pub fn hash_password_with(argon2: &Argon2<'_>, password: &str) -> Result<String> {
+    let salt = SaltString::generate(&mut OsRng);
+
+    Ok(argon2
         .hash_password(password.as_bytes(), &salt)
         .map_err(|e| miette!("Failed to hash password: {e}"))?
         .to_string())
 }
 
+// The cost is read from the hash itself, so hashes made with older settings still verify
 pub fn verify_password(password: &str, hashed_password: &str) -> Result<bool> {
-    let parsed_hash = PasswordHash::new(&hashed_password)
+    let parsed_hash = PasswordHash::new(hashed_password)
         .map_err(|e| miette!("Failed to parse hashed password: {e}"))?;
     Ok(Argon2::default()
         .verify_password(password.as_bytes(), &parsed_hash)
         .is_ok())
 }
 
-pub fn create_jwt(user: User) -> Result<String> {
-    let env = env()?;
-    let now = SystemTime::now()
+pub fn password_needs_rehash(hashed_password: &str) -> Result<bool> {
+    password_needs_rehash_with(&argon2()?, hashed_password)
+}
+
+// True for the shared legacy salt, or a hash made with another algorithm or cost than the one
+// configured now
+pub fn password_needs_rehash_with(argon2: &Argon2<'_>, hashed_password: &str) -> Result<bool> {
+    let parsed_hash = PasswordHash::new(hashed_password)
+        .map_err(|e| miette!("Failed to parse hashed password: {e}"))?;
+
+    if parsed_hash.salt.map(|salt| salt.as_str()) == Some(LEGACY_SALT)
+        || parsed_hash.algorithm != Argon2Algorithm::Argon2id.ident()
+    {
+        return Ok(true);
+    }
+
+    let params = Params::try_from(&parsed_hash)
+        .map_err(|e| miette!("Failed to read the password hash parameters: {e}"))?;
+    let configured = argon2.params();
+
+    Ok(params.m_cost() != configured.m_cost()
+        || params.t_cost() != configured.t_cost()
+        || params.p_cost() != configured.p_cost())
+}
+
+// Access tokens only live for a few minutes, the refresh token stored in the session table is
+// what keeps someone logged in
+pub const ACCESS_TOKEN_TTL: u64 = 15 * 60;
+pub const REFRESH_TOKEN_TTL: u64 = 30 * 24 * 60 * 60;
+
+// The key tokens are signed and checked with, read from JWT_SIGNATURE when the router is built
+#[derive(Debug, Clone, Default)]
+pub struct JwtSignature(pub Option<String>);
+
+impl JwtSignature {
+    pub fn from_env() -> Self {
+        Self(env().ok().map(|env| env.jwt_signature))
+    }
+
+    pub fn key(&self) -> Result<&str> {
+        self.0
+            .as_deref()
+            .filter(|key| !key.is_empty())
+            .ok_or_else(|| miette!("Set JWT_SIGNATURE in your .env to sign login tokens"))
+    }
+}
+
+pub fn now_secs() -> Result<u64> {
+    Ok(SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .map_err(|e| miette!("Failed to get current time: {e}"))?
-        .as_secs();
+        .as_secs())
+}
+
+// The token carries the session it was issued for, so revoking the session revokes it too
+pub fn create_jwt(user: &User, session: i64, signature: &str) -> Result<String> {
+    let now = now_secs()?;
+    let user = User {
+        password: String::new(),
+        ..user.clone()
+    };
 
     let claims = Claims {
         sub: serde_json::to_string(&user)
             .map_err(|e| miette!("Failed to serialize User for JWT: {e}"))?,
-        exp: now + (24 * 60 * 60 * 504),
+        sid: session,
+        exp: now + ACCESS_TOKEN_TTL,
         iat: now,
     };
 
     encode(
-        &Header::default(),
+        &Header::new(Algorithm::HS256),
         &claims,
-        &EncodingKey::from_secret(env.jwt_signature.as_ref()),
+        &EncodingKey::from_secret(signature.as_ref()),
     )
     .map_err(|e| miette!("Failed to generate JWT: {e}"))
 }
 
-pub fn parse_jwt(token: &str) -> Result<User> {
-    let env = env()?;
-    let mut validation = Validation::default();
-
-    // Don't care about these because it's a self hosted app
-    validation.insecure_disable_signature_validation();
-    validation.validate_exp = false;
-
+pub fn parse_jwt(token: &str, signature: &str) -> Result<(User, i64)> {
     let token_data = decode::<Claims>(
         token,
-        &DecodingKey::from_secret(env.jwt_signature.as_ref()),
-        &validation,
+        &DecodingKey::from_secret(signature.as_ref()),
+        &Validation::new(Algorithm::HS256),
     )
     .map_err(|e| miette!("Invalid JWT token: {e}"))?;
 
-    let parsed_token = serde_json::from_str(&token_data.claims.sub)
+    let user = serde_json::from_str(&token_data.claims.sub)
         .map_err(|e| miette!("Failed to deserialize User from JWT: {e}"))?;
 
-    Ok(parsed_token)
+    Ok((user, token_data.claims.sid))
+}
+
+pub fn generate_refresh_token() -> Result<String> {
+    let mut token = [0_u8; 32];
+    SystemRandom::new()
+        .fill(&mut token)
+        .map_err(|_| miette!("Could not generate a refresh token"))?;
+
+    Ok(URL_SAFE_NO_PAD.encode(token))
+}
+
+// Personal tokens carry a prefix so they're told apart from session tokens, and easy to spot
+// when one leaks
+pub const API_TOKEN_PREFIX: &str = "gurl_pat_";
+
+pub fn generate_api_token() -> Result<String> {
+    Ok(format!("{API_TOKEN_PREFIX}{}", generate_refresh_token()?))
+}
+
+// Refresh and personal tokens are random enough that a plain digest is all the db needs
+pub fn hash_token(token: &str) -> String {
+    STANDARD.encode(digest(&SHA256, token.as_bytes()))
+}
+
+// Derived from the session, so it needs no storage and stops working when the session does
+pub fn csrf_token(signature: &str, session: i64) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, signature.as_bytes());
+
+    URL_SAFE_NO_PAD.encode(hmac::sign(&key, format!("csrf:{session}").as_bytes()))
+}
+
+pub fn verify_csrf_token(signature: &str, session: i64, token: &str) -> bool {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, signature.as_bytes());
+
+    URL_SAFE_NO_PAD
+        .decode(token)
+        .is_ok_and(|tag| hmac::verify(&key, format!("csrf:{session}").as_bytes(), &tag).is_ok())
 }
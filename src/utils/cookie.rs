@@ -0,0 +1,55 @@
+use axum::http::{HeaderMap, header::COOKIE};
+
+use crate::utils::{ACCESS_TOKEN_TTL, REFRESH_TOKEN_TTL};
+
+// The access token, sent with every request
+pub const SESSION_COOKIE: &str = "gurl_session";
+// Only sent to /api/user, where sessions are refreshed and ended
+pub const REFRESH_COOKIE: &str = "gurl_refresh";
+// Readable by the page, which echoes it in the X-CSRF-Token header of anything that changes data
+pub const CSRF_COOKIE: &str = "gurl_csrf";
+pub const CSRF_HEADER: &str = "x-csrf-token";
+
+// Set-Cookie values for a new or refreshed session
+pub fn session_cookies(access_token: &str, refresh_token: &str, csrf_token: &str) -> [String; 3] {
+    [
+        cookie(SESSION_COOKIE, access_token, "/", ACCESS_TOKEN_TTL, true),
+        cookie(
+            REFRESH_COOKIE,
+            refresh_token,
+            "/api/user",
+            REFRESH_TOKEN_TTL,
+            true,
+        ),
+        cookie(CSRF_COOKIE, csrf_token, "/", REFRESH_TOKEN_TTL, false),
+    ]
+}
+
+// Set-Cookie values that make the browser forget the session
+pub fn cleared_session_cookies() -> [String; 3] {
+    [
+        cookie(SESSION_COOKIE, "", "/", 0, true),
+        cookie(REFRESH_COOKIE, "", "/api/user", 0, true),
+        cookie(CSRF_COOKIE, "", "/", 0, false),
+    ]
+}
+
+pub fn read_cookie(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|header| header.to_str().ok())
+        .flat_map(|header| header.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(key, value)| *key == name && !value.is_empty())
+        .map(|(_, value)| value.to_string())
+}
+
+fn cookie(name: &str, value: &str, path: &str, max_age: u64, http_only: bool) -> String {
+    let http_only = match http_only {
+        true => "; HttpOnly",
+        false => "",
+    };
+
+    format!("{name}={value}; Path={path}; Max-Age={max_age}; SameSite=Strict{http_only}")
+}
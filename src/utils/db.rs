@@ -1,48 +1,104 @@
-use std::fs;
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
 
 use miette::{Result, miette};
-use rusqlite::Connection;
+use r2d2::{ManageConnection, Pool};
+use rusqlite::{Connection, OpenFlags};
 
-use crate::utils::env::env;
+use crate::utils::{env::env, migrate::migrate};
 
-pub fn db(init: bool, test: bool) -> Result<Connection> {
-    let connection = if test {
-        Connection::open_in_memory()
-            .map_err(|e| miette!("sqlite connection could not be opened: {e}"))?
-    } else {
-        let env = env()?;
-
-        if cfg!(feature = "docker") {
-            Connection::open(format!("/.docker-db/{}", env.db_name))
-                .map_err(|e| miette!("sqlite connection could not be opened: {e}"))?
-        } else {
-            Connection::open(env.db_name)
-                .map_err(|e| miette!("sqlite connection could not be opened: {e}"))?
+pub type DbPool = Pool<SqliteConnectionManager>;
+
+// Connections past this wait for one to be returned, sqlite only runs one write at a time anyway
+const POOL_SIZE: u32 = 8;
+// How long a write waits for another one to finish before giving up with SQLITE_BUSY
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+static TEST_DATABASES: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, Clone)]
+pub struct SqliteConnectionManager {
+    path: String,
+    memory: bool,
+}
+
+impl ManageConnection for SqliteConnectionManager {
+    type Connection = Connection;
+    type Error = rusqlite::Error;
+
+    fn connect(&self) -> Result<Connection, rusqlite::Error> {
+        let connection = Connection::open_with_flags(
+            &self.path,
+            OpenFlags::default() | OpenFlags::SQLITE_OPEN_URI,
+        )?;
+
+        connection.pragma_update(None, "foreign_keys", "ON")?;
+        connection.busy_timeout(BUSY_TIMEOUT)?;
+
+        // Readers don't wait on the writer in WAL mode, in memory databases don't have a journal
+        if !self.memory {
+            connection.pragma_update_and_check(None, "journal_mode", "WAL", |row| {
+                row.get::<_, String>(0)
+            })?;
         }
-    };
 
-    if init {
-        let db_path = match connection.path() {
-            Some("") => "in memory".to_string(),
-            Some(path) => format!("at {path}"),
-            None => panic!("sqlite connection path is non-existent"),
-        };
+        Ok(connection)
+    }
 
-        println!("sqlite database file located {db_path}");
+    fn is_valid(&self, connection: &mut Connection) -> Result<(), rusqlite::Error> {
+        connection.execute_batch("SELECT 1")
+    }
 
-        let db_initialized = connection
-            .table_exists(None, "request")
-            .map_err(|e| miette!("Could not query request table{e}"))?;
+    fn has_broken(&self, _: &mut Connection) -> bool {
+        false
+    }
+}
+
+// Where the database file is, docker keeps it in its mounted volume
+pub fn db_path() -> Result<String> {
+    let env = env()?;
 
-        if !db_initialized {
-            let init_script = fs::read_to_string("init.sql")
-                .map_err(|e| miette!("init.sql could not be read: {e}"))?;
+    Ok(match cfg!(feature = "docker") {
+        true => format!("/.docker-db/{}", env.db_name),
+        false => env.db_name,
+    })
+}
+
+pub fn db(init: bool, test: bool) -> Result<DbPool> {
+    let manager = if test {
+        // Every pooled connection opens the same in memory database, a new one for each call. The
+        // memdb vfs locks like a file does, unlike a shared cache that fails writes right away
+        let id = TEST_DATABASES.fetch_add(1, Ordering::Relaxed);
 
-            connection
-                .execute_batch(&init_script)
-                .map_err(|e| miette!("Could not initialize db with init script: {e}"))?;
+        SqliteConnectionManager {
+            path: format!("file:/gurl-test-{}-{id}?vfs=memdb", std::process::id()),
+            memory: true,
+        }
+    } else {
+        SqliteConnectionManager {
+            path: db_path()?,
+            memory: false,
         }
+    };
+
+    // An in memory database is gone once its last connection closes, so those are kept open
+    let lifetime = (!manager.memory).then_some(Duration::from_secs(30 * 60));
+
+    let pool = Pool::builder()
+        .max_size(POOL_SIZE)
+        .idle_timeout(lifetime)
+        .max_lifetime(lifetime)
+        .build(manager)
+        .map_err(|e| miette!("sqlite connection could not be opened: {e}"))?;
+
+    if init {
+        let connection = pool
+            .get()
+            .map_err(|e| miette!("sqlite connection could not be opened: {e}"))?;
+        migrate(&connection)?;
     }
 
-    Ok(connection)
+    Ok(pool)
 }
@@ -9,6 +9,16 @@ pub struct Env {
     pub port: String,
     #[validate(length(min = 1))]
     pub jwt_signature: String,
+    pub grpc_backend: String,
+    pub grpc_max_time: String,
+    pub response_max_size: String,
+    // Encrypts secret variables at rest, changing it makes the stored ones unreadable
+    pub secret_key: String,
+    pub assets_dir: String,
+    // Argon2 cost for new password hashes, older hashes are upgraded on login
+    pub argon2_memory_kib: String,
+    pub argon2_iterations: String,
+    pub argon2_parallelism: String,
 }
 
 pub fn env() -> Result<Env> {
@@ -17,12 +27,28 @@ pub fn env() -> Result<Env> {
         _ => {
             let db_name = env::var("DB_NAME").unwrap_or_default();
             let port = env::var("PORT").unwrap_or(9000.to_string());
-            let jwt_signature = env::var("JWT_SIGNATURE").unwrap_or("blah".to_string());
+            let jwt_signature = env::var("JWT_SIGNATURE").unwrap_or_default();
+            let grpc_backend = env::var("GRPC_BACKEND").unwrap_or("native".to_string());
+            let grpc_max_time = env::var("GRPC_MAX_TIME").unwrap_or(300.to_string());
+            let response_max_size = env::var("RESPONSE_MAX_SIZE").unwrap_or(1_048_576.to_string());
+            let secret_key = env::var("SECRET_KEY").unwrap_or_default();
+            let assets_dir = env::var("ASSETS_DIR").unwrap_or_default();
+            let argon2_memory_kib = env::var("ARGON2_MEMORY_KIB").unwrap_or_default();
+            let argon2_iterations = env::var("ARGON2_ITERATIONS").unwrap_or_default();
+            let argon2_parallelism = env::var("ARGON2_PARALLELISM").unwrap_or_default();
 
             Ok(Env {
                 db_name,
                 port,
                 jwt_signature,
+                grpc_backend,
+                grpc_max_time,
+                response_max_size,
+                secret_key,
+                assets_dir,
+                argon2_memory_kib,
+                argon2_iterations,
+                argon2_parallelism,
             })
         }
     }
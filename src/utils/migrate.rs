@@ -0,0 +1,251 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use include_dir::{Dir, include_dir};
+use miette::{Result, miette};
+use rusqlite::{Connection, params};
+
+static MIGRATIONS: Dir<'static> = include_dir!("$CARGO_MANIFEST_DIR/migrations");
+
+// Files in migrations/ named <version>_<name>.sql, applied in version order. A migration is never
+// edited once released, changes go in a new one
+pub fn migrations() -> Result<Vec<(u32, &'static str, &'static str)>> {
+    let mut migrations = MIGRATIONS
+        .files()
+        .map(|file| {
+            let path = file.path().display();
+            let (version, name) = file
+                .path()
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| stem.split_once('_'))
+                .ok_or_else(|| miette!("Migration {path} isn't named <version>_<name>.sql"))?;
+            let version = version
+                .parse::<u32>()
+                .map_err(|e| miette!("Migration {path} has an invalid version: {e}"))?;
+            let script = file
+                .contents_utf8()
+                .ok_or_else(|| miette!("Migration {path} isn't valid utf-8"))?;
+
+            Ok((version, name, script))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    migrations.sort_by_key(|(version, _, _)| *version);
+
+    Ok(migrations)
+}
+
+pub fn latest_schema_version() -> Result<u32> {
+    Ok(migrations()?
+        .last()
+        .map(|(version, _, _)| *version)
+        .unwrap_or_default())
+}
+
+pub fn schema_version(connection: &Connection) -> Result<u32> {
+    let tracked = connection
+        .table_exists(None, "schema_version")
+        .map_err(|e| miette!("Could not query schema_version table: {e}"))?;
+
+    if !tracked {
+        return Ok(0);
+    }
+
+    connection
+        .query_row("SELECT MAX(version) FROM schema_version", [], |row| {
+            row.get::<_, Option<u32>>(0)
+        })
+        .map(Option::unwrap_or_default)
+        .map_err(|e| miette!("Could not read the schema version: {e}"))
+}
+
+// Brings the database up to the latest migration and returns the version it ends at
+pub fn migrate(connection: &Connection) -> Result<u32> {
+    connection
+        .execute(
+            r#"
+            CREATE TABLE IF NOT EXISTS schema_version (
+                version INTEGER PRIMARY KEY,
+                name TEXT NOT NULL,
+                date TEXT NOT NULL
+            )
+        "#,
+            [],
+        )
+        .map_err(|e| miette!("Could not create schema_version table: {e}"))?;
+
+    let mut current = schema_version(connection)?;
+
+    // Installs from before versioning have tables but no version, they're caught up to the
+    // first migration in place instead of running it
+    let untracked = connection
+        .table_exists(None, "request")
+        .map_err(|e| miette!("Could not query request table: {e}"))?;
+    if current == 0 && untracked {
+        let transaction = connection
+            .unchecked_transaction()
+            .map_err(|e| miette!("Could not start the legacy upgrade: {e}"))?;
+
+        upgrade_legacy(&transaction)?;
+        record_migration(&transaction, 1, "init")?;
+
+        transaction
+            .commit()
+            .map_err(|e| miette!("Could not finish the legacy upgrade: {e}"))?;
+        current = 1;
+    }
+
+    for (version, name, script) in migrations()? {
+        if version <= current {
+            continue;
+        }
+
+        let transaction = connection
+            .unchecked_transaction()
+            .map_err(|e| miette!("Could not start migration {version}_{name}: {e}"))?;
+
+        transaction
+            .execute_batch(script)
+            .map_err(|e| miette!("Migration {version}_{name} failed: {e}"))?;
+        record_migration(&transaction, version, name)?;
+
+        transaction
+            .commit()
+            .map_err(|e| miette!("Could not finish migration {version}_{name}: {e}"))?;
+        current = version;
+    }
+
+    Ok(current)
+}
+
+fn record_migration(connection: &Connection, version: u32, name: &str) -> Result<()> {
+    let date = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis()
+        .to_string();
+
+    connection
+        .execute(
+            "INSERT INTO schema_version (version, name, date) VALUES (?1, ?2, ?3)",
+            params![version, name, date],
+        )
+        .map_err(|e| miette!("Could not record migration {version}_{name}: {e}"))?;
+
+    Ok(())
+}
+
+// Everything 0001_init added since the first release, for databases it didn't create. Its script
+// only creates what's missing, so it runs as is once the columns it can't add are in place
+fn upgrade_legacy(connection: &Connection) -> Result<()> {
+    let init = migrations()?
+        .into_iter()
+        .find(|(version, _, _)| *version == 1)
+        .map(|(_, _, script)| script)
+        .ok_or_else(|| miette!("Migration 0001_init is missing"))?;
+
+    add_missing_columns(
+        connection,
+        "request",
+        &[
+            ("status_message", "TEXT"),
+            ("status_details", "TEXT"),
+            ("headers", "TEXT"),
+            ("trailers", "TEXT"),
+            ("total_ms", "REAL"),
+            ("connect_ms", "REAL"),
+            ("first_byte_ms", "REAL"),
+            ("response", "BLOB"),
+            ("response_size", "INTEGER"),
+            ("response_truncated", "INTEGER"),
+            ("resolved_command", "TEXT"),
+        ],
+    )?;
+    connection
+        .execute_batch(init)
+        .map_err(|e| miette!("Could not create the missing tables: {e}"))?;
+    add_missing_columns(connection, "environment_variable", &[("secret", "INTEGER")])?;
+    migrate_favorites(connection)?;
+
+    Ok(())
+}
+
+// Favorites used to be a CSV of request ids in "user".favorites, they're moved to the favorite
+// table in the same order, skipping ids of requests that are gone, hidden or someone else's
+pub fn migrate_favorites(connection: &Connection) -> Result<()> {
+    let has_csv = connection
+        .prepare(r#"SELECT 1 FROM pragma_table_info('user') WHERE name = 'favorites'"#)
+        .map_err(|e| miette!("Invalid statement: {e}"))?
+        .exists([])
+        .map_err(|e| miette!("Could not query user columns: {e}"))?;
+
+    if !has_csv {
+        return Ok(());
+    }
+
+    let users = connection
+        .prepare(r#"SELECT email, favorites FROM "user" WHERE favorites IS NOT NULL"#)
+        .map_err(|e| miette!("Invalid statement: {e}"))?
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })
+        .map_err(|e| miette!("Could not query favorites: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| miette!("Could not read favorites: {e}"))?;
+
+    let date = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis()
+        .to_string();
+
+    for (email, favorites) in users {
+        let favorites = favorites
+            .split(',')
+            .filter_map(|id| id.trim().parse::<i32>().ok());
+
+        for (position, request_id) in favorites.enumerate() {
+            connection
+                .execute(
+                    r#"
+                    INSERT INTO favorite (user_email, request_id, position, date)
+                    SELECT ?1, id, ?3, ?4 FROM request
+                    WHERE id = ?2 AND user_email = ?1 AND hidden = false
+                    ON CONFLICT (user_email, request_id) DO NOTHING
+                "#,
+                    params![email, request_id, position, date],
+                )
+                .map_err(|e| miette!("Could not migrate favorite {request_id}: {e}"))?;
+        }
+    }
+
+    connection
+        .execute(r#"ALTER TABLE "user" DROP COLUMN favorites"#, [])
+        .map_err(|e| miette!("Could not drop the favorites column: {e}"))?;
+
+    Ok(())
+}
+
+// Databases created before a column was added get it appended, in the same order
+fn add_missing_columns(
+    connection: &Connection,
+    table: &str,
+    columns: &[(&str, &str)],
+) -> Result<()> {
+    let existing = connection
+        .prepare(&format!("SELECT name FROM pragma_table_info('{table}')"))
+        .map_err(|e| miette!("Invalid statement: {e}"))?
+        .query_map([], |row| row.get::<_, String>(0))
+        .map_err(|e| miette!("Could not query {table} columns: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| miette!("Could not read {table} columns: {e}"))?;
+
+    for (name, kind) in columns {
+        if !existing.iter().any(|column| column == name) {
+            connection
+                .execute(&format!("ALTER TABLE {table} ADD COLUMN {name} {kind}"), [])
+                .map_err(|e| miette!("Could not add column {name} to {table}: {e}"))?;
+        }
+    }
+
+    Ok(())
+}
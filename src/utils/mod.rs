@@ -1,7 +1,17 @@
+pub mod assets;
 pub mod auth;
+pub mod cookie;
 pub mod db;
 pub mod env;
+pub mod migrate;
+pub mod secret;
+pub mod variables;
 
+pub use assets::*;
 pub use auth::*;
+pub use cookie::*;
 pub use db::*;
 pub use env::*;
+pub use migrate::*;
+pub use secret::*;
+pub use variables::*;
@@ -0,0 +1,98 @@
+use base64::{Engine, engine::general_purpose::STANDARD};
+use miette::{Result, miette};
+use regex::{Captures, Regex};
+use ring::{
+    aead::{AES_256_GCM, Aad, LessSafeKey, NONCE_LEN, Nonce, UnboundKey},
+    digest::{SHA256, digest},
+    rand::{SecureRandom, SystemRandom},
+};
+
+use crate::utils::{env::env, has_variables};
+
+pub const SECRET_MASK: &str = "****";
+
+// Header values that are credentials even when they're typed into the command as is
+const SENSITIVE_HEADERS: &str =
+    "authorization|proxy-authorization|cookie|x-api-key|api-key|x-auth-token";
+
+pub fn secret_key() -> Result<String> {
+    let key = env()?.secret_key;
+
+    match key.is_empty() {
+        true => Err(miette!(
+            "Set SECRET_KEY in your .env to store secret variables"
+        )),
+        false => Ok(key),
+    }
+}
+
+// AES-256-GCM with a key derived from the server key, stored as base64 of nonce + ciphertext
+pub fn encrypt_secret(key: &str, plaintext: &str) -> Result<String> {
+    let mut nonce = [0_u8; NONCE_LEN];
+    SystemRandom::new()
+        .fill(&mut nonce)
+        .map_err(|_| miette!("Could not generate a nonce for the secret"))?;
+
+    let mut sealed = plaintext.as_bytes().to_vec();
+    cipher(key)?
+        .seal_in_place_append_tag(
+            Nonce::assume_unique_for_key(nonce),
+            Aad::empty(),
+            &mut sealed,
+        )
+        .map_err(|_| miette!("Could not encrypt the secret"))?;
+
+    Ok(STANDARD.encode([nonce.as_slice(), &sealed].concat()))
+}
+
+pub fn decrypt_secret(key: &str, stored: &str) -> Result<String> {
+    let bytes = STANDARD
+        .decode(stored)
+        .map_err(|e| miette!("Stored secret isn't base64: {e}"))?;
+    if bytes.len() < NONCE_LEN {
+        return Err(miette!("Stored secret is too short"));
+    }
+
+    let (nonce, sealed) = bytes.split_at(NONCE_LEN);
+    let nonce = Nonce::try_assume_unique_for_key(nonce)
+        .map_err(|_| miette!("Stored secret has an invalid nonce"))?;
+    let mut sealed = sealed.to_vec();
+    let plaintext = cipher(key)?
+        .open_in_place(nonce, Aad::empty(), &mut sealed)
+        .map_err(|_| miette!("Could not decrypt a secret variable, was SECRET_KEY changed?"))?;
+
+    String::from_utf8(plaintext.to_vec()).map_err(|e| miette!("Secret isn't valid utf-8: {e}"))
+}
+
+fn cipher(key: &str) -> Result<LessSafeKey> {
+    let key = digest(&SHA256, key.as_bytes());
+    let key = UnboundKey::new(&AES_256_GCM, key.as_ref())
+        .map_err(|_| miette!("Could not create the secret cipher"))?;
+
+    Ok(LessSafeKey::new(key))
+}
+
+// Credentials typed straight into a header are masked too, unless they come from {{variables}}.
+// Quoted headers run to the closing quote, unquoted ones to the end of the word
+pub fn mask_sensitive_headers(command: &str) -> Result<String> {
+    let patterns = [
+        format!(r#"(?i)(['"]\s*(?:{SENSITIVE_HEADERS})\s*:\s*)([^'"\n]+)"#),
+        format!(r#"(?i)((?:^|\s)(?:{SENSITIVE_HEADERS}):)([^\s'"]+)"#),
+    ];
+
+    patterns
+        .iter()
+        .try_fold(command.to_string(), |command, pattern| {
+            let pattern =
+                Regex::new(pattern).map_err(|e| miette!("Invalid header pattern: {e}"))?;
+
+            Ok(pattern
+                .replace_all(&command, |captures: &Captures<'_>| {
+                    match has_variables(&captures[2]) {
+                        true => captures[0].to_string(),
+                        false => format!("{}{SECRET_MASK}", &captures[1]),
+                    }
+                })
+                .to_string())
+        })
+}
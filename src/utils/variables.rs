@@ -0,0 +1,58 @@
+use miette::{Result, miette};
+use regex::{Captures, Regex};
+
+use crate::grpc::GrpcCommand;
+
+// {{name}} placeholders, spaces inside the braces are allowed
+pub fn variable_pattern() -> Result<Regex> {
+    Regex::new(r"\{\{\s*([A-Za-z0-9_.-]+)\s*\}\}")
+        .map_err(|e| miette!("Invalid variable pattern: {e}"))
+}
+
+pub fn has_variables(template: &str) -> bool {
+    variable_pattern().is_ok_and(|pattern| pattern.is_match(template))
+}
+
+// Replaces every placeholder with its value, all unknown names are reported at once
+pub fn interpolate(template: &str, variables: &[(String, String)]) -> Result<String> {
+    let pattern = variable_pattern()?;
+    let mut missing = Vec::new();
+
+    let resolved = pattern.replace_all(template, |captures: &Captures<'_>| {
+        let name = &captures[1];
+
+        match variables.iter().find(|(key, _)| key == name) {
+            Some((_, value)) => value.clone(),
+            None => {
+                if !missing.contains(&name.to_string()) {
+                    missing.push(name.to_string());
+                }
+                captures[0].to_string()
+            }
+        }
+    });
+
+    if !missing.is_empty() {
+        return Err(miette!(
+            "Unknown variable(s) {}, add them to the active environment",
+            missing
+                .iter()
+                .map(|name| format!("{{{{{name}}}}}"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+
+    Ok(resolved.to_string())
+}
+
+// Fills in a command's placeholders after it's parsed, one argument at a time, so a value with
+// spaces, quotes or a leading dash stays part of the argument it was written in
+pub fn interpolate_command(template: &str, variables: &[(String, String)]) -> Result<String> {
+    // Unknown names are reported for the whole command at once
+    interpolate(template, variables)?;
+
+    Ok(GrpcCommand::parse(template)?
+        .map_args(|arg| interpolate(arg, variables))?
+        .to_command_line())
+}
@@ -0,0 +1,871 @@
+// tokio::test builds its runtime with expect
+#![allow(clippy::unwrap_in_result)]
+
+use std::{net::SocketAddr, time::Duration};
+
+use axum::body::{Body, to_bytes};
+use miette::{IntoDiagnostic, Result, miette};
+use prost_reflect::DescriptorPool;
+use tokio::net::TcpListener;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::transport::Server;
+use tower::util::ServiceExt;
+
+use gURL::{
+    db,
+    grpc::{
+        Backend, CompletionContext, DescriptorCache, Execution, GrpcCommand, GrpcResponse,
+        InFlightCalls, ProtoSource, StreamEvent, StreamSession, StreamSessions, Verb,
+        attach_protoset, build_protoset, completion_context, execute, message_skeleton,
+        parse_messages, start, start_with_deadline, tokenize, unsupported_flag, uses_registry,
+    },
+    handlers::render_call_result,
+    init_router,
+};
+
+async fn test_grpc_server() -> Result<SocketAddr> {
+    let listener = TcpListener::bind("127.0.0.1:0").await.into_diagnostic()?;
+    let address = listener.local_addr().into_diagnostic()?;
+
+    let (reporter, health) = tonic_health::server::health_reporter();
+    reporter
+        .set_service_status("gurl.Test", tonic_health::ServingStatus::Serving)
+        .await;
+
+    let reflection = tonic_reflection::server::Builder::configure()
+        .register_encoded_file_descriptor_set(tonic_health::pb::FILE_DESCRIPTOR_SET)
+        .build_v1()
+        .into_diagnostic()?;
+
+    tokio::spawn(
+        Server::builder()
+            .add_service(health)
+            .add_service(reflection)
+            .serve_with_incoming(TcpListenerStream::new(listener)),
+    );
+
+    Ok(address)
+}
+
+fn parse(command: &str) -> Result<GrpcCommand> {
+    GrpcCommand::parse(command)
+}
+
+#[tokio::test]
+async fn test_native_list_services() -> Result<()> {
+    let address = test_grpc_server().await?;
+    let res = execute(
+        &parse(&format!("-plaintext {address} list"))?,
+        Backend::Native,
+    )
+    .await?;
+
+    assert_eq!(res.status, "OK");
+    assert!(res.body_text().contains("grpc.health.v1.Health"));
+    assert!(
+        res.body_text()
+            .contains("grpc.reflection.v1.ServerReflection")
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_native_describe_service() -> Result<()> {
+    let address = test_grpc_server().await?;
+    let res = execute(
+        &parse(&format!(
+            "-plaintext {address} describe grpc.health.v1.Health"
+        ))?,
+        Backend::Native,
+    )
+    .await?;
+
+    assert!(
+        res.body_text()
+            .contains("grpc.health.v1.Health is a service:")
+    );
+    assert!(res.body_text().contains(
+        "rpc Watch ( .grpc.health.v1.HealthCheckRequest ) returns ( stream .grpc.health.v1.HealthCheckResponse );"
+    ));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_native_unary_call() -> Result<()> {
+    let address = test_grpc_server().await?;
+    let res = execute(
+        &parse(&format!(
+            r#"-plaintext -d '{{"service": "gurl.Test"}}' {address} grpc.health.v1.Health/Check"#
+        ))?,
+        Backend::Native,
+    )
+    .await?;
+
+    assert_eq!(res.status, "OK");
+    assert_eq!(res.method.as_deref(), Some("Check"));
+    assert!(res.body_text().contains(r#""status": "SERVING""#));
+    assert!(
+        res.headers
+            .iter()
+            .any(|(name, value)| name == "content-type" && value == "application/grpc")
+    );
+    assert!(res.timings.total_ms.is_some());
+    assert!(res.timings.connect_ms.is_some());
+    assert!(res.timings.first_byte_ms.is_some());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_native_call_error_status() -> Result<()> {
+    let address = test_grpc_server().await?;
+    let res = execute(
+        &parse(&format!(
+            "-plaintext -d @ {address} grpc.health.v1.Health.Check <<EOF\n{{\"service\": \"missing\"}}\nEOF"
+        ))?,
+        Backend::Native,
+    )
+    .await?;
+
+    assert_eq!(res.status, "NOT_FOUND");
+    assert!(res.body.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_native_server_streaming() -> Result<()> {
+    let address = test_grpc_server().await?;
+    let Execution::Streaming(mut call) = start(
+        &parse(&format!(
+            r#"-plaintext -d '{{"service": "gurl.Test"}}' {address} grpc.health.v1.Health/Watch"#
+        ))?,
+        Backend::Native,
+    )
+    .await?
+    else {
+        return Err(miette!("Watch should keep streaming"));
+    };
+
+    assert!(call.sender.is_none());
+    assert!(matches!(
+        call.events.recv().await,
+        Some(StreamEvent::Headers { .. })
+    ));
+    match call.events.recv().await {
+        Some(StreamEvent::Message { body }) => assert!(body.contains(r#""status": "SERVING""#)),
+        event => return Err(miette!("Expected a message, got {event:?}")),
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_native_bidi_streaming_session() -> Result<()> {
+    let address = test_grpc_server().await?;
+    let Execution::Streaming(call) = start(
+        &parse(&format!(
+            r#"-plaintext -d '{{"listServices": ""}}' {address} grpc.reflection.v1.ServerReflection/ServerReflectionInfo"#
+        ))?,
+        Backend::Native,
+    )
+    .await?
+    else {
+        return Err(miette!("ServerReflectionInfo should keep streaming"));
+    };
+
+    let streams = StreamSessions::default();
+    let id = streams.insert(StreamSession {
+        owner: "a@b.c".to_string(),
+        input: call.method.input(),
+        allow_unknown_fields: false,
+        events: Some(call.events),
+        sender: call.sender,
+        finished: false,
+    })?;
+    assert!(streams.take_events("d@e.f", &id).is_err());
+    assert!(streams.send("d@e.f", &id, "{}").is_err());
+    assert!(streams.close_send("d@e.f", &id).is_err());
+    let mut events = streams.take_events("a@b.c", &id)?;
+    assert!(streams.take_events("a@b.c", &id).is_err());
+
+    assert_eq!(
+        streams.send(
+            "a@b.c",
+            &id,
+            r#"{"fileContainingSymbol": "grpc.health.v1.Health"} {"listServices": ""}"#
+        )?,
+        2
+    );
+    assert!(streams.send("a@b.c", &id, r#"{"unknown": 1}"#).is_err());
+    streams.close_send("a@b.c", &id)?;
+
+    let mut messages = Vec::new();
+    let mut status = None;
+    while let Some(event) = events.recv().await {
+        match event {
+            StreamEvent::Message { body } => messages.push(body),
+            StreamEvent::Status { status: code, .. } => status = Some(code),
+            StreamEvent::Headers { .. } => {}
+        }
+    }
+
+    assert_eq!(messages.len(), 3);
+    assert!(messages[1].contains("fileDescriptorResponse"));
+    assert_eq!(status.as_deref(), Some("OK"));
+
+    streams.finish(&id)?;
+    assert!(streams.send("a@b.c", &id, "{}").is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_native_call_deadline() -> Result<()> {
+    let address = test_grpc_server().await?;
+    let mut command = parse(&format!(
+        r#"-plaintext -max-time 30 -d '{{"service": "gurl.Test"}}' {address} grpc.health.v1.Health/Watch"#
+    ))?;
+    command.limit_max_time(Duration::from_millis(200));
+    assert_eq!(command.max_time(), Some(Duration::from_millis(200)));
+
+    let res = execute(&command, Backend::Native).await?;
+
+    assert_eq!(res.status, "DEADLINE_EXCEEDED");
+    assert_eq!(res.body.len(), 1);
+
+    command.limit_max_time(Duration::from_secs(60));
+    assert_eq!(command.max_time(), Some(Duration::from_millis(200)));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_reflection_is_bounded_by_the_deadline() -> Result<()> {
+    // Accepts connections but never speaks HTTP/2, so reflection would wait forever
+    let listener = TcpListener::bind("127.0.0.1:0").await.into_diagnostic()?;
+    let address = listener.local_addr().into_diagnostic()?;
+    let command = parse(&format!("-plaintext {address} grpc.health.v1.Health/Check"))?;
+
+    let Execution::Complete(res) =
+        start_with_deadline(&command, Backend::Native, Duration::from_millis(200)).await?
+    else {
+        return Err(miette!("A call that never started can't be streaming"));
+    };
+
+    assert_eq!(res.status, "DEADLINE_EXCEEDED");
+    assert_eq!(res.method.as_deref(), Some("Check"));
+    drop(listener);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cancel_in_flight_call() -> Result<()> {
+    let calls = InFlightCalls::default();
+    let (id, cancelled) = calls.register("a@b.c", Some("call".to_string()))?;
+
+    assert_eq!(id, "call");
+    assert!(calls.register("a@b.c", Some("call".to_string())).is_err());
+    assert!(calls.cancel("d@e.f", &id).is_err());
+    calls.cancel("a@b.c", &id)?;
+    assert!(cancelled.await.is_ok());
+    assert!(calls.cancel("a@b.c", &id).is_err());
+
+    assert!(calls.register("a@b.c", Some(String::new())).is_err());
+    assert!(calls.register("a@b.c", Some("<b>".to_string())).is_err());
+    assert!(calls.register("a@b.c", Some("a".repeat(65))).is_err());
+
+    let (id, cancelled) = calls.register("a@b.c", None)?;
+    let (other, _) = calls.register("a@b.c", None)?;
+    assert_eq!(id.len(), 22);
+    assert_ne!(id, other);
+    calls.remove("a@b.c", &id)?;
+    assert!(cancelled.await.is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_descriptor_cache() -> Result<()> {
+    let address = test_grpc_server().await?;
+    let cache = DescriptorCache::default();
+    let command = parse(&format!("-plaintext {address} list"))?;
+
+    let pool = cache.get_or_load(&command, None, false).await?;
+    assert!(pool.get_service_by_name("grpc.health.v1.Health").is_some());
+
+    let cached = cache.get_or_load(&command, None, false).await?;
+    assert_eq!(pool, cached);
+
+    let refreshed = cache.get_or_load(&command, None, true).await?;
+    assert!(
+        refreshed
+            .get_service_by_name("grpc.health.v1.Health")
+            .is_some()
+    );
+
+    // A target that never answers fails within its -max-time, and asking again right away
+    // gets the same failure without another dial
+    let listener = TcpListener::bind("127.0.0.1:0").await.into_diagnostic()?;
+    let silent = parse(&format!(
+        "-plaintext -max-time 0.2 {} list",
+        listener.local_addr().into_diagnostic()?
+    ))?;
+
+    let error = cache.get_or_load(&silent, None, false).await.err();
+    assert!(error.is_some_and(|e| e.to_string().contains("longer than 0.2s")));
+
+    let started = std::time::Instant::now();
+    assert!(cache.get_or_load(&silent, None, false).await.is_err());
+    assert!(started.elapsed() < Duration::from_millis(100));
+    drop(listener);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_service_browser() -> Result<()> {
+    let address = test_grpc_server().await?;
+    let router = init_router(db(true, true)?);
+
+    let body = serde_json::json!({
+        "target": format!("-plaintext {address}"),
+        "refresh": "true",
+    });
+    let res = router
+        .oneshot(
+            axum::http::Request::builder()
+                .uri("/handle/request/browse")
+                .method("POST")
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .into_diagnostic()?,
+        )
+        .await
+        .into_diagnostic()?;
+    let html = String::from_utf8(
+        to_bytes(res.into_body(), usize::MAX)
+            .await
+            .into_diagnostic()?
+            .to_vec(),
+    )
+    .into_diagnostic()?;
+
+    assert!(html.contains("grpc.health.v1.Health"));
+    assert!(html.contains(&format!(
+        r#"data-command="-plaintext {address} grpc.health.v1.Health/Check""#
+    )));
+    assert!(html.contains("message HealthCheckRequest {"));
+
+    Ok(())
+}
+
+const HEALTH_PROTO: &str = r#"
+    syntax = "proto3";
+    package grpc.health.v1;
+
+    import "grpc/health/v1/status.proto";
+
+    message HealthCheckRequest {
+        string service = 1;
+    }
+
+    message HealthCheckResponse {
+        ServingStatus status = 1;
+    }
+
+    service Health {
+        rpc Check(HealthCheckRequest) returns (HealthCheckResponse);
+    }
+"#;
+
+const STATUS_PROTO: &str = r#"
+    syntax = "proto3";
+    package grpc.health.v1;
+
+    import "google/protobuf/empty.proto";
+
+    enum ServingStatus {
+        UNKNOWN = 0;
+        SERVING = 1;
+        NOT_SERVING = 2;
+        SERVICE_UNKNOWN = 3;
+    }
+
+    message Unused {
+        google.protobuf.Empty empty = 1;
+    }
+"#;
+
+#[tokio::test]
+async fn test_registry_protoset_call() -> Result<()> {
+    let address = test_grpc_server().await?;
+    let protoset = build_protoset(&[
+        ProtoSource::Proto {
+            name: "grpc/health/v1/health.proto".to_string(),
+            source: HEALTH_PROTO.to_string(),
+        },
+        ProtoSource::Proto {
+            name: "grpc/health/v1/status.proto".to_string(),
+            source: STATUS_PROTO.to_string(),
+        },
+    ])?;
+
+    let mut command = parse(&format!(
+        r#"-plaintext -d '{{"service": "gurl.Test"}}' {address} grpc.health.v1.Health/Check"#
+    ))?;
+    assert!(uses_registry(&command));
+
+    let _file = attach_protoset(&mut command, &protoset)?;
+    assert!(!uses_registry(&command));
+
+    let res = execute(&command, Backend::Native).await?;
+    assert_eq!(res.status, "OK");
+    assert!(res.body_text().contains("SERVING"));
+
+    assert!(
+        build_protoset(&[ProtoSource::Proto {
+            name: "../health.proto".to_string(),
+            source: HEALTH_PROTO.to_string(),
+        }])
+        .is_err()
+    );
+    assert!(
+        build_protoset(&[ProtoSource::Proto {
+            name: "health.proto".to_string(),
+            source: HEALTH_PROTO.to_string(),
+        }])
+        .is_err()
+    );
+
+    Ok(())
+}
+
+const SKELETON_PROTO: &str = r#"
+    syntax = "proto3";
+    package acme.v1;
+
+    import "google/protobuf/timestamp.proto";
+    import "google/protobuf/wrappers.proto";
+
+    enum Color {
+        COLOR_UNSPECIFIED = 0;
+        RED = 1;
+    }
+
+    message Node {
+        string name = 1;
+        repeated Node children = 2;
+    }
+
+    message CreateRequest {
+        int64 id = 1;
+        Color color = 2;
+        repeated string tags = 3;
+        map<string, int32> counts = 4;
+        oneof target {
+            string user_name = 5;
+            int32 group_id = 6;
+        }
+        google.protobuf.Timestamp created_at = 7;
+        google.protobuf.StringValue note = 8;
+        Node root = 9;
+        optional bool dry_run = 10;
+    }
+"#;
+
+#[test]
+fn test_message_skeleton() -> Result<()> {
+    let protoset = build_protoset(&[ProtoSource::Proto {
+        name: "acme/v1/create.proto".to_string(),
+        source: SKELETON_PROTO.to_string(),
+    }])?;
+    let pool = DescriptorPool::decode(protoset.as_slice()).into_diagnostic()?;
+    let message = pool
+        .get_message_by_name("acme.v1.CreateRequest")
+        .ok_or_else(|| miette!("Missing CreateRequest"))?;
+
+    let skeleton = message_skeleton(&message);
+    assert_eq!(
+        skeleton.json,
+        serde_json::json!({
+            "id": "0",
+            "color": "COLOR_UNSPECIFIED",
+            "tags": [""],
+            "counts": { "key": 0 },
+            "userName": "",
+            "createdAt": "1970-01-01T00:00:00Z",
+            "note": "",
+            "root": { "name": "", "children": [{}] },
+            "dryRun": false
+        })
+    );
+    assert_eq!(
+        skeleton.notes,
+        vec![
+            "color: one of COLOR_UNSPECIFIED, RED".to_string(),
+            "target: set only one of userName, groupId".to_string(),
+        ]
+    );
+
+    // The placeholder has to be a request the method accepts as is
+    let data = serde_json::to_string(&skeleton.json).into_diagnostic()?;
+    assert_eq!(
+        parse_messages(&message, Some(&data), false, false)?.len(),
+        1
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_request_skeleton_route() -> Result<()> {
+    let address = test_grpc_server().await?;
+    let router = init_router(db(true, true)?);
+
+    let body = serde_json::json!({
+        "command": format!("grpcurl -plaintext {address} grpc.health.v1.Health/Check"),
+    });
+    let res = router
+        .oneshot(
+            axum::http::Request::builder()
+                .uri("/grpcurl/skeleton")
+                .method("POST")
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .into_diagnostic()?,
+        )
+        .await
+        .into_diagnostic()?;
+    let bytes = to_bytes(res.into_body(), usize::MAX)
+        .await
+        .into_diagnostic()?;
+    let skeleton = serde_json::from_slice::<serde_json::Value>(&bytes).into_diagnostic()?;
+    let command = skeleton["command"].as_str().unwrap_or_default();
+
+    assert_eq!(
+        command,
+        format!(
+            "-plaintext -d '{{\n  \"service\": \"\"\n}}' {address} grpc.health.v1.Health/Check"
+        )
+    );
+    assert_eq!(
+        parse(command)?.data.as_deref(),
+        Some("{\n  \"service\": \"\"\n}")
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_request_skeleton_keeps_variables() -> Result<()> {
+    let address = test_grpc_server().await?;
+    let router = init_router(db(true, true)?);
+
+    for (uri, method, body) in [
+        (
+            "/api/environment/variable",
+            "PUT",
+            serde_json::json!({ "environment": "local", "key": "host", "value": address.to_string() }),
+        ),
+        (
+            "/api/environment/active",
+            "PATCH",
+            serde_json::json!({ "name": "local" }),
+        ),
+    ] {
+        router
+            .clone()
+            .oneshot(
+                axum::http::Request::builder()
+                    .uri(uri)
+                    .method(method)
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .into_diagnostic()?,
+            )
+            .await
+            .into_diagnostic()?;
+    }
+
+    let body = serde_json::json!({
+        "command": "grpcurl -plaintext {{host}} grpc.health.v1.Health/Check",
+    });
+    let res = router
+        .oneshot(
+            axum::http::Request::builder()
+                .uri("/grpcurl/skeleton")
+                .method("POST")
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .into_diagnostic()?,
+        )
+        .await
+        .into_diagnostic()?;
+    let bytes = to_bytes(res.into_body(), usize::MAX)
+        .await
+        .into_diagnostic()?;
+    let skeleton = serde_json::from_slice::<serde_json::Value>(&bytes).into_diagnostic()?;
+
+    assert_eq!(
+        skeleton["command"],
+        "-plaintext -d '{\n  \"service\": \"\"\n}' '{{host}}' grpc.health.v1.Health/Check"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_completion_context() -> Result<()> {
+    assert_eq!(
+        completion_context("grpcurl -plain", 14),
+        CompletionContext::Flag {
+            from: 8,
+            prefix: "-plain".to_string()
+        }
+    );
+    assert_eq!(
+        completion_context("-plaintext loc", 14),
+        CompletionContext::Address {
+            from: 11,
+            prefix: "loc".to_string()
+        }
+    );
+
+    let CompletionContext::Symbol {
+        from,
+        prefix,
+        target: Some(target),
+        methods: true,
+    } = completion_context("-plaintext localhost:50051 grpc.he", 34)
+    else {
+        return Err(miette!("Expected a symbol completion"));
+    };
+    assert_eq!((from, prefix.as_str()), (27, "grpc.he"));
+    assert_eq!(target.address.as_deref(), Some("localhost:50051"));
+
+    let command =
+        r#"-plaintext -d '{"service": "a", "nested": {"ch": 1}, "na' localhost:50051 pkg.Svc/Call"#;
+    let CompletionContext::Field {
+        from,
+        prefix,
+        path,
+        used,
+        quoted: true,
+        command: Some(target),
+    } = completion_context(command, 56)
+    else {
+        return Err(miette!("Expected a field completion"));
+    };
+    assert_eq!((from, prefix.as_str()), (54, "na"));
+    assert!(path.is_empty());
+    assert_eq!(used, vec!["service", "nested"]);
+    assert_eq!(target.symbol.as_deref(), Some("pkg.Svc/Call"));
+
+    let CompletionContext::Field { path, quoted, .. } =
+        completion_context(r#"-d '{"items": [{"a": 1, "#, 24)
+    else {
+        return Err(miette!("Expected a nested field completion"));
+    };
+    assert_eq!(path, vec!["items"]);
+    assert!(!quoted);
+
+    assert_eq!(
+        completion_context(r#"-d '{"service": "gu"#, 19),
+        CompletionContext::None
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_complete_route() -> Result<()> {
+    let address = test_grpc_server().await?;
+    let router = init_router(db(true, true)?);
+
+    let complete = async |command: String, cursor: usize| -> Result<serde_json::Value> {
+        let body = serde_json::json!({ "command": command, "cursor": cursor });
+        let res = router
+            .clone()
+            .oneshot(
+                axum::http::Request::builder()
+                    .uri("/grpcurl/complete")
+                    .method("POST")
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .into_diagnostic()?,
+            )
+            .await
+            .into_diagnostic()?;
+        let bytes = to_bytes(res.into_body(), usize::MAX)
+            .await
+            .into_diagnostic()?;
+
+        serde_json::from_slice::<serde_json::Value>(&bytes).into_diagnostic()
+    };
+
+    let history = serde_json::json!({
+        "command": format!("-plaintext {address} list"),
+        "status": "OK",
+        "method": "list",
+        "date": "1700000000000",
+        "hidden": false
+    });
+    router
+        .clone()
+        .oneshot(
+            axum::http::Request::builder()
+                .uri("/api/request")
+                .method("POST")
+                .header("content-type", "application/json")
+                .body(Body::from(history.to_string()))
+                .into_diagnostic()?,
+        )
+        .await
+        .into_diagnostic()?;
+
+    let res = complete("-plaintext 127.0".to_string(), 16).await?;
+    assert_eq!(res["from"], 11);
+    assert_eq!(res["completions"][0]["value"], address.to_string());
+    assert_eq!(res["completions"][0]["kind"], "address");
+
+    let command = format!("-plaintext {address} grpc.health.v1.Health/");
+    let res = complete(command.clone(), command.len()).await?;
+    let values = res["completions"]
+        .as_array()
+        .map(|completions| {
+            completions
+                .iter()
+                .map(|completion| completion["value"].clone())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    assert_eq!(
+        values,
+        vec!["grpc.health.v1.Health/Check", "grpc.health.v1.Health/Watch"]
+    );
+
+    let command = format!(r#"-plaintext -d '{{"se' {address} grpc.health.v1.Health/Check"#);
+    let res = complete(command, 19).await?;
+    assert_eq!(res["from"], 17);
+    assert_eq!(res["completions"][0]["value"], "service\": ");
+    assert_eq!(res["completions"][0]["detail"], "string");
+
+    Ok(())
+}
+
+#[test]
+fn test_command_line_round_trip() -> Result<()> {
+    let command = parse(
+        r#"-H 'authorization: Bearer it'\''s' -d '{"a": "b c"}' localhost:50051 pkg.Svc/Call"#,
+    )?;
+    let line = command.to_command_line();
+
+    assert_eq!(
+        line,
+        r#"-H 'authorization: Bearer it'\''s' -d '{"a": "b c"}' localhost:50051 pkg.Svc/Call"#
+    );
+    assert_eq!(parse(&line)?, command);
+
+    Ok(())
+}
+
+#[test]
+fn test_unsupported_flags() -> Result<()> {
+    assert_eq!(
+        unsupported_flag(&parse("-plaintext -d {} localhost:50051 list")?),
+        None
+    );
+    assert_eq!(
+        unsupported_flag(&parse("-proto a.proto localhost:50051 list")?),
+        Some("-proto".to_string())
+    );
+    assert_eq!(
+        unsupported_flag(&parse("-format text localhost:50051 list")?),
+        Some("-format text".to_string())
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_tokenize_quotes_and_escapes() -> Result<()> {
+    let tokens = tokenize(
+        r#"-H 'authorization: Bearer a b' -d "{\"name\": \"a b\"}" it\'s \
+        "$HOME" '\n'"#,
+    )?;
+
+    assert_eq!(
+        tokens.args,
+        vec![
+            "-H",
+            "authorization: Bearer a b",
+            "-d",
+            r#"{"name": "a b"}"#,
+            "it's",
+            "$HOME",
+            r"\n",
+        ]
+    );
+    assert!(tokenize(r#"-d '{"name": "a}"#).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_parse_heredoc_command() -> Result<()> {
+    let command = parse(
+        "grpcurl -plaintext -d @ localhost:50051 pkg.Greeter/SayHello <<-'EOF'\n\t{\"name\": \"a b\"}\n\t{\"name\": \"c\"}\n\tEOF\n",
+    )?;
+
+    assert_eq!(command.verb, Verb::Invoke);
+    assert_eq!(command.address.as_deref(), Some("localhost:50051"));
+    assert_eq!(command.symbol.as_deref(), Some("pkg.Greeter/SayHello"));
+    assert_eq!(
+        command.data.as_deref(),
+        Some("{\"name\": \"a b\"}\n{\"name\": \"c\"}")
+    );
+    assert_eq!(command.service().as_deref(), Some("pkg.Greeter"));
+    assert_eq!(command.method(), "SayHello");
+    assert_eq!(command.summary(), "localhost:50051 pkg.Greeter/SayHello");
+
+    let here_string = parse(r#"-d @ localhost:50051 pkg.Greeter.SayHello <<< '{"name": "a"}'"#)?;
+    assert_eq!(here_string.data.as_deref(), Some(r#"{"name": "a"}"#));
+    assert_eq!(here_string.method(), "SayHello");
+
+    let list = parse("-protoset greeter.protoset list pkg.Greeter")?;
+    assert_eq!(list.verb, Verb::List);
+    assert_eq!(list.address, None);
+    assert_eq!(list.flag("protoset"), Some("greeter.protoset"));
+    assert_eq!(
+        list.to_args(),
+        vec!["-protoset", "greeter.protoset", "list", "pkg.Greeter"]
+    );
+
+    assert!(parse("-d @ localhost:50051 pkg.Greeter/SayHello").is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_call_results_are_escaped() {
+    let failed = GrpcResponse {
+        status: "INTERNAL".to_string(),
+        message: Some("<img src=x onerror=alert(1)>".to_string()),
+        ..GrpcResponse::default()
+    };
+    let html = render_call_result(&failed, "");
+    assert!(html.contains("gRPC call failed - &lt;img src=x onerror=alert(1)&gt;"));
+    assert!(!html.contains("<img"));
+
+    let ok = GrpcResponse {
+        status: "OK".to_string(),
+        body: vec!["{\"name\": \"</textarea><script>\"}".to_string()],
+        ..GrpcResponse::default()
+    };
+    let html = render_call_result(&ok, "");
+    assert!(!html.contains("<script>"));
+}
@@ -0,0 +1,24 @@
+#!/bin/sh
+# Downloads the third party scripts into public/vendor/ so gURL runs without internet access.
+# Commit the files and rebuild, the binary embeds them and stops using the CDNs
+
+set -e
+
+cd "$(dirname "$0")/public/vendor"
+
+fetch() {
+    curl -fsSL "$2" -o "$1"
+    echo "$1 sha384-$(openssl dgst -sha384 -binary "$1" | openssl base64 -A)"
+}
+
+fetch htmx-1.9.11.min.js https://unpkg.com/htmx.org@1.9.11
+fetch json-enc-1.9.11.js https://unpkg.com/htmx.org@1.9.11/dist/ext/json-enc.js
+fetch beautify-html-1.15.1.min.js https://cdnjs.cloudflare.com/ajax/libs/js-beautify/1.15.1/beautify-html.min.js
+
+# Same file the CDN tag pinned
+htmx="sha384-$(openssl dgst -sha384 -binary htmx-1.9.11.min.js | openssl base64 -A)"
+if [ "$htmx" != "sha384-0gxUXCCR8yv9FM2b+U3FDbsKthCI66oH5IA9fHppQq9DDMHuMauqq1ZHBpJxQ0J0" ]; then
+    echo "htmx-1.9.11.min.js doesn't match its pinned hash" >&2
+    rm htmx-1.9.11.min.js
+    exit 1
+fi